@@ -0,0 +1,243 @@
+use expr_parser::{Associativity, BinaryCode, Expr, RuleSet, Signature, Strictness, UnaryCode};
+use std::io::Read;
+use std::process::exit;
+use stmt_parser::{Bytecode, Dialect, Program};
+
+const USAGE: &str = "\
+Usage: planggy <COMMAND> [FILE]
+
+Reads the program from FILE, or from the standard input if it is omitted.
+
+Commands:
+    translate --to <en|hu>    Re-emit the program with the keywords of a dialect
+    declare [--lang <en|hu>]  Infer the types of the variables and write them into the VARIABLES line
+    run FILE                  Execute the program or its bytecode, READ takes lines from the standard input
+    compile FILE OUT          Compile the program to bytecode and write it to OUT
+    simplify [--relaxed]      Simplify an expression step by step
+    derive VAR                Differentiate an expression with respect to VAR
+    rewrite RULES             Rewrite an expression with the rules of the RULES file, one per line
+    operators [--lang <en|hu>]  List the operators with their precedence, associativity and types";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("translate") => translate(&args[1..]),
+        Some("declare") => declare(&args[1..]),
+        Some("run") => run(&args[1..]),
+        Some("compile") => compile(&args[1..]),
+        Some("simplify") => simplify(&args[1..]),
+        Some("derive") => derive(&args[1..]),
+        Some("rewrite") => rewrite(&args[1..]),
+        Some("operators") => operators(&args[1..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            return;
+        }
+        _ => Err(USAGE.to_string()),
+    };
+    match result {
+        Ok(out) => print!("{}", out),
+        Err(e) => {
+            eprintln!("{}", e);
+            exit(1);
+        }
+    }
+}
+
+fn translate(args: &[String]) -> Result<String, String> {
+    let mut to = None;
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--to" => to = Some(args.next().ok_or(USAGE)?.parse::<Dialect>()?),
+            _ if file.is_none() => file = Some(arg.as_str()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let to = to.ok_or(USAGE)?;
+    stmt_parser::translate(&read_source(file)?, to).map_err(|e| e.to_string())
+}
+
+fn declare(args: &[String]) -> Result<String, String> {
+    let mut lang = Dialect::English;
+    let mut file = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--lang" => lang = args.next().ok_or(USAGE)?.parse::<Dialect>()?,
+            _ if file.is_none() => file = Some(arg.as_str()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    stmt_parser::declare_variables(&read_source(file)?, lang).map_err(|e| e.to_string())
+}
+
+fn run(args: &[String]) -> Result<String, String> {
+    let path = match args {
+        [path] => path.as_str(),
+        _ => return Err(USAGE.to_string()),
+    };
+    let bytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+    let bytecode = if Bytecode::is_bytecode(&bytes) {
+        Bytecode::read_from(&mut bytes.as_slice())
+    } else {
+        let source = String::from_utf8(bytes).map_err(|e| format!("{}: {}", path, e))?;
+        Ok(parse_program(&source, path)?.compile())
+    };
+    let bytecode = bytecode.map_err(|e| format!("{}: {}", path, e))?;
+    let stdin = std::io::stdin();
+    let stdout = std::io::stdout();
+    bytecode
+        .run(&mut stdin.lock(), &mut stdout.lock())
+        .map_err(|e| format!("{}: {}", path, e))?;
+    Ok(String::new())
+}
+
+fn compile(args: &[String]) -> Result<String, String> {
+    let (path, out) = match args {
+        [path, out] => (path.as_str(), out.as_str()),
+        _ => return Err(USAGE.to_string()),
+    };
+    let bytecode = parse_program(&read_source(Some(path))?, path)?.compile();
+    let mut bytes = Vec::new();
+    bytecode
+        .write_to(&mut bytes)
+        .and_then(|()| std::fs::write(out, bytes))
+        .map_err(|e| format!("{}: {}", out, e))?;
+    Ok(String::new())
+}
+
+fn parse_program(source: &str, path: &str) -> Result<Program<Expr>, String> {
+    Program::parse(source)
+        .and_then(Program::parse_exprs)
+        .map_err(|e| format!("{}: {}", path, e))
+}
+
+fn simplify(args: &[String]) -> Result<String, String> {
+    let mut strictness = Strictness::Strict;
+    let mut file = None;
+    for arg in args {
+        match arg.as_str() {
+            "--relaxed" => strictness = Strictness::Relaxed,
+            _ if file.is_none() => file = Some(arg.as_str()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let source = read_source(file)?;
+    let e = Expr::parse(source.trim()).map_err(|e| e.to_string())?;
+    let (e, steps) = e.simplify_steps(strictness);
+    let mut out = String::new();
+    for step in steps {
+        out += &format!("{}\n", step);
+    }
+    Ok(out + &format!("{}\n", e))
+}
+
+fn derive(args: &[String]) -> Result<String, String> {
+    let (var, file) = match args {
+        [var] => (var.as_str(), None),
+        [var, file] => (var.as_str(), Some(file.as_str())),
+        _ => return Err(USAGE.to_string()),
+    };
+    let source = read_source(file)?;
+    let e = Expr::parse(source.trim()).map_err(|e| e.to_string())?;
+    let d = e.derivative(&var.into()).map_err(|e| e.to_string())?;
+    Ok(format!("{}\n", d))
+}
+
+/// The number of rewrites after which `rewrite` gives up.
+const STEP_LIMIT: usize = 1000;
+
+fn rewrite(args: &[String]) -> Result<String, String> {
+    let (rules_path, file) = match args {
+        [rules] => (rules.as_str(), None),
+        [rules, file] => (rules.as_str(), Some(file.as_str())),
+        _ => return Err(USAGE.to_string()),
+    };
+    let rules = read_source(Some(rules_path))?;
+    let rules = RuleSet::parse(&rules).map_err(|e| format!("{}: {}", rules_path, e))?;
+    let source = read_source(file)?;
+    let e = Expr::parse(source.trim()).map_err(|e| e.to_string())?;
+    let rewritten = rules.rewrite(e, STEP_LIMIT);
+    let mut out = String::new();
+    for step in rewritten.steps() {
+        out += &format!("{}\n", step);
+    }
+    if !rewritten.is_complete() {
+        return Err(format!("{}gave up after {} rewrites", out, STEP_LIMIT));
+    }
+    Ok(out + &format!("{}\n", rewritten.expr()))
+}
+
+fn operators(args: &[String]) -> Result<String, String> {
+    let lang = match args {
+        [] => Dialect::English,
+        [flag, lang] if flag == "--lang" => lang.parse::<Dialect>()?,
+        _ => return Err(USAGE.to_string()),
+    };
+    let mut out = format!(
+        "{:<36} {:>10}  {:<13}  {}\n",
+        "OPERATOR", "PRECEDENCE", "ASSOCIATIVITY", "TYPES"
+    );
+    for code in UnaryCode::ALL.iter().filter(|code| !code.is_wrapping()) {
+        let (precedence, associativity) = (code.precedence(), code.associativity());
+        out += &operator(
+            code.spellings(lang),
+            precedence,
+            associativity,
+            &code.signatures(),
+            lang,
+        );
+    }
+    let mut binary = BinaryCode::ALL.to_vec();
+    binary.sort_by_key(|code| std::cmp::Reverse(code.precedence()));
+    for code in binary {
+        let (precedence, associativity) = (code.precedence(), code.associativity());
+        out += &operator(
+            code.spellings(lang),
+            precedence,
+            associativity,
+            &code.signatures(),
+            lang,
+        );
+    }
+    Ok(out)
+}
+
+/// A line of the operator list, higher precedences bind tighter.
+fn operator(
+    spellings: &[&str],
+    precedence: u8,
+    associativity: Associativity,
+    signatures: &[Signature],
+    lang: Dialect,
+) -> String {
+    let signatures: Vec<_> = signatures
+        .iter()
+        .map(|signature| {
+            let params: Vec<_> = signature.params().iter().map(|t| t.name(lang)).collect();
+            format!("{} -> {}", params.join(", "), signature.ret().name(lang))
+        })
+        .collect();
+    format!(
+        "{:<36} {:>10}  {:<13}  {}\n",
+        spellings.join(" "),
+        precedence,
+        associativity.to_string(),
+        signatures.join("; ")
+    )
+}
+
+fn read_source(file: Option<&str>) -> Result<String, String> {
+    match file {
+        Some(path) => std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            let mut source = String::new();
+            std::io::stdin()
+                .read_to_string(&mut source)
+                .map_err(|e| e.to_string())?;
+            Ok(source)
+        }
+    }
+}
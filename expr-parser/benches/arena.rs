@@ -0,0 +1,49 @@
+//! Compares the boxed `Expr` with the `ExprArena` on a large generated expression.
+
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use expr_parser::{Expr, ExprArena};
+
+/// A sum of `terms` products over a handful of variables, like generated code.
+fn source(terms: usize) -> String {
+    let vars = ["alpha", "beta", "gamma", "delta", "epsilon"];
+    (0..terms)
+        .map(|i| {
+            let (a, b) = (vars[i % vars.len()], vars[(i * 7 + 3) % vars.len()]);
+            format!("{} * ({} - {}) / SIN {}", a, b, i, a)
+        })
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+fn bench(c: &mut Criterion) {
+    let source = source(2000);
+    let e = Expr::parse(&source).unwrap();
+    let mut arena = ExprArena::new();
+    arena.parse(&source).unwrap();
+
+    let mut parse = c.benchmark_group("parse");
+    parse.bench_function("boxed", |b| {
+        b.iter(|| Expr::parse(black_box(&source)).unwrap())
+    });
+    parse.bench_function("arena", |b| {
+        b.iter(|| ExprArena::new().parse(black_box(&source)).unwrap())
+    });
+    parse.finish();
+
+    let mut clone = c.benchmark_group("clone");
+    clone.bench_function("boxed", |b| b.iter(|| black_box(&e).clone()));
+    clone.bench_function("arena", |b| b.iter(|| black_box(&arena).clone()));
+    clone.finish();
+
+    let mut drop = c.benchmark_group("drop");
+    drop.bench_function("boxed", |b| {
+        b.iter_batched(|| e.clone(), std::mem::drop, BatchSize::SmallInput)
+    });
+    drop.bench_function("arena", |b| {
+        b.iter_batched(|| arena.clone(), std::mem::drop, BatchSize::SmallInput)
+    });
+    drop.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,11 +1,11 @@
-use expr_parser::Expr;
+use expr_parser::{Expr, Value};
 
 fn main() {
-    let result = Expr::parse("1 + 2");
+    let result = Expr::parse("1 + 2").unwrap();
 
-    let result = result.unwrap().unwrap_binary();
+    let result = result.as_binary().expect("not a binary expression");
     let op = result.op_code();
-    let lhs = result.lhs().clone().unwrap_value().unwrap_int();
-    let rhs = result.rhs().clone().unwrap_value().unwrap_int();
+    let lhs = result.lhs().as_value().and_then(Value::as_int).unwrap();
+    let rhs = result.rhs().as_value().and_then(Value::as_int).unwrap();
     print!("{} {} {}", lhs, op, rhs);
 }
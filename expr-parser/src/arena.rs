@@ -0,0 +1,268 @@
+//! A compact representation of expressions, for large generated programs.
+//!
+//! The nodes of an [`ExprArena`] are stored in a single `Vec` and refer to
+//! their children by [`ExprId`], so building, cloning and dropping a tree
+//! allocates once for all of its nodes instead of once per node. Variable
+//! names are interned into [`Symbol`]s, so every name is stored once and
+//! compared as an integer.
+
+use super::pratt::Builder;
+use super::{BinaryCode, Expr, Operator, OperatorCode, OperatorTable, UnaryCode, Value, VarName};
+use std::collections::HashMap;
+
+/// A node of an [`ExprArena`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct ExprId(u32);
+
+/// An interned variable name of a [`SymbolTable`].
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// The names of the variables of an [`ExprArena`], each stored once.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct SymbolTable {
+    names: Vec<VarName>,
+    symbols: HashMap<VarName, Symbol>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        SymbolTable::default()
+    }
+
+    /// The symbol of `name`, which is added to the table if it is new.
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(&symbol) = self.symbols.get(name) {
+            return symbol;
+        }
+        let symbol = Symbol(self.names.len() as u32);
+        self.names.push(name.into());
+        self.symbols.insert(name.into(), symbol);
+        symbol
+    }
+
+    /// The symbol of `name`, if it was interned.
+    pub fn get(&self, name: &str) -> Option<Symbol> {
+        self.symbols.get(name).copied()
+    }
+
+    /// The name of a symbol of this table.
+    ///
+    /// # Panics
+    ///
+    /// If the symbol is of another table.
+    pub fn resolve(&self, symbol: Symbol) -> &VarName {
+        &self.names[symbol.0 as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+/// The variants of [`Expr`], with their children in the same arena.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    /// A literal, never a [`Value::VarName`], those are [`Node::Var`]s.
+    Value(Value),
+    Var(Symbol),
+    Unary(UnaryCode, ExprId),
+    Binary(BinaryCode, ExprId, ExprId),
+    Index(ExprId, ExprId),
+    Slice(ExprId, ExprId, ExprId),
+}
+
+/// The nodes of any number of expressions, see the [module documentation](self).
+///
+/// Nodes are only ever added, and always after their children.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ExprArena {
+    nodes: Vec<Node>,
+    symbols: SymbolTable,
+}
+
+impl ExprArena {
+    pub fn new() -> Self {
+        ExprArena::default()
+    }
+
+    /// Adds a node, whose children must be in the arena already.
+    pub fn push(&mut self, node: Node) -> ExprId {
+        self.nodes.push(node);
+        ExprId(self.nodes.len() as u32 - 1)
+    }
+
+    /// # Panics
+    ///
+    /// If the node is of another arena.
+    pub fn node(&self, id: ExprId) -> &Node {
+        &self.nodes[id.0 as usize]
+    }
+
+    pub fn symbols(&self) -> &SymbolTable {
+        &self.symbols
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.symbols.intern(name)
+    }
+
+    /// The number of nodes.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// Parses an expression into the arena, like [`Expr::parse`] does into boxes.
+    ///
+    /// A failed parse may leave nodes behind, which no `ExprId` refers to.
+    pub fn parse<'s>(
+        &mut self,
+        s: &'s str,
+    ) -> Result<ExprId, lalrpop_util::ParseError<usize, lexer::Token<'s>, &'static str>> {
+        OperatorTable::shared().parse_with(s, self)
+    }
+
+    /// Copies a boxed expression into the arena.
+    pub fn insert(&mut self, e: &Expr) -> ExprId {
+        let node = match e {
+            Expr::Value(Value::VarName(name)) => Node::Var(self.intern(name.as_str())),
+            Expr::Value(x) => Node::Value(x.clone()),
+            Expr::Unary(x) => Node::Unary(*x.op_code(), self.insert(x.rhs())),
+            Expr::Binary(x) => {
+                let lhs = self.insert(x.lhs());
+                Node::Binary(*x.op_code(), lhs, self.insert(x.rhs()))
+            }
+            Expr::Index(x, at) => {
+                let x = self.insert(x);
+                Node::Index(x, self.insert(at))
+            }
+            Expr::Slice(x, start, end) => {
+                let x = self.insert(x);
+                let start = self.insert(start);
+                Node::Slice(x, start, self.insert(end))
+            }
+        };
+        self.push(node)
+    }
+
+    /// The expression of a node as a boxed tree.
+    pub fn to_expr(&self, id: ExprId) -> Expr {
+        match self.node(id) {
+            Node::Value(x) => x.clone().into(),
+            Node::Var(symbol) => self.symbols.resolve(*symbol).clone().to_expr(),
+            Node::Unary(op, x) => (*op, self.to_expr(*x)).into(),
+            Node::Binary(op, lhs, rhs) => (*op, self.to_expr(*lhs), self.to_expr(*rhs)).into(),
+            Node::Index(x, at) => Expr::Index(self.to_expr(*x).boxed(), self.to_expr(*at).boxed()),
+            Node::Slice(x, start, end) => Expr::Slice(
+                self.to_expr(*x).boxed(),
+                self.to_expr(*start).boxed(),
+                self.to_expr(*end).boxed(),
+            ),
+        }
+    }
+}
+
+/// Builds the nodes the Pratt parser recognizes into the arena.
+impl Builder for ExprArena {
+    type Node = ExprId;
+
+    fn value(&mut self, x: Value) -> ExprId {
+        let node = match x {
+            Value::VarName(name) => Node::Var(self.intern(name.as_str())),
+            x => Node::Value(x),
+        };
+        self.push(node)
+    }
+
+    fn wrap(&mut self, code: UnaryCode, x: ExprId) -> ExprId {
+        self.push(Node::Unary(code, x))
+    }
+
+    /// Custom operators build boxed expressions, which are copied in.
+    fn prefix(&mut self, op: &Operator, x: ExprId) -> ExprId {
+        match op.code() {
+            OperatorCode::Unary(code) => self.push(Node::Unary(code, x)),
+            _ => {
+                let e = op.apply_prefix(self.to_expr(x));
+                self.insert(&e)
+            }
+        }
+    }
+
+    fn infix(&mut self, op: &Operator, lhs: ExprId, rhs: ExprId) -> ExprId {
+        match op.code() {
+            OperatorCode::Binary(code) => self.push(Node::Binary(code, lhs, rhs)),
+            _ => {
+                let e = op.apply_infix(self.to_expr(lhs), self.to_expr(rhs));
+                self.insert(&e)
+            }
+        }
+    }
+
+    fn index(&mut self, x: ExprId, at: ExprId) -> ExprId {
+        self.push(Node::Index(x, at))
+    }
+
+    fn slice(&mut self, x: ExprId, start: ExprId, end: ExprId) -> ExprId {
+        self.push(Node::Slice(x, start, end))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt) => {
+            #[test]
+            fn $test_name() {
+                let mut arena = ExprArena::new();
+                let id = arena.parse($in).unwrap();
+                let e = Expr::parse($in).unwrap();
+                assert_eq!(arena.to_expr(id), e);
+                let mut copy = ExprArena::new();
+                let inserted = copy.insert(&e);
+                assert_eq!(copy, arena);
+                assert_eq!(inserted, id);
+            }
+        };
+    }
+
+    test!(arithmetic: "1 + 2.5 * x - -y ^ 2");
+    test!(logic: "NOT a AND (b OR c = 'x') ÉS d");
+    test!(strings: "UPPER s[0] + s[1:n] @ \"abc\"");
+    test!(functions: "|SIN x| / ROUND (EXP y)");
+
+    #[test]
+    fn interning() {
+        let mut arena = ExprArena::new();
+        let a = arena.parse("x * x + y").unwrap();
+        let b = arena.parse("y - x").unwrap();
+        assert_eq!(arena.symbols().len(), 2);
+        let x = arena.symbols().get("x").unwrap();
+        assert_eq!(arena.symbols().resolve(x), &VarName::from("x"));
+        assert_eq!(arena.to_expr(a).to_string(), "x * x + y");
+        assert_eq!(arena.to_expr(b).to_string(), "y - x");
+        assert_eq!(
+            arena.node(b),
+            &Node::Binary(BinaryCode::Sub, ExprId(5), ExprId(6))
+        );
+        assert_eq!(arena.node(ExprId(6)), &Node::Var(x));
+    }
+
+    #[test]
+    fn parse_error() {
+        let mut arena = ExprArena::new();
+        assert!(arena.parse("x +").is_err());
+        let id = arena.parse("1").unwrap();
+        assert_eq!(arena.to_expr(id), Expr::from(1));
+    }
+}
@@ -1,42 +1,140 @@
-use super::Expr;
-use derive_more::{Constructor, Display, From, Into};
+use super::{Associativity, Dialect, Expr, Signature, Type};
+use derive_more::{Constructor, From, Into};
+use std::fmt;
 
-#[derive(Debug, PartialEq, Copy, Clone, Hash, Display)]
+/// The binary operators, described by their methods: the parser, the
+/// formatter and the type checker all follow them.
+#[derive(Debug, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinaryCode {
-    #[display(fmt = "^")]
     Pow,
-    #[display(fmt = "@")]
     Search,
-    #[display(fmt = "+")]
     Add,
-    #[display(fmt = "-")]
     Sub,
-    #[display(fmt = "*")]
     Mul,
-    #[display(fmt = "MOD")]
     Mod,
-    #[display(fmt = "/")]
     Div,
-    #[display(fmt = "DIV")]
     IntDiv,
-    #[display(fmt = "=")]
     Eq,
-    #[display(fmt = "/=")]
     Ne,
-    #[display(fmt = ">")]
     Gt,
-    #[display(fmt = ">=")]
     Ge,
-    #[display(fmt = "<")]
     Lt,
-    #[display(fmt = "<=")]
     Le,
-    #[display(fmt = "AND")]
     And,
-    #[display(fmt = "OR")]
     Or,
 }
 
+impl BinaryCode {
+    /// Every operator. Bytecode files store operators by their position here, so
+    /// new ones go at the end.
+    pub const ALL: [BinaryCode; 16] = {
+        use BinaryCode::*;
+        [
+            Pow, Search, Add, Sub, Mul, Mod, Div, IntDiv, Eq, Ne, Gt, Ge, Lt, Le, And, Or,
+        ]
+    };
+
+    /// How tightly the operator binds in the standard [`OperatorTable`],
+    /// higher precedences bind tighter.
+    ///
+    /// [`OperatorTable`]: crate::OperatorTable
+    pub fn precedence(self) -> u8 {
+        use BinaryCode::*;
+        match self {
+            Pow => 6,
+            Search => 5,
+            Mul | Div | IntDiv | Mod => 4,
+            Add | Sub => 3,
+            Eq | Ne | Gt | Ge | Lt | Le => 2,
+            And | Or => 1,
+        }
+    }
+
+    /// Only `^` is right-associative, `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+    pub fn associativity(self) -> Associativity {
+        match self {
+            BinaryCode::Pow => Associativity::Right,
+            _ => Associativity::Left,
+        }
+    }
+
+    /// The number of operands, always 2.
+    pub fn arity(self) -> usize {
+        2
+    }
+
+    /// The spellings of the operator in `dialect`, the canonical one first.
+    /// Hungarian words are listed with and without accents, the lexer also
+    /// takes them with any accents that fold to the same letters.
+    pub fn spellings(self, dialect: Dialect) -> &'static [&'static str] {
+        use BinaryCode::*;
+        use Dialect::*;
+        match (self, dialect) {
+            (Pow, _) => &["^"],
+            (Search, _) => &["@"],
+            (Add, _) => &["+"],
+            (Sub, _) => &["-"],
+            (Mul, _) => &["*"],
+            (Mod, _) => &["MOD"],
+            (Div, _) => &["/"],
+            (IntDiv, _) => &["DIV"],
+            (Eq, _) => &["="],
+            (Ne, _) => &["/="],
+            (Gt, _) => &[">"],
+            (Ge, _) => &[">="],
+            (Lt, _) => &["<"],
+            (Le, _) => &["<="],
+            (And, English) => &["AND", "and"],
+            (And, Hungarian) => &["ÉS", "és", "ES", "es"],
+            (Or, English) => &["OR", "or"],
+            (Or, Hungarian) => &["VAGY", "vagy"],
+        }
+    }
+
+    /// The type of the result, `None` if the operator doesn't take operands
+    /// of these types.
+    ///
+    /// Follows the rules of [`crate::numeric`], so the result of evaluating the
+    /// operator is of this type unless it is an error.
+    pub fn result_type(self, l: &Type, r: &Type) -> Option<Type> {
+        use BinaryCode::*;
+        use Type::*;
+        let numbers = l.is_number() && r.is_number();
+        Some(match self {
+            Add | Sub | Mul | Pow if (l, r) == (&Int, &Int) => Int,
+            Add | Sub | Mul | Pow | Div if numbers => Float,
+            Add if l.is_text() && r.is_text() => String,
+            IntDiv | Mod if (l, r) == (&Int, &Int) => Int,
+            Search if *l == String && r.is_text() => Bool,
+            Eq | Ne if (l == r && l.is_scalar()) || numbers => Bool,
+            Lt | Le | Gt | Ge if (l == r && l.is_text()) || numbers => Bool,
+            And | Or if (l, r) == (&Bool, &Bool) => Bool,
+            _ => return None,
+        })
+    }
+
+    /// The operand and result types of every overload of the operator.
+    pub fn signatures(self) -> Vec<Signature> {
+        let mut signatures = Vec::new();
+        for l in &Type::SCALARS {
+            for r in &Type::SCALARS {
+                if let Some(ret) = self.result_type(l, r) {
+                    signatures.push(Signature::new(vec![l.clone(), r.clone()], ret));
+                }
+            }
+        }
+        signatures
+    }
+}
+
+/// The canonical English spelling.
+impl fmt::Display for BinaryCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.spellings(Dialect::English)[0])
+    }
+}
+
 #[derive(Debug, PartialEq, From, Clone, Into, Constructor)]
 pub struct Binary(BinaryCode, Box<Expr>, Box<Expr>);
 
@@ -53,11 +151,38 @@ impl Binary {
         &self.2
     }
 
+    pub fn lhs_mut(&mut self) -> &mut Expr {
+        &mut self.1
+    }
+
+    pub fn rhs_mut(&mut self) -> &mut Expr {
+        &mut self.2
+    }
+
     pub fn to_expr(self) -> Expr {
         self.into()
     }
 }
 
+/// An operand of the same precedence has to be wrapped in parentheses on the
+/// side the operator doesn't associate to.
+impl fmt::Display for Binary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let code = *self.op_code();
+        let (lhs, rhs) = (self.lhs().precedence(), self.rhs().precedence());
+        let right = code.associativity() == Associativity::Right;
+        self.lhs().fmt_operand(
+            f,
+            lhs < code.precedence() || lhs == code.precedence() && right,
+        )?;
+        write!(f, " {} ", code)?;
+        self.rhs().fmt_operand(
+            f,
+            rhs < code.precedence() || rhs == code.precedence() && !right,
+        )
+    }
+}
+
 impl From<(BinaryCode, Expr, Expr)> for Binary {
     fn from((code, lhs, rhs): (BinaryCode, Expr, Expr)) -> Self {
         (code, lhs.boxed(), rhs.boxed()).into()
@@ -69,8 +194,7 @@ macro_rules! test {
     ($test_name:ident: $in:tt -> $lhs:expr ; $code:tt ; $rhs:expr) => {
         #[test]
         fn $test_name() {
-            let parser = crate::Parser::new();
-            let result = parser.parse($in).unwrap();
+            let result = crate::Expr::parse($in).unwrap();
             let expected = Binary::from((crate::BinaryCode::$code, Box::new($lhs), Box::new($rhs)));
             assert_eq!(result, expected.to_expr())
         }
@@ -83,5 +207,69 @@ mod tests {
 
     test!(add_1_1: "1 + 1" -> 1.into() ; Add ; 1.into());
     test!(mul_1_1: "1 * 1" -> 1.into() ; Mul ; 1.into());
+    test!(div_1_1: "1 / 1" -> 1.into() ; Div ; 1.into());
     test!(and_true_true: "TRUE AND TRUE" -> true.into() ; And ; true.into());
+
+    #[test]
+    fn display_codes_parse_back() {
+        for &code in &BinaryCode::ALL {
+            let expected = Binary::from((code, Expr::from(1), Expr::from(2)));
+            assert_eq!(
+                crate::Expr::parse(&expected.to_string()),
+                Ok(expected.to_expr())
+            );
+        }
+    }
+
+    #[test]
+    fn spellings_parse() {
+        for &code in &BinaryCode::ALL {
+            for &dialect in &[Dialect::English, Dialect::Hungarian] {
+                for spelling in code.spellings(dialect) {
+                    let source = format!("1 {} 2", spelling);
+                    let expected = Binary::from((code, Expr::from(1), Expr::from(2)));
+                    assert_eq!(crate::Expr::parse(&source), Ok(expected.to_expr()));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lexer_spellings_are_listed() {
+        for &code in &BinaryCode::ALL {
+            let listed = [
+                code.spellings(Dialect::English),
+                code.spellings(Dialect::Hungarian),
+            ]
+            .concat();
+            if let Some(keyword) = lexer::Keyword::parse(listed[0]) {
+                for spelling in keyword.spellings() {
+                    assert!(listed.contains(spelling), "{} of {:?}", spelling, code);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn signatures() {
+        use Type::*;
+        let signature = |params: &[Type], ret| Signature::new(params.to_vec(), ret);
+        assert_eq!(
+            BinaryCode::Search.signatures(),
+            vec![
+                signature(&[String, Char], Bool),
+                signature(&[String, String], Bool)
+            ]
+        );
+        assert_eq!(
+            BinaryCode::Pow.signatures(),
+            vec![
+                signature(&[Int, Int], Int),
+                signature(&[Int, Float], Float),
+                signature(&[Float, Int], Float),
+                signature(&[Float, Float], Float),
+            ]
+        );
+        assert_eq!(BinaryCode::Eq.signatures().len(), 7);
+    }
 }
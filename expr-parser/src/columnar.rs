@@ -0,0 +1,560 @@
+//! Evaluation of an expression over columns of values, for filtering tables.
+//!
+//! Every operator is applied to whole columns, one after the other, rather than
+//! evaluating the expression row by row. The common operators on numbers,
+//! strings and booleans have loops over typed slices, the others apply the
+//! operator of [`Value`] to every row of their operands.
+
+use super::eval::holds;
+use super::{BinaryCode, Env, EvalError, Expr, TFloat, TInt, Type, UnaryCode, Value, VarName};
+use derive_more::Display;
+use std::borrow::Cow;
+use std::collections::HashMap;
+
+/// The values of a variable, or of an expression, in every row.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Column<'a> {
+    Bool(Cow<'a, [bool]>),
+    Int(Cow<'a, [TInt]>),
+    Float(Cow<'a, [TFloat]>),
+    Char(Cow<'a, [char]>),
+    String(Cow<'a, [String]>),
+}
+
+macro_rules! column_from {
+    ($($variant:ident($t:ty)),*) => {
+        $(
+            impl<'a> From<&'a [$t]> for Column<'a> {
+                fn from(x: &'a [$t]) -> Self {
+                    Column::$variant(Cow::Borrowed(x))
+                }
+            }
+
+            impl From<Vec<$t>> for Column<'_> {
+                fn from(x: Vec<$t>) -> Self {
+                    Column::$variant(Cow::Owned(x))
+                }
+            }
+        )*
+    };
+}
+
+column_from!(
+    Bool(bool),
+    Int(TInt),
+    Float(TFloat),
+    Char(char),
+    String(String)
+);
+
+impl<'a> Column<'a> {
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Bool(x) => x.len(),
+            Column::Int(x) => x.len(),
+            Column::Float(x) => x.len(),
+            Column::Char(x) => x.len(),
+            Column::String(x) => x.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The type of the values.
+    pub fn type_of(&self) -> Type {
+        match self {
+            Column::Bool(_) => Type::Bool,
+            Column::Int(_) => Type::Int,
+            Column::Float(_) => Type::Float,
+            Column::Char(_) => Type::Char,
+            Column::String(_) => Type::String,
+        }
+    }
+
+    /// The value in the `row`th row.
+    ///
+    /// # Panics
+    ///
+    /// If the column has no such row.
+    pub fn get(&self, row: usize) -> Value {
+        match self {
+            Column::Bool(x) => x[row].into(),
+            Column::Int(x) => x[row].into(),
+            Column::Float(x) => x[row].into(),
+            Column::Char(x) => x[row].into(),
+            Column::String(x) => x[row].clone().into(),
+        }
+    }
+
+    pub fn as_bools(&self) -> Option<&[bool]> {
+        match self {
+            Column::Bool(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Borrows the column, without copying its values.
+    fn view(&self) -> Column<'_> {
+        match self {
+            Column::Bool(x) => Column::Bool(Cow::Borrowed(x)),
+            Column::Int(x) => Column::Int(Cow::Borrowed(x)),
+            Column::Float(x) => Column::Float(Cow::Borrowed(x)),
+            Column::Char(x) => Column::Char(Cow::Borrowed(x)),
+            Column::String(x) => Column::String(Cow::Borrowed(x)),
+        }
+    }
+
+    fn to_owned_column(&self) -> Column<'static> {
+        match self {
+            Column::Bool(x) => x.to_vec().into(),
+            Column::Int(x) => x.to_vec().into(),
+            Column::Float(x) => x.to_vec().into(),
+            Column::Char(x) => x.to_vec().into(),
+            Column::String(x) => x.to_vec().into(),
+        }
+    }
+
+    /// The numbers of the column as `Float`s.
+    fn floats(&self) -> Option<Cow<'_, [TFloat]>> {
+        match self {
+            Column::Int(x) => Some(x.iter().map(|&i| i.into()).collect()),
+            Column::Float(x) => Some(Cow::Borrowed(x)),
+            _ => None,
+        }
+    }
+
+    fn repeat(x: &Value, len: usize) -> Column<'static> {
+        Column::from_values(vec![Some(x.clone()); len])
+    }
+
+    /// Collects the values of the rows, `None` for the rows that were not
+    /// evaluated, which get a value of the type of the others.
+    ///
+    /// The operators give values of one type for operands of one type, so
+    /// the rows of a column never differ in type.
+    fn from_values(values: Vec<Option<Value>>) -> Column<'static> {
+        let mut types = values.iter().flatten().map(Value::type_of);
+        let ty = match types.next() {
+            Some(first) => {
+                debug_assert!(types.all(|t| t == first), "rows of different types");
+                first
+            }
+            None => return Column::Bool(vec![false; values.len()].into()),
+        };
+        macro_rules! collect {
+            ($variant:ident) => {
+                values
+                    .into_iter()
+                    .map(|x| match x {
+                        Some(Value::$variant(x)) => x,
+                        _ => Default::default(),
+                    })
+                    .collect::<Vec<_>>()
+                    .into()
+            };
+        }
+        match ty {
+            Some(Type::Bool) => collect!(Boolean),
+            Some(Type::Int) => collect!(Int),
+            Some(Type::Float) => collect!(Float),
+            Some(Type::Char) => collect!(Char),
+            Some(Type::String) => collect!(String),
+            _ => unreachable!("columns hold scalars"),
+        }
+    }
+}
+
+/// Named columns of the same length, the rows of a table.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Batch<'a> {
+    len: usize,
+    columns: HashMap<VarName, Column<'a>>,
+}
+
+impl<'a> Batch<'a> {
+    /// A batch of `len` rows, without columns.
+    pub fn new(len: usize) -> Self {
+        Batch {
+            len,
+            columns: HashMap::new(),
+        }
+    }
+
+    /// The number of rows.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn get(&self, name: &VarName) -> Option<&Column<'a>> {
+        self.columns.get(name)
+    }
+
+    /// Binds `name` to a column, which must have a value for every row.
+    pub fn set(&mut self, name: VarName, column: Column<'a>) -> Result<(), ColumnError> {
+        if column.len() != self.len {
+            return Err(ColumnError::Length {
+                name,
+                len: column.len(),
+                expected: self.len,
+            });
+        }
+        self.columns.insert(name, column);
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum ColumnError {
+    #[display(fmt = "column `{}` has {} rows instead of {}", name, len, expected)]
+    Length {
+        name: VarName,
+        len: usize,
+        expected: usize,
+    },
+    #[display(fmt = "{} in row {}", error, row)]
+    Row { row: usize, error: EvalError },
+    #[display(fmt = "the condition is not a Bool in every row")]
+    NotABool,
+}
+
+impl Expr {
+    /// Evaluates the expression for every row of `batch`, with the variables
+    /// bound to the columns of the same name.
+    ///
+    /// The result is the same as [`Expr::eval`] gives row by row, `AND` and `OR`
+    /// only evaluate their right operand in the rows they need it. If rows fail,
+    /// one of them is reported, not necessarily the first.
+    pub fn eval_columns<'b>(&self, batch: &'b Batch) -> Result<Column<'b>, ColumnError> {
+        let evaluator = Evaluator {
+            batch,
+            env: Env::new(),
+        };
+        evaluator.eval(self, None)
+    }
+
+    /// The mask of the rows of `batch` the condition holds in.
+    pub fn select(&self, batch: &Batch) -> Result<Vec<bool>, ColumnError> {
+        match self.eval_columns(batch)? {
+            Column::Bool(mask) => Ok(mask.into_owned()),
+            _ if batch.is_empty() => Ok(Vec::new()),
+            _ => Err(ColumnError::NotABool),
+        }
+    }
+}
+
+/// The rows an operand is evaluated in, `None` for every row. The errors
+/// of the other rows are ignored, their values are unused.
+type Live<'l> = Option<&'l [bool]>;
+
+fn is_live(live: Live, row: usize) -> bool {
+    live.is_none_or(|live| live[row])
+}
+
+struct Evaluator<'b, 'a> {
+    batch: &'b Batch<'a>,
+    /// Supplies the random numbers of `RND`.
+    env: Env,
+}
+
+impl<'b> Evaluator<'b, '_> {
+    fn eval(&self, e: &Expr, live: Live) -> Result<Column<'b>, ColumnError> {
+        let len = self.batch.len;
+        match e {
+            Expr::Value(Value::VarName(name)) => match self.batch.get(name) {
+                Some(column) => Ok(column.view()),
+                None => match (0..len).find(|&row| is_live(live, row)) {
+                    Some(row) => Err(ColumnError::Row {
+                        row,
+                        error: EvalError::Unbound(name.clone()),
+                    }),
+                    None => Ok(Column::from_values(vec![None; len])),
+                },
+            },
+            Expr::Value(x) => Ok(Column::repeat(x, len)),
+            Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => self.eval(x.rhs(), live),
+            Expr::Unary(x) => {
+                let rhs = self.eval(x.rhs(), live)?;
+                self.unary(*x.op_code(), &rhs, live)
+            }
+            Expr::Binary(x) if matches!(x.op_code(), BinaryCode::And | BinaryCode::Or) => {
+                let op = *x.op_code();
+                let lhs = self.eval(x.lhs(), live)?;
+                // the rows the lhs does not decide
+                let undecided: Option<Vec<bool>> = lhs.as_bools().map(|a| {
+                    (0..len)
+                        .map(|row| is_live(live, row) && a[row] == (op == BinaryCode::And))
+                        .collect()
+                });
+                let rhs = self.eval(x.rhs(), undecided.as_deref().or(live))?;
+                match (&lhs, &rhs) {
+                    (Column::Bool(a), Column::Bool(b)) => Ok(zip(a, b, |&a, &b| match op {
+                        BinaryCode::And => a && b,
+                        _ => a || b,
+                    })),
+                    _ => self.rows(live, |row| match (op, lhs.get(row)) {
+                        (BinaryCode::And, Value::Boolean(false)) => Ok(false.into()),
+                        (BinaryCode::Or, Value::Boolean(true)) => Ok(true.into()),
+                        (op, lhs) => lhs.binary(op, rhs.get(row)),
+                    }),
+                }
+            }
+            Expr::Binary(x) => {
+                let lhs = self.eval(x.lhs(), live)?;
+                let rhs = self.eval(x.rhs(), live)?;
+                self.binary(*x.op_code(), &lhs, &rhs, live)
+            }
+            Expr::Index(x, at) => {
+                let (x, at) = (self.eval(x, live)?, self.eval(at, live)?);
+                self.rows(live, |row| x.get(row).index(&at.get(row)))
+            }
+            Expr::Slice(x, start, end) => {
+                let x = self.eval(x, live)?;
+                let (start, end) = (self.eval(start, live)?, self.eval(end, live)?);
+                self.rows(live, |row| x.get(row).slice(&start.get(row), &end.get(row)))
+            }
+        }
+    }
+
+    fn unary(&self, op: UnaryCode, x: &Column, live: Live) -> Result<Column<'b>, ColumnError> {
+        use UnaryCode::*;
+        let float = |f: fn(TFloat) -> TFloat| -> Column<'static> {
+            let x = x.floats().expect("the column is numeric");
+            x.iter().map(|&a| f(a)).collect::<Vec<_>>().into()
+        };
+        Ok(match (op, x) {
+            (Not, Column::Bool(a)) => a.iter().map(|a| !a).collect::<Vec<_>>().into(),
+            (Neg, Column::Float(a)) => a.iter().map(|a| -a).collect::<Vec<_>>().into(),
+            (Plus, Column::Int(_)) | (Plus, Column::Float(_)) => x.view().to_owned_column(),
+            (ToFloat, Column::Int(_)) | (ToFloat, Column::Float(_)) => float(|a| a),
+            (Sin, Column::Int(_)) | (Sin, Column::Float(_)) => float(TFloat::sin),
+            (Cos, Column::Int(_)) | (Cos, Column::Float(_)) => float(TFloat::cos),
+            (Tan, Column::Int(_)) | (Tan, Column::Float(_)) => float(TFloat::tan),
+            (Exp, Column::Int(_)) | (Exp, Column::Float(_)) => float(TFloat::exp),
+            (Arctan, Column::Int(_)) | (Arctan, Column::Float(_)) => float(TFloat::atan),
+            _ => return self.rows(live, |row| x.get(row).unary(op, &self.env)),
+        })
+    }
+
+    fn binary(
+        &self,
+        op: BinaryCode,
+        lhs: &Column,
+        rhs: &Column,
+        live: Live,
+    ) -> Result<Column<'b>, ColumnError> {
+        use BinaryCode::*;
+        use Column::*;
+        let checked = |f: fn(TInt, TInt) -> Option<TInt>, a: &[TInt], b: &[TInt]| {
+            let mut out = Vec::with_capacity(a.len());
+            for (row, (&a, &b)) in a.iter().zip(b.iter()).enumerate() {
+                match f(a, b) {
+                    Some(x) => out.push(x),
+                    None if is_live(live, row) => {
+                        let error = EvalError::Overflow(op.to_string());
+                        return Err(ColumnError::Row { row, error });
+                    }
+                    None => out.push(0),
+                }
+            }
+            Ok(Column::from(out))
+        };
+        let comparison = matches!(op, Eq | Ne | Lt | Le | Gt | Ge);
+        match (op, lhs, rhs) {
+            (Add, Int(a), Int(b)) => checked(TInt::checked_add, a, b),
+            (Sub, Int(a), Int(b)) => checked(TInt::checked_sub, a, b),
+            (Mul, Int(a), Int(b)) => checked(TInt::checked_mul, a, b),
+            (_, Int(a), Int(b)) if comparison => Ok(zip(a, b, |a, b| holds(op, Some(a.cmp(b))))),
+            (Add, _, _) | (Sub, _, _) | (Mul, _, _) | (Div, _, _) | (Pow, _, _)
+                if !matches!((op, lhs, rhs), (Pow, Int(_), Int(_))) =>
+            {
+                match (lhs.floats(), rhs.floats()) {
+                    (Some(a), Some(b)) => self.floats(op, &a, &b, live),
+                    _ => self.rows(live, |row| lhs.get(row).binary(op, rhs.get(row))),
+                }
+            }
+            _ if comparison && lhs.floats().is_some() && rhs.floats().is_some() => {
+                let (a, b) = (lhs.floats().unwrap(), rhs.floats().unwrap());
+                Ok(zip(&a, &b, |a, b| holds(op, a.partial_cmp(b))))
+            }
+            (_, String(a), String(b)) if comparison => {
+                Ok(zip(a, b, |a, b| holds(op, Some(a.cmp(b)))))
+            }
+            (Search, String(a), String(b)) => Ok(zip(a, b, |a, b| a.contains(b.as_str()))),
+            (Eq, Bool(a), Bool(b)) | (Ne, Bool(a), Bool(b)) => {
+                Ok(zip(a, b, |a, b| holds(op, Some(a.cmp(b)))))
+            }
+            _ => self.rows(live, |row| lhs.get(row).binary(op, rhs.get(row))),
+        }
+    }
+
+    fn floats(
+        &self,
+        op: BinaryCode,
+        a: &[TFloat],
+        b: &[TFloat],
+        live: Live,
+    ) -> Result<Column<'b>, ColumnError> {
+        use BinaryCode::*;
+        if op == Div {
+            let zero = (0..a.len()).find(|&row| is_live(live, row) && b[row] == 0.0);
+            if let Some(row) = zero {
+                let error = EvalError::DivisionByZero;
+                return Err(ColumnError::Row { row, error });
+            }
+        }
+        Ok(zip(a, b, |&a, &b| match op {
+            Add => a + b,
+            Sub => a - b,
+            Mul => a * b,
+            Div => a / b,
+            _ => a.powf(b),
+        }))
+    }
+
+    /// Applies `f` to every live row.
+    fn rows(
+        &self,
+        live: Live,
+        mut f: impl FnMut(usize) -> Result<Value, EvalError>,
+    ) -> Result<Column<'b>, ColumnError> {
+        let values = (0..self.batch.len)
+            .map(|row| match is_live(live, row) {
+                true => f(row)
+                    .map(Some)
+                    .map_err(|error| ColumnError::Row { row, error }),
+                false => Ok(None),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Column::from_values(values))
+    }
+}
+
+fn zip<A, B, T>(a: &[A], b: &[B], f: impl Fn(&A, &B) -> T) -> Column<'static>
+where
+    Column<'static>: From<Vec<T>>,
+{
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| f(a, b))
+        .collect::<Vec<_>>()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn batch() -> Batch<'static> {
+        let mut batch = Batch::new(5);
+        let mut set = |name: &str, column: Column<'static>| batch.set(name.into(), column).unwrap();
+        set("age", vec![17, 18, 40, -3, 0].into());
+        set(
+            "name",
+            vec!["Kovács", "Kis", "Kovacs", "", "Nagy"]
+                .into_iter()
+                .map(String::from)
+                .collect::<Vec<_>>()
+                .into(),
+        );
+        set("w", vec![0.5, 2.0, -1.5, 0.0, 1e300].into());
+        set("c", vec!['a', 'K', '1', ' ', 'ő'].into());
+        set("ok", vec![true, false, true, false, true].into());
+        batch
+    }
+
+    /// The rows evaluated one by one with [`Expr::eval`].
+    fn row_by_row(e: &Expr, batch: &Batch) -> Vec<Result<Value, EvalError>> {
+        (0..batch.len())
+            .map(|row| {
+                let env: Env = batch
+                    .columns
+                    .iter()
+                    .map(|(name, column)| (name.clone(), column.get(row)))
+                    .collect();
+                e.eval(&env)
+            })
+            .collect()
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt) => {
+            #[test]
+            fn $test_name() {
+                let (e, batch) = (Expr::parse($in).unwrap(), batch());
+                let expected = row_by_row(&e, &batch);
+                match e.eval_columns(&batch) {
+                    Ok(column) => {
+                        let rows: Vec<_> =
+                            (0..batch.len()).map(|row| Ok(column.get(row))).collect();
+                        assert_eq!(rows, expected);
+                    }
+                    Err(ColumnError::Row { row, error }) => assert_eq!(expected[row], Err(error)),
+                    Err(e) => panic!("{}", e),
+                }
+            }
+        };
+    }
+
+    test!(filter: "age >= 18 AND name @ \"Kov\"");
+    test!(int_arithmetic: "age * 2 - 1 + age MOD 3");
+    test!(mixed_arithmetic: "(age + w) / 2 ^ 3");
+    test!(float_functions: "SIN w + COS age - EXP (w / 1000)");
+    test!(comparisons: "w < age OR w = 0.0 OR name > \"L\" OR ok = FALSE");
+    test!(chars: "UPPER c = c AND IS_DIGIT c");
+    test!(strings: "name + c + \"!\"");
+    test!(negation: "NOT ok OR -w > 0");
+    test!(short_circuit_and: "age /= 0 AND 100 DIV age > 2");
+    test!(short_circuit_or: "name = \"\" OR name[0] = 'K'");
+    test!(negative_powers: "2 ^ (age - 17)");
+    test!(division_by_zero: "1 / age");
+    test!(int_division_by_zero: "1 DIV age");
+    test!(overflow: "age * 2147483647");
+    test!(out_of_range: "name[3]");
+    test!(type_mismatch: "age + name");
+    test!(unbound: "age + x");
+
+    #[test]
+    fn mask() {
+        let e = Expr::parse("age >= 18 AND name @ \"Kov\"").unwrap();
+        assert_eq!(
+            e.select(&batch()),
+            Ok(vec![false, false, true, false, false])
+        );
+    }
+
+    #[test]
+    fn borrowed_columns() {
+        let ages = [30, 12];
+        let mut batch = Batch::new(2);
+        batch.set("age".into(), ages[..].into()).unwrap();
+        assert_eq!(
+            Expr::parse("age").unwrap().eval_columns(&batch),
+            Ok(Column::Int(Cow::Borrowed(&ages[..])))
+        );
+    }
+
+    #[test]
+    fn unbound_in_no_rows() {
+        let e = Expr::parse("age > 100 AND x").unwrap();
+        assert_eq!(e.select(&batch()), Ok(vec![false; 5]));
+    }
+
+    #[test]
+    fn not_a_bool() {
+        let e = Expr::parse("age + 1").unwrap();
+        assert_eq!(e.select(&batch()), Err(ColumnError::NotABool));
+    }
+
+    #[test]
+    fn length() {
+        let mut batch = Batch::new(2);
+        let error = batch.set("a".into(), vec![1, 2, 3].into()).unwrap_err();
+        assert_eq!(error.to_string(), "column `a` has 3 rows instead of 2");
+    }
+}
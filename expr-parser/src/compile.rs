@@ -0,0 +1,224 @@
+use super::eval::{binary, unary};
+use super::{BinaryCode, Env, EvalError, Expr, UnaryCode, Value, VarName};
+use std::borrow::Cow;
+use std::fmt;
+
+/// The variables a [`CompiledExpr`] is evaluated with, in the order of the
+/// slice of values it takes.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Schema {
+    names: Vec<VarName>,
+}
+
+impl Schema {
+    pub fn new(names: Vec<VarName>) -> Self {
+        Schema { names }
+    }
+
+    pub fn names(&self) -> &[VarName] {
+        &self.names
+    }
+
+    /// The index of the value of `name`, the first one if it is listed twice.
+    pub fn slot(&self, name: &VarName) -> Option<usize> {
+        self.names.iter().position(|x| x == name)
+    }
+}
+
+impl<N: Into<VarName>> std::iter::FromIterator<N> for Schema {
+    fn from_iter<I: IntoIterator<Item = N>>(iter: I) -> Self {
+        Schema::new(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// What the closures of a [`CompiledExpr`] evaluate with.
+struct Frame<'a> {
+    values: &'a [Value],
+    constants: &'a [Value],
+    env: &'a Env,
+}
+
+/// Evaluates to a borrowed value for variables and constants, so only the
+/// operators make new values.
+type Node = Box<dyn for<'a> Fn(&Frame<'a>) -> Result<Cow<'a, Value>, EvalError>>;
+
+/// An expression prepared by [`Expr::compile`] to be evaluated many times.
+pub struct CompiledExpr {
+    root: Node,
+    constants: Vec<Value>,
+    /// Supplies the random numbers of `RND`.
+    env: Env,
+}
+
+impl Expr {
+    /// Compiles the expression into closures that read the variables from a
+    /// slice of values, laid out like `schema`.
+    ///
+    /// The names are resolved here, so evaluating the result looks up nothing.
+    /// Variables and constants are used by reference, so it only allocates for
+    /// the strings the operators create, and to return a string that is a
+    /// variable or a constant. Variables missing from the schema are unbound,
+    /// like in [`Expr::eval`] they are only errors when evaluated.
+    pub fn compile(&self, schema: &Schema) -> CompiledExpr {
+        let mut constants = Vec::new();
+        CompiledExpr {
+            root: compile(self, schema, &mut constants),
+            constants,
+            env: Env::new(),
+        }
+    }
+}
+
+impl CompiledExpr {
+    /// Seeds the random number generator of `RND`, see [`Env::with_seed`].
+    pub fn with_seed(self, seed: u64) -> Self {
+        CompiledExpr {
+            env: Env::with_seed(seed),
+            ..self
+        }
+    }
+
+    /// Evaluates the expression like [`Expr::eval`], with the `i`th variable
+    /// of the schema bound to `values[i]`.
+    pub fn eval(&self, values: &[Value]) -> Result<Value, EvalError> {
+        self.eval_ref(values).map(Cow::into_owned)
+    }
+
+    /// Evaluates the expression like [`CompiledExpr::eval`], borrowing the
+    /// result if it is a variable or a constant.
+    pub fn eval_ref<'a>(&'a self, values: &'a [Value]) -> Result<Cow<'a, Value>, EvalError> {
+        (self.root)(&Frame {
+            values,
+            constants: &self.constants,
+            env: &self.env,
+        })
+    }
+}
+
+impl fmt::Debug for CompiledExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompiledExpr").finish_non_exhaustive()
+    }
+}
+
+fn compile(e: &Expr, schema: &Schema, constants: &mut Vec<Value>) -> Node {
+    let mut compile = |e| compile(e, schema, constants);
+    match e {
+        Expr::Value(Value::VarName(name)) => {
+            let name = name.clone();
+            match schema.slot(&name) {
+                Some(slot) => Box::new(move |frame| {
+                    frame
+                        .values
+                        .get(slot)
+                        .map(Cow::Borrowed)
+                        .ok_or_else(|| EvalError::Unbound(name.clone()))
+                }),
+                None => Box::new(move |_| Err(EvalError::Unbound(name.clone()))),
+            }
+        }
+        Expr::Value(x) => {
+            let i = constants.len();
+            constants.push(x.clone());
+            Box::new(move |frame| Ok(Cow::Borrowed(&frame.constants[i])))
+        }
+        Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => compile(x.rhs()),
+        Expr::Unary(x) => {
+            let (op, rhs) = (*x.op_code(), compile(x.rhs()));
+            Box::new(move |frame| Ok(Cow::Owned(unary(op, &*rhs(frame)?, frame.env)?)))
+        }
+        Expr::Binary(x) => {
+            let op = *x.op_code();
+            let (lhs, rhs) = (compile(x.lhs()), compile(x.rhs()));
+            Box::new(move |frame| {
+                let lhs = lhs(frame)?;
+                Ok(Cow::Owned(match (op, &*lhs) {
+                    (BinaryCode::And, Value::Boolean(false)) => false.into(),
+                    (BinaryCode::Or, Value::Boolean(true)) => true.into(),
+                    _ => binary(op, &lhs, &*rhs(frame)?)?,
+                }))
+            })
+        }
+        Expr::Index(x, at) => {
+            let (x, at) = (compile(x), compile(at));
+            Box::new(move |frame| Ok(Cow::Owned(x(frame)?.index(&*at(frame)?)?)))
+        }
+        Expr::Slice(x, start, end) => {
+            let (x, start, end) = (compile(x), compile(start), compile(end));
+            Box::new(move |frame| {
+                let x = x(frame)?;
+                Ok(Cow::Owned(x.slice(&*start(frame)?, &*end(frame)?)?))
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        vec!["i", "s", "x"].into_iter().collect()
+    }
+
+    fn values() -> Vec<Value> {
+        vec![7.into(), "Kovács".into(), 0.5.into()]
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt) => {
+            #[test]
+            fn $test_name() {
+                let e = Expr::parse($in).unwrap();
+                let env: Env = schema().names().iter().cloned().zip(values()).collect();
+                let compiled = e.compile(&schema());
+                assert_eq!(compiled.eval(&values()), e.eval(&env));
+            }
+        };
+    }
+
+    test!(arithmetic: "(1 + 2) * 3 - i DIV 2 + x");
+    test!(strings: "UPPER s[0:3] + s[4]");
+    test!(comparison: "i > 5 AND s @ \"vá\"");
+    test!(type_mismatch: "i + s");
+    test!(division_by_zero: "i MOD (i - 7)");
+    test!(out_of_range: "s[i]");
+    test!(short_circuit: "i < 5 AND 1 DIV 0 = 1 OR TRUE");
+    test!(unbound: "i + y");
+
+    #[test]
+    fn schema_order() {
+        let e = Expr::parse("a - b").unwrap();
+        let compiled = e.compile(&vec!["b", "a"].into_iter().collect());
+        assert_eq!(compiled.eval(&[1.into(), 10.into()]), Ok(9.into()));
+        assert_eq!(compiled.eval(&[2.into(), 3.into()]), Ok(1.into()));
+    }
+
+    #[test]
+    fn missing_value() {
+        let compiled = Expr::parse("i + x").unwrap().compile(&schema());
+        assert_eq!(
+            compiled.eval(&[1.into()]),
+            Err(EvalError::Unbound("x".into()))
+        );
+    }
+
+    #[test]
+    fn borrows_variables_and_constants() {
+        let compiled = Expr::parse("(s)").unwrap().compile(&schema());
+        let values = values();
+        assert!(matches!(compiled.eval_ref(&values), Ok(Cow::Borrowed(x)) if *x == values[1]));
+        let compiled = Expr::parse("\"a\"").unwrap().compile(&schema());
+        assert_eq!(compiled.eval_ref(&values), Ok(Cow::Borrowed(&"a".into())));
+    }
+
+    #[test]
+    fn seeded() {
+        let e = Expr::parse("RND 1000").unwrap();
+        let a = e.compile(&Schema::default()).with_seed(42);
+        let b = e.compile(&Schema::default()).with_seed(42);
+        let a: Vec<_> = (0..5).map(|_| a.eval(&[])).collect();
+        let b: Vec<_> = (0..5).map(|_| b.eval(&[])).collect();
+        assert_eq!(a, b);
+    }
+}
@@ -0,0 +1,135 @@
+use super::{fold_children, Env, EvalError, Expr, Fold, UnaryCode, Value};
+use std::fmt;
+
+/// A constant subexpression that is kept as is, because evaluating it fails.
+#[derive(Debug, PartialEq, Clone)]
+pub struct FoldWarning {
+    expr: Expr,
+    error: EvalError,
+}
+
+impl FoldWarning {
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn error(&self) -> &EvalError {
+        &self.error
+    }
+}
+
+impl fmt::Display for FoldWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "`{}` always fails: {}", self.expr, self.error)
+    }
+}
+
+impl Expr {
+    /// Replaces every constant subexpression with its value, following the rules
+    /// of [`crate::numeric`].
+    ///
+    /// The variables bound in `constants` count as constants. `RND` is never folded,
+    /// neither are subexpressions whose evaluation fails, those are pushed to `warnings`
+    /// so the error still happens at run time.
+    pub fn fold_constants(self, constants: &Env, warnings: &mut Vec<FoldWarning>) -> Expr {
+        ConstFolder {
+            constants,
+            warnings,
+        }
+        .fold_expr(self)
+    }
+}
+
+struct ConstFolder<'a> {
+    constants: &'a Env,
+    warnings: &'a mut Vec<FoldWarning>,
+}
+
+impl Fold for ConstFolder<'_> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        let e = fold_children(self, e);
+        let constant = !e.is_value()
+            && e.as_unary()
+                .is_none_or(|x| *x.op_code() != UnaryCode::Random)
+            && e.children()
+                .all(|x| x.as_value().is_some_and(|x| !x.is_var_name()));
+        if !constant {
+            return e;
+        }
+        match e.eval(self.constants) {
+            Ok(Value::Float(x)) if !x.is_finite() => e,
+            Ok(x) => x.to_expr(),
+            Err(error) => {
+                self.warnings.push(FoldWarning {
+                    expr: e.clone(),
+                    error,
+                });
+                e
+            }
+        }
+    }
+
+    fn fold_value(&mut self, v: Value) -> Expr {
+        match v {
+            Value::VarName(name) => match self.constants.get(&name) {
+                Some(x) => x.clone().to_expr(),
+                None => Value::VarName(name).to_expr(),
+            },
+            v => v.to_expr(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(s: &str) -> (String, Vec<String>) {
+        let constants: Env = vec![("k", 10)].into_iter().collect();
+        let mut warnings = Vec::new();
+        let folded = Expr::parse(s)
+            .unwrap()
+            .fold_constants(&constants, &mut warnings);
+        let warnings = warnings.iter().map(ToString::to_string).collect();
+        (folded.to_string(), warnings)
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $res:tt) => {
+            #[test]
+            fn $test_name() {
+                assert_eq!(fold($in), ($res.to_string(), vec![]));
+            }
+        };
+    }
+
+    test!(arithmetic: "2 * 3 + x" -> "6 + x");
+    test!(left_assoc: "x + 2 * 3" -> "x + 6");
+    test!(not: "NOT TRUE" -> "FALSE");
+    test!(sin: "SIN 0.0" -> "0.0");
+    test!(real_div: "7 / 2" -> "3.5");
+    test!(parentheses: "(1 + 2) * x" -> "3 * x");
+    test!(strings: "\"ab\" + 'c' = s" -> "\"abc\" = s");
+    test!(index: "\"abc\"[1]" -> "'b'");
+    test!(constant_var: "k DIV 3" -> "3");
+    test!(random: "RND 1 + 1" -> "RND 1 + 1");
+    test!(negative: "x * (0 - 2)" -> "x * -2");
+
+    #[test]
+    fn keeps_errors() {
+        assert_eq!(
+            fold("x + 1 DIV (2 - 2)"),
+            (
+                "x + 1 DIV 0".to_string(),
+                vec!["`1 DIV 0` always fails: division by zero".to_string()]
+            )
+        );
+    }
+
+    #[test]
+    fn keeps_overflow() {
+        let (folded, warnings) = fold("2147483647 + 1");
+        assert_eq!(folded, "2147483647 + 1");
+        assert_eq!(warnings.len(), 1);
+    }
+}
@@ -0,0 +1,167 @@
+use super::{BinaryCode, Env, Expr, Strictness, UnaryCode, Value, VarName};
+use derive_more::Display;
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum DiffError {
+    #[display(fmt = "`{}` has no derivative", _0)]
+    NotDifferentiable(String),
+    #[display(fmt = "{} is not a number", _0)]
+    NotANumber(Value),
+}
+
+impl Expr {
+    /// The derivative of the expression with respect to `var`, simplified.
+    ///
+    /// Other variables are constants. Operators without a symbolic derivative,
+    /// like `ROUND`, `||`, `RND` or the operators of strings and booleans, are errors
+    /// wherever they are, as are literals that are not numbers. The derivative is
+    /// simplified with [`Strictness::Relaxed`], as calculus treats numbers as reals,
+    /// and its constant subexpressions are folded.
+    pub fn derivative(&self, var: &VarName) -> Result<Expr, DiffError> {
+        let mut e = diff(self, var)?;
+        loop {
+            let simplified = e
+                .clone()
+                .simplify(Strictness::Relaxed)
+                .fold_constants(&Env::new(), &mut Vec::new());
+            if simplified == e {
+                return Ok(e);
+            }
+            e = simplified;
+        }
+    }
+}
+
+fn unary(op: UnaryCode, x: Expr) -> Expr {
+    (op, x).into()
+}
+
+fn binary(op: BinaryCode, lhs: Expr, rhs: Expr) -> Expr {
+    (op, lhs, rhs).into()
+}
+
+fn contains(e: &Expr, var: &VarName) -> bool {
+    e.pre_order().any(|x| x.as_var_name() == Some(var))
+}
+
+fn diff(e: &Expr, var: &VarName) -> Result<Expr, DiffError> {
+    use BinaryCode::*;
+    use UnaryCode::*;
+    let not_differentiable = |op: &dyn ToString| Err(DiffError::NotDifferentiable(op.to_string()));
+    Ok(match e {
+        Expr::Value(Value::VarName(name)) => Expr::from(if name == var { 1 } else { 0 }),
+        Expr::Value(Value::Int(_)) | Expr::Value(Value::Float(_)) => 0.into(),
+        Expr::Value(x) => return Err(DiffError::NotANumber(x.clone())),
+        Expr::Unary(x) => {
+            let u = x.rhs().clone();
+            let du = diff(x.rhs(), var)?;
+            let one_minus_square = || binary(Sub, 1.into(), binary(Pow, u.clone(), 2.into()));
+            let outer = match *x.op_code() {
+                Parentheses | Plus | ToFloat => return Ok(du),
+                Neg => return Ok(unary(Neg, du)),
+                Sin => unary(Cos, u),
+                Cos => unary(Neg, unary(Sin, u)),
+                Tan => binary(Div, 1.into(), binary(Pow, unary(Cos, u), 2.into())),
+                Exp => unary(Exp, u),
+                Log => binary(Div, 1.into(), u),
+                Arcsin => binary(Div, 1.into(), binary(Pow, one_minus_square(), 0.5.into())),
+                Arccos => unary(
+                    Neg,
+                    binary(Div, 1.into(), binary(Pow, one_minus_square(), 0.5.into())),
+                ),
+                Arctan => binary(
+                    Div,
+                    1.into(),
+                    binary(Add, 1.into(), binary(Pow, u, 2.into())),
+                ),
+                op => return not_differentiable(&op),
+            };
+            // the chain rule
+            binary(Mul, outer, du)
+        }
+        Expr::Binary(x) => {
+            let (u, v) = (x.lhs().clone(), x.rhs().clone());
+            let (du, dv) = (diff(&u, var)?, diff(&v, var)?);
+            match *x.op_code() {
+                Add | Sub => binary(*x.op_code(), du, dv),
+                Mul => binary(Add, binary(Mul, du, v), binary(Mul, u, dv)),
+                Div => binary(
+                    Div,
+                    binary(Sub, binary(Mul, du, v.clone()), binary(Mul, u, dv)),
+                    binary(Pow, v, 2.into()),
+                ),
+                Pow if !contains(&v, var) => {
+                    let power = binary(Pow, u, binary(Sub, v.clone(), 1.into()));
+                    binary(Mul, binary(Mul, v, power), du)
+                }
+                Pow if !contains(&u, var) => binary(Mul, binary(Mul, e.clone(), unary(Log, u)), dv),
+                Pow => {
+                    // (u ^ v)' = u ^ v * (v' * LOG u + v * u' / u)
+                    let inner = binary(
+                        Add,
+                        binary(Mul, dv, unary(Log, u.clone())),
+                        binary(Div, binary(Mul, v, du), u),
+                    );
+                    binary(Mul, e.clone(), inner)
+                }
+                op => return not_differentiable(&op),
+            }
+        }
+        Expr::Index(..) => return not_differentiable(&"[]"),
+        Expr::Slice(..) => return not_differentiable(&"[:]"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $res:tt) => {
+            #[test]
+            fn $test_name() {
+                let e = Expr::parse($in).unwrap().derivative(&"x".into());
+                assert_eq!(e.map(|e| e.to_string()), Ok($res.to_string()));
+            }
+        };
+        ($test_name:ident: $in:tt -> Err($res:expr)) => {
+            #[test]
+            fn $test_name() {
+                let e = Expr::parse($in).unwrap().derivative(&"x".into());
+                assert_eq!(e, Err($res));
+            }
+        };
+    }
+
+    test!(constant: "3 + y" -> "0");
+    test!(identity: "x" -> "1");
+    test!(linear: "3 * x + 1" -> "3");
+    test!(square: "x ^ 2" -> "2 * x");
+    test!(cube: "x ^ 3" -> "3 * x ^ 2");
+    test!(product: "x * y" -> "y");
+    test!(product_rule: "x * SIN x" -> "SIN x + x * COS x");
+    test!(quotient_rule: "1 / x" -> "-1 / x ^ 2");
+    test!(chain_rule: "SIN (2 * x)" -> "COS (2 * x) * 2");
+    test!(cos: "COS x" -> "-SIN x");
+    test!(exp: "EXP x" -> "EXP x");
+    test!(log: "LOG x" -> "1 / x");
+    test!(tan: "TAN x" -> "1 / COS x ^ 2");
+    test!(arctan: "ARCTAN x" -> "1 / (1 + x ^ 2)");
+    test!(exponential: "y ^ x" -> "y ^ x * LOG y");
+    test!(power_tower: "x ^ x" -> "x ^ x * (LOG x + x / x)");
+    test!(parentheses: "(x + 1) * (x - 1)" -> "x - 1 + (x + 1)");
+    test!(folded: "2 ^ x" -> "2 ^ x * 0.6931471805599453");
+
+    test!(round: "ROUND x" -> Err(DiffError::NotDifferentiable("ROUND".into())));
+    test!(abs: "|x|" -> Err(DiffError::NotDifferentiable("||".into())));
+    test!(random: "RND x" -> Err(DiffError::NotDifferentiable("RND".into())));
+    test!(modulo: "x MOD 2" -> Err(DiffError::NotDifferentiable("MOD".into())));
+    test!(index: "s[0] + x" -> Err(DiffError::NotDifferentiable("[]".into())));
+    test!(string: "x + \"a\"" -> Err(DiffError::NotANumber("a".into())));
+
+    #[test]
+    fn error_message() {
+        let e = Expr::parse("UPPER x").unwrap().derivative(&"x".into());
+        assert_eq!(e.unwrap_err().to_string(), "`UPPER` has no derivative");
+    }
+}
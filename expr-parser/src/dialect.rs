@@ -0,0 +1,159 @@
+use super::{BinaryCode, UnaryCode};
+use std::str::FromStr;
+
+/// The natural language the keywords of a program are written in.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+pub enum Dialect {
+    English,
+    Hungarian,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "en" | "english" => Ok(Dialect::English),
+            "hu" | "hungarian" | "magyar" => Ok(Dialect::Hungarian),
+            _ => Err(format!("unknown dialect: {}", s)),
+        }
+    }
+}
+
+/// A word that the expression grammar reserves.
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum Keyword {
+    Unary(UnaryCode),
+    Binary(BinaryCode),
+    Bool(bool),
+}
+
+impl Keyword {
+    /// Recognizes every spelling the expression grammar accepts.
+    pub fn parse(word: &str) -> Option<Self> {
+        lexer::Keyword::parse(word).and_then(Keyword::of)
+    }
+
+    /// The meaning of a keyword of the lexer in an expression, `None` for
+    /// the keywords of statements and declarations.
+    pub fn of(keyword: lexer::Keyword) -> Option<Self> {
+        use lexer::Keyword as K;
+        use BinaryCode as BC;
+        use UnaryCode as UC;
+        Some(match keyword {
+            K::Rnd => Keyword::Unary(UC::Random),
+            K::Sin => Keyword::Unary(UC::Sin),
+            K::Cos => Keyword::Unary(UC::Cos),
+            K::Tan => Keyword::Unary(UC::Tan),
+            K::Exp => Keyword::Unary(UC::Exp),
+            K::Log => Keyword::Unary(UC::Log),
+            K::Not => Keyword::Unary(UC::Not),
+            K::Arcsin => Keyword::Unary(UC::Arcsin),
+            K::Arccos => Keyword::Unary(UC::Arccos),
+            K::Arctan => Keyword::Unary(UC::Arctan),
+            K::Upper => Keyword::Unary(UC::ToUpper),
+            K::Round => Keyword::Unary(UC::Round),
+            K::Lower => Keyword::Unary(UC::ToLower),
+            K::Int => Keyword::Unary(UC::ToInt),
+            K::IsChar => Keyword::Unary(UC::IsWordChar),
+            K::Float => Keyword::Unary(UC::ToFloat),
+            K::IsDigit => Keyword::Unary(UC::IsDigit),
+            K::Or => Keyword::Binary(BC::Or),
+            K::And => Keyword::Binary(BC::And),
+            K::Div => Keyword::Binary(BC::IntDiv),
+            K::Mod => Keyword::Binary(BC::Mod),
+            K::True => Keyword::Bool(true),
+            K::False => Keyword::Bool(false),
+            K::Variables
+            | K::Read
+            | K::Print
+            | K::If
+            | K::Then
+            | K::Else
+            | K::EndIf
+            | K::While
+            | K::EndWhile
+            | K::Bool
+            | K::Char
+            | K::String => return None,
+        })
+    }
+
+    /// The canonical, upper case spelling of the keyword in `dialect`.
+    pub fn spelling(self, dialect: Dialect) -> &'static str {
+        match (self, dialect) {
+            (Keyword::Unary(code), _) => code.spellings(dialect)[0],
+            (Keyword::Binary(code), _) => code.spellings(dialect)[0],
+            (Keyword::Bool(true), Dialect::English) => "TRUE",
+            (Keyword::Bool(false), Dialect::English) => "FALSE",
+            (Keyword::Bool(true), Dialect::Hungarian) => "IGAZ",
+            (Keyword::Bool(false), Dialect::Hungarian) => "HAMIS",
+        }
+    }
+}
+
+/// Rewrites every keyword of an expression into `to`.
+///
+/// The rest of the source, including identifiers, literals and whitespace,
+/// is copied as is. Lower case keywords stay lower case where the grammar
+/// has a lower case spelling.
+pub fn translate(source: &str, to: Dialect) -> String {
+    let mut out = String::with_capacity(source.len());
+    for token in lexer::lex(source) {
+        let keyword = match token.kind {
+            lexer::TokenKind::Keyword(keyword) => Keyword::of(keyword),
+            _ => None,
+        };
+        match keyword {
+            Some(keyword) => {
+                let spelling = keyword.spelling(to);
+                let lower = spelling.to_lowercase();
+                if token.text.chars().any(char::is_uppercase) || Keyword::parse(&lower).is_none() {
+                    out.push_str(spelling)
+                } else {
+                    out.push_str(&lower)
+                }
+            }
+            None => out.push_str(token.text),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $to:tt : $out:tt) => {
+            #[test]
+            fn $test_name() {
+                assert_eq!(translate($in, Dialect::$to), $out);
+                assert_eq!(
+                    crate::Expr::parse($in).unwrap(),
+                    crate::Expr::parse($out).unwrap()
+                );
+            }
+        };
+    }
+
+    test!(to_english: "NEM a ÉS b VAGY IGAZ" -> English : "NOT a AND b OR TRUE");
+    test!(to_hungarian: "NOT a AND b OR TRUE" -> Hungarian : "NEM a ÉS b VAGY IGAZ");
+    test!(keeps_lower_case: "upper s = kicsi t" -> Hungarian : "nagy s = kicsi t");
+    test!(keeps_strings: r#"s @ "NOT AND""# -> Hungarian : r#"s @ "NOT AND""#);
+    test!(keeps_identifiers: "nota + vagyon * 1f" -> English : "nota + vagyon * 1f");
+    test!(keeps_spacing: "EGÉSZ(x)  DIV 2" -> English : "INT(x)  DIV 2");
+    test!(same_in_both: "SIN x MOD 2" -> Hungarian : "SIN x MOD 2");
+
+    #[test]
+    fn every_spelling_is_a_keyword() {
+        for keyword in "RND SIN COS TAN EXP LOG NOT ARCSIN ARCCOS ARCTAN UPPER ROUND LOWER INT \
+                        IS_CHAR FLOAT IS_DIGIT OR AND DIV MOD TRUE FALSE"
+            .split(' ')
+            .filter_map(Keyword::parse)
+        {
+            for &dialect in &[Dialect::English, Dialect::Hungarian] {
+                assert_eq!(Keyword::parse(keyword.spelling(dialect)), Some(keyword));
+            }
+        }
+    }
+}
@@ -0,0 +1,382 @@
+use super::numeric::{self, as_float, ArithmeticError};
+use super::{BinaryCode, Expr, TFloat, TInt, Type, UnaryCode, Value, VarName};
+use derive_more::Display;
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The values of the variables an expression is evaluated with.
+///
+/// The environment also holds the state of the random number generator behind
+/// `RND`, seed it with [`Env::with_seed`] to get reproducible results.
+#[derive(Debug, Clone)]
+pub struct Env {
+    vars: HashMap<VarName, Value>,
+    rng: Cell<u64>,
+}
+
+impl Env {
+    pub fn new() -> Self {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Env::with_seed(nanos)
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Env {
+            vars: HashMap::new(),
+            rng: Cell::new(seed),
+        }
+    }
+
+    pub fn get(&self, name: &VarName) -> Option<&Value> {
+        self.vars.get(name)
+    }
+
+    /// Binds `name` to `value`, returning the previous value.
+    pub fn set(&mut self, name: VarName, value: Value) -> Option<Value> {
+        self.vars.insert(name, value)
+    }
+
+    /// Unbinds `name`, returning its value.
+    pub fn remove(&mut self, name: &VarName) -> Option<Value> {
+        self.vars.remove(name)
+    }
+
+    pub fn vars(&self) -> impl Iterator<Item = (&VarName, &Value)> {
+        self.vars.iter()
+    }
+
+    /// The next random number of `RND`, a uniformly distributed float in
+    /// `[0, 1)`, using splitmix64.
+    pub fn random(&self) -> TFloat {
+        let state = self.rng.get().wrapping_add(0x9E37_79B9_7F4A_7C15);
+        self.rng.set(state);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as TFloat / (1u64 << 53) as TFloat
+    }
+}
+
+impl Default for Env {
+    fn default() -> Self {
+        Env::new()
+    }
+}
+
+impl<N: Into<VarName>, V: Into<Value>> std::iter::FromIterator<(N, V)> for Env {
+    fn from_iter<I: IntoIterator<Item = (N, V)>>(iter: I) -> Self {
+        let mut env = Env::new();
+        for (name, value) in iter {
+            env.set(name.into(), value.into());
+        }
+        env
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum EvalError {
+    #[display(fmt = "variable `{}` is not bound", _0)]
+    Unbound(VarName),
+    #[display(
+        fmt = "`{}` is not defined for {}",
+        op,
+        "crate::types::display_types(operands)"
+    )]
+    TypeMismatch { op: String, operands: Vec<Type> },
+    #[display(fmt = "division by zero")]
+    DivisionByZero,
+    #[display(fmt = "index {} is out of range for length {}", index, len)]
+    IndexOutOfRange { index: TInt, len: usize },
+    #[display(fmt = "the slice {}:{} starts after it ends", start, end)]
+    InvalidSlice { start: TInt, end: TInt },
+    #[display(fmt = "{} is out of the domain of `{}`", arg, op)]
+    MathDomain { op: UnaryCode, arg: TFloat },
+    #[display(fmt = "`{}` overflowed", _0)]
+    Overflow(String),
+    #[display(fmt = "an Int can't be raised to a negative power")]
+    NegativeExponent,
+}
+
+fn mismatch(op: impl ToString, operands: &[&Value]) -> EvalError {
+    EvalError::TypeMismatch {
+        op: op.to_string(),
+        operands: operands.iter().filter_map(|x| x.type_of()).collect(),
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression with the variables of `env`.
+    ///
+    /// `AND` and `OR` short-circuit. Strings are indexed by characters from zero,
+    /// `s[i:j]` is the substring from `i` up to, but not including, `j`.
+    pub fn eval(&self, env: &Env) -> Result<Value, EvalError> {
+        match self {
+            Expr::Value(Value::VarName(name)) => env
+                .get(name)
+                .cloned()
+                .ok_or_else(|| EvalError::Unbound(name.clone())),
+            Expr::Value(x) => Ok(x.clone()),
+            Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => x.rhs().eval(env),
+            Expr::Unary(x) => unary(*x.op_code(), &x.rhs().eval(env)?, env),
+            Expr::Binary(x) => match (x.op_code(), x.lhs().eval(env)?) {
+                (BinaryCode::And, Value::Boolean(false)) => Ok(false.into()),
+                (BinaryCode::Or, Value::Boolean(true)) => Ok(true.into()),
+                (&op, lhs) => binary(op, &lhs, &x.rhs().eval(env)?),
+            },
+            Expr::Index(x, at) => x.eval(env)?.index(&at.eval(env)?),
+            Expr::Slice(x, start, end) => x.eval(env)?.slice(&start.eval(env)?, &end.eval(env)?),
+        }
+    }
+}
+
+/// The operators on values, for evaluators that do not walk an [`Expr`].
+impl Value {
+    /// Applies a unary operator, `RND` takes its random numbers from `env`.
+    pub fn unary(self, op: UnaryCode, env: &Env) -> Result<Value, EvalError> {
+        match op {
+            UnaryCode::Parentheses => Ok(self),
+            _ => unary(op, &self, env),
+        }
+    }
+
+    /// Applies a binary operator. `AND` and `OR` evaluate both operands here,
+    /// short-circuiting is up to the caller.
+    pub fn binary(self, op: BinaryCode, rhs: Value) -> Result<Value, EvalError> {
+        binary(op, &self, &rhs)
+    }
+
+    /// The character of a string at `at`, see [`Expr::eval`].
+    pub fn index(&self, at: &Value) -> Result<Value, EvalError> {
+        match (self, at) {
+            (Value::String(s), &Value::Int(i)) => {
+                let len = s.chars().count();
+                usize::try_from(i)
+                    .ok()
+                    .and_then(|n| s.chars().nth(n))
+                    .map(Value::Char)
+                    .ok_or(EvalError::IndexOutOfRange { index: i, len })
+            }
+            _ => Err(mismatch("[]", &[self, at])),
+        }
+    }
+
+    /// The substring from `start` up to, but not including, `end`.
+    pub fn slice(&self, start: &Value, end: &Value) -> Result<Value, EvalError> {
+        match (self, start, end) {
+            (Value::String(s), &Value::Int(i), &Value::Int(j)) => {
+                let len = s.chars().count();
+                let bound = |k: TInt| match usize::try_from(k) {
+                    Ok(n) if n <= len => Ok(n),
+                    _ => Err(EvalError::IndexOutOfRange { index: k, len }),
+                };
+                let (from, to) = (bound(i)?, bound(j)?);
+                if from > to {
+                    return Err(EvalError::InvalidSlice { start: i, end: j });
+                }
+                Ok(s.chars()
+                    .skip(from)
+                    .take(to - from)
+                    .collect::<String>()
+                    .into())
+            }
+            _ => Err(mismatch("[:]", &[self, start, end])),
+        }
+    }
+}
+
+/// Applies a unary operator to a borrowed operand, which only `(` `)` and
+/// unary `+` copy.
+pub(crate) fn unary(op: UnaryCode, x: &Value, env: &Env) -> Result<Value, EvalError> {
+    use UnaryCode::*;
+    let float = |f: fn(TFloat) -> TFloat, domain: fn(TFloat) -> bool| match as_float(x) {
+        Some(arg) if domain(arg) => Ok(Value::Float(f(arg))),
+        Some(arg) => Err(EvalError::MathDomain { op, arg }),
+        None => Err(mismatch(op, &[x])),
+    };
+    match (op, x) {
+        (Parentheses, _) | (Plus, Value::Int(_)) | (Plus, Value::Float(_)) => Ok(x.clone()),
+        (Neg, &Value::Int(a)) => a
+            .checked_neg()
+            .map(Value::Int)
+            .ok_or_else(|| EvalError::Overflow(op.to_string())),
+        (Neg, &Value::Float(a)) => Ok(Value::Float(-a)),
+        (Abs, &Value::Int(a)) => a
+            .checked_abs()
+            .map(Value::Int)
+            .ok_or_else(|| EvalError::Overflow(op.to_string())),
+        (Abs, &Value::Float(a)) => Ok(Value::Float(a.abs())),
+        (Random, &Value::Int(n)) if n > 0 => Ok(Value::Int((env.random() * n as TFloat) as TInt)),
+        (Random, &Value::Float(n)) if n > 0.0 => Ok(Value::Float(env.random() * n)),
+        (Random, &Value::Int(n)) => Err(EvalError::MathDomain { op, arg: n.into() }),
+        (Random, &Value::Float(arg)) => Err(EvalError::MathDomain { op, arg }),
+        (Sin, _) => float(TFloat::sin, |_| true),
+        (Cos, _) => float(TFloat::cos, |_| true),
+        (Tan, _) => float(TFloat::tan, |_| true),
+        (Exp, _) => float(TFloat::exp, |_| true),
+        (Log, _) => float(TFloat::ln, |a| a > 0.0),
+        (Arcsin, _) => float(TFloat::asin, |a| (-1.0..=1.0).contains(&a)),
+        (Arccos, _) => float(TFloat::acos, |a| (-1.0..=1.0).contains(&a)),
+        (Arctan, _) => float(TFloat::atan, |_| true),
+        (Not, &Value::Boolean(a)) => Ok(Value::Boolean(!a)),
+        (ToUpper, Value::String(s)) => Ok(Value::String(s.to_uppercase())),
+        (ToUpper, Value::Char(c)) => Ok(Value::Char(c.to_uppercase().next().unwrap_or(*c))),
+        (ToLower, Value::String(s)) => Ok(Value::String(s.to_lowercase())),
+        (ToLower, Value::Char(c)) => Ok(Value::Char(c.to_lowercase().next().unwrap_or(*c))),
+        (IsWordChar, &Value::Char(c)) => Ok(Value::Boolean(c.is_alphabetic())),
+        (IsDigit, &Value::Char(c)) => Ok(Value::Boolean(c.is_ascii_digit())),
+        (Round, &Value::Int(a)) | (ToInt, &Value::Int(a)) => Ok(Value::Int(a)),
+        (Round, &Value::Float(a)) => to_int(op, a.round()),
+        (ToInt, &Value::Float(a)) => to_int(op, a.trunc()),
+        (ToFloat, _) if as_float(x).is_some() => Ok(Value::Float(as_float(x).unwrap())),
+        _ => Err(mismatch(op, &[x])),
+    }
+}
+
+fn to_int(op: UnaryCode, a: TFloat) -> Result<Value, EvalError> {
+    numeric::float_to_int(a)
+        .map(Value::Int)
+        .ok_or_else(|| EvalError::Overflow(op.to_string()))
+}
+
+/// Arithmetic follows the rules of the [`numeric`] module.
+pub(crate) fn binary(op: BinaryCode, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    use BinaryCode::*;
+    use Value::*;
+    if let Some(result) = numeric::arithmetic(op, lhs, rhs) {
+        return result.map_err(|e| match e {
+            ArithmeticError::DivisionByZero => EvalError::DivisionByZero,
+            ArithmeticError::Overflow => EvalError::Overflow(op.to_string()),
+            ArithmeticError::NegativeExponent => EvalError::NegativeExponent,
+        });
+    }
+    match (op, lhs, rhs) {
+        (Add, String(a), String(b)) => Ok(String(format!("{}{}", a, b))),
+        (Add, String(a), &Char(b)) => Ok(String(format!("{}{}", a, b))),
+        (Add, &Char(a), String(b)) => Ok(String(format!("{}{}", a, b))),
+        (Add, &Char(a), &Char(b)) => Ok(String(format!("{}{}", a, b))),
+        (Search, String(a), String(b)) => Ok(Boolean(a.contains(b.as_str()))),
+        (Search, String(a), &Char(b)) => Ok(Boolean(a.contains(b))),
+        (And, &Boolean(a), &Boolean(b)) => Ok(Boolean(a && b)),
+        (Or, &Boolean(a), &Boolean(b)) => Ok(Boolean(a || b)),
+        (Eq, _, _) | (Ne, _, _) | (Lt, _, _) | (Le, _, _) | (Gt, _, _) | (Ge, _, _) => {
+            compare(op, lhs, rhs)
+        }
+        _ => Err(mismatch(op, &[lhs, rhs])),
+    }
+}
+
+fn compare(op: BinaryCode, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    use BinaryCode::*;
+    let ordering = match (lhs, rhs) {
+        (Value::Boolean(a), Value::Boolean(b)) if matches!(op, Eq | Ne) => Some(a.cmp(b)),
+        (Value::Char(a), Value::Char(b)) => Some(a.cmp(b)),
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        _ if as_float(lhs).is_some() && as_float(rhs).is_some() => numeric::compare(lhs, rhs),
+        _ => return Err(mismatch(op, &[lhs, rhs])),
+    };
+    Ok(Value::Boolean(holds(op, ordering)))
+}
+
+/// Whether the comparison `op` holds for operands ordered like `ordering`,
+/// `None` is for unordered operands, like `NaN`s, which are only unequal.
+pub(crate) fn holds(op: BinaryCode, ordering: Option<std::cmp::Ordering>) -> bool {
+    use std::cmp::Ordering;
+    use BinaryCode::*;
+    match (op, ordering) {
+        (Ne, None) => true,
+        (_, None) => false,
+        (Eq, Some(o)) => o == Ordering::Equal,
+        (Ne, Some(o)) => o != Ordering::Equal,
+        (Lt, Some(o)) => o == Ordering::Less,
+        (Le, Some(o)) => o != Ordering::Greater,
+        (Gt, Some(o)) => o == Ordering::Greater,
+        (Ge, Some(o)) => o != Ordering::Less,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> Env {
+        let mut env: Env = vec![("i", Value::Int(7)), ("s", "Kovács".into())]
+            .into_iter()
+            .collect();
+        env.set("x".into(), 0.5.into());
+        env
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $res:expr) => {
+            #[test]
+            fn $test_name() {
+                let result = Expr::parse($in).unwrap().eval(&env());
+                assert_eq!(result, $res)
+            }
+        };
+    }
+
+    test!(int_arithmetic: "1 + 2 * 3 - i" -> Ok(0.into()));
+    test!(mixed_arithmetic: "i + x" -> Ok(7.5.into()));
+    test!(int_div: "i DIV 2" -> Ok(3.into()));
+    test!(modulo: "i MOD 4" -> Ok(3.into()));
+    test!(div: "i / 2" -> Ok(3.5.into()));
+    test!(int_div_negative: "-i DIV 2" -> Ok((-4).into()));
+    test!(mod_negative: "-i MOD 2" -> Ok(1.into()));
+    test!(compare_equal_mixed: "2 = 2.0 AND 1 /= 1.5" -> Ok(true.into()));
+    test!(pow: "2 ^ 10" -> Ok(1024.into()));
+    test!(float_pow: "4 ^ x" -> Ok(2.0.into()));
+    test!(concat: r#"s + "né""# -> Ok("Kovácsné".into()));
+    test!(search: r#"s @ "Kov" AND NOT (s @ 'x')"# -> Ok(true.into()));
+    test!(compare_mixed: "i > 6.5" -> Ok(true.into()));
+    test!(compare_strings: r#""abc" < "abd""# -> Ok(true.into()));
+    test!(short_circuit: "FALSE AND unbound" -> Ok(false.into()));
+    test!(index: "s[4]" -> Ok('c'.into()));
+    test!(slice: "s[0:3]" -> Ok("Kov".into()));
+    test!(empty_slice: "s[6:6]" -> Ok("".into()));
+    test!(upper: "UPPER s" -> Ok("KOVÁCS".into()));
+    test!(is_digit: "IS_DIGIT '7' AND NOT IS_CHAR '7'" -> Ok(true.into()));
+    test!(round: "ROUND 2.5 + INT 2.7" -> Ok(5.into()));
+    test!(to_float: "FLOAT i" -> Ok(7.0.into()));
+    test!(sin: "SIN 0.0" -> Ok(0.0.into()));
+    test!(abs: "|-i|" -> Ok(7.into()));
+
+    test!(unbound: "y + 1" -> Err(EvalError::Unbound("y".into())));
+    test!(div_by_zero: "i DIV 0" -> Err(EvalError::DivisionByZero));
+    test!(real_div_by_zero: "i / 0.0" -> Err(EvalError::DivisionByZero));
+    test!(out_of_range: "s[6]" -> Err(EvalError::IndexOutOfRange { index: 6, len: 6 }));
+    test!(negative_index: "s[-1]" -> Err(EvalError::IndexOutOfRange { index: -1, len: 6 }));
+    test!(reversed_slice: "s[3:1]" -> Err(EvalError::InvalidSlice { start: 3, end: 1 }));
+    test!(log_domain: "LOG 0" -> Err(EvalError::MathDomain { op: UnaryCode::Log, arg: 0.0 }));
+    test!(arcsin_domain: "ARCSIN 2" -> Err(EvalError::MathDomain { op: UnaryCode::Arcsin, arg: 2.0 }));
+    test!(overflow: "2147483647 + 1" -> Err(EvalError::Overflow("+".into())));
+    test!(negative_exponent: "2 ^ -i" -> Err(EvalError::NegativeExponent));
+    test!(mismatch: "s + 1" -> Err(EvalError::TypeMismatch {
+        op: "+".into(),
+        operands: vec![Type::String, Type::Int],
+    }));
+
+    #[test]
+    fn random() {
+        let env = Env::with_seed(42);
+        let expr = Expr::parse("RND 10").unwrap();
+        let values: Vec<_> = (0..100).map(|_| expr.eval(&env).unwrap()).collect();
+        assert!(values.iter().all(|x| matches!(x, Value::Int(0..=9))));
+        assert!(values.iter().any(|x| x != &values[0]));
+
+        let again = Env::with_seed(42);
+        assert_eq!(expr.eval(&again), Ok(values[0].clone()));
+    }
+
+    #[test]
+    fn error_message() {
+        let err = Expr::parse("s + TRUE").unwrap().eval(&env()).unwrap_err();
+        assert_eq!(err.to_string(), "`+` is not defined for String and Bool");
+    }
+}
@@ -1,5 +1,7 @@
 use super::{Binary, BinaryCode, TFloat, TInt, Unary, UnaryCode, Value, VarName};
 use derive_more::From;
+use std::convert::TryFrom;
+use std::fmt;
 
 #[derive(Debug, PartialEq, Clone, From)]
 pub enum Expr {
@@ -35,11 +37,184 @@ impl Expr {
         if let Self::Binary(x) = self {
             x
         } else {
-            panic!("Expr is not a binaBinary expr")
+            panic!("Expr is not a binary expr")
+        }
+    }
+
+    pub fn is_value(&self) -> bool {
+        matches!(self, Self::Value(_))
+    }
+
+    pub fn is_unary(&self) -> bool {
+        matches!(self, Self::Unary(_))
+    }
+
+    pub fn is_binary(&self) -> bool {
+        matches!(self, Self::Binary(_))
+    }
+
+    pub fn is_index(&self) -> bool {
+        matches!(self, Self::Index(..))
+    }
+
+    pub fn is_slice(&self) -> bool {
+        matches!(self, Self::Slice(..))
+    }
+
+    pub fn as_value(&self) -> Option<&Value> {
+        if let Self::Value(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_unary(&self) -> Option<&Unary> {
+        if let Self::Unary(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub fn as_binary(&self) -> Option<&Binary> {
+        if let Self::Binary(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    /// The indexed expression and the index.
+    pub fn as_index(&self) -> Option<(&Expr, &Expr)> {
+        if let Self::Index(x, at) = self {
+            Some((x, at))
+        } else {
+            None
+        }
+    }
+
+    /// The sliced expression, the start and the end of the slice.
+    pub fn as_slice(&self) -> Option<(&Expr, &Expr, &Expr)> {
+        if let Self::Slice(x, start, end) = self {
+            Some((x, start, end))
+        } else {
+            None
+        }
+    }
+
+    /// The name, if the expression is a lone variable.
+    pub fn as_var_name(&self) -> Option<&VarName> {
+        self.as_value().and_then(Value::as_var_name)
+    }
+
+    /// Gives the expression back if it's not a value.
+    pub fn into_value(self) -> Result<Value, Expr> {
+        Value::try_from(self)
+    }
+
+    /// Gives the expression back if it's not a unary expression.
+    pub fn into_unary(self) -> Result<Unary, Expr> {
+        Unary::try_from(self)
+    }
+
+    /// Gives the expression back if it's not a binary expression.
+    pub fn into_binary(self) -> Result<Binary, Expr> {
+        Binary::try_from(self)
+    }
+
+    /// The precedence of the operator at the root, see [`BinaryCode::precedence`].
+    /// Values, indexes and slices bind tightest.
+    pub(crate) fn precedence(&self) -> u8 {
+        match self {
+            Expr::Unary(x) => x.op_code().precedence(),
+            Expr::Binary(x) => x.op_code().precedence(),
+            _ => u8::MAX,
+        }
+    }
+
+    /// Whether the expression can stand on its own in front of `[`.
+    fn is_atomic(&self) -> bool {
+        match self {
+            Expr::Unary(x) => x.op_code().is_wrapping(),
+            Expr::Binary(_) => false,
+            _ => true,
+        }
+    }
+
+    /// Whether the expression can follow a prefix operator without parentheses.
+    pub(crate) fn is_unary_operand(&self) -> bool {
+        !matches!(self, Expr::Binary(_))
+    }
+
+    /// Formats `self` as the operand of another expression.
+    ///
+    /// In the alternate mode every compound operand gets parenthesized.
+    pub(crate) fn fmt_operand(&self, f: &mut fmt::Formatter, parenthesize: bool) -> fmt::Result {
+        let compound = match self {
+            Expr::Binary(_) => true,
+            Expr::Unary(x) => !x.op_code().is_wrapping(),
+            _ => false,
+        };
+        if f.alternate() && compound || parenthesize {
+            write!(f, "(")?;
+            fmt::Display::fmt(self, f)?;
+            write!(f, ")")
+        } else {
+            fmt::Display::fmt(self, f)
+        }
+    }
+}
+
+/// Formats the expression as planggy source.
+///
+/// Parsed expressions are printed back to a string that parses to the same tree,
+/// parentheses are only inserted into hand built trees where the precedence of
+/// the operators requires them. The alternate form (`{:#}`) parenthesizes every
+/// compound subexpression instead, to make the precedence visible.
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Value(x) => write!(f, "{}", x),
+            Expr::Unary(x) => fmt::Display::fmt(x, f),
+            Expr::Binary(x) => fmt::Display::fmt(x, f),
+            Expr::Index(val, at) => {
+                val.fmt_operand(f, !val.is_atomic())?;
+                write!(f, "[")?;
+                fmt::Display::fmt(at, f)?;
+                write!(f, "]")
+            }
+            Expr::Slice(val, start, end) => {
+                val.fmt_operand(f, !val.is_atomic())?;
+                write!(f, "[")?;
+                fmt::Display::fmt(start, f)?;
+                write!(f, ":")?;
+                fmt::Display::fmt(end, f)?;
+                write!(f, "]")
+            }
         }
     }
 }
 
+macro_rules! try_from_expr {
+    ($T:ident) => {
+        impl TryFrom<Expr> for $T {
+            type Error = Expr;
+            fn try_from(x: Expr) -> Result<Self, Self::Error> {
+                if let Expr::$T(x) = x {
+                    Ok(x)
+                } else {
+                    Err(x)
+                }
+            }
+        }
+    };
+}
+
+try_from_expr!(Value);
+try_from_expr!(Unary);
+try_from_expr!(Binary);
+
 macro_rules! from_const {
     ($T:ident) => {
         impl From<$T> for Expr {
@@ -107,10 +282,9 @@ where
 
 #[cfg(test)]
 macro_rules! parse {
-    ($in:tt) => {{
-        let parser = crate::Parser::new();
-        parser.parse($in).unwrap()
-    }};
+    ($in:tt) => {
+        crate::Expr::parse($in).unwrap()
+    };
 }
 
 #[cfg(test)]
@@ -189,6 +363,65 @@ mod tests {
         );
     }
 
+    macro_rules! round_trip {
+        ($test_name:ident: $in:tt) => {
+            #[test]
+            fn $test_name() {
+                let expr = parse!($in);
+                assert_eq!(expr.to_string(), $in);
+                assert_eq!(Expr::parse(&expr.to_string()), Ok(expr));
+            }
+        };
+    }
+
+    round_trip!(display_complex: "1 * 2 + 3 ^ 4 AND 5 = 6");
+    round_trip!(display_parentheses: "(1 + 2) * 3");
+    round_trip!(display_unary: "-x + SIN 1.0 - NOT TRUE");
+    round_trip!(display_nested_unary: "--|x|");
+    round_trip!(display_index: "s[1] @ s[2:i + 1]");
+    round_trip!(display_string: "UPPER \"natus enim\" = s");
+
+    #[test]
+    fn display_adds_needed_parentheses() {
+        let sum = Expr::from((BinaryCode::Add, Expr::from(1), Expr::from(2)));
+        let expr = Expr::from((BinaryCode::Mul, sum.clone(), sum.clone()));
+        assert_eq!(expr.to_string(), "(1 + 2) * (1 + 2)");
+        let expr = Expr::from((BinaryCode::Sub, Expr::from(1), sum.clone()));
+        assert_eq!(expr.to_string(), "1 - (1 + 2)");
+        let expr = Expr::from((UnaryCode::Neg, sum.clone()));
+        assert_eq!(expr.to_string(), "-(1 + 2)");
+        let expr = Expr::Index(sum.boxed(), 1.into());
+        assert_eq!(expr.to_string(), "(1 + 2)[1]");
+        let pow = Expr::from((BinaryCode::Pow, Expr::from(2), Expr::from(3)));
+        let expr = Expr::from((BinaryCode::Pow, pow.clone(), Expr::from(2)));
+        assert_eq!(expr.to_string(), "(2 ^ 3) ^ 2");
+        let expr = Expr::from((BinaryCode::Pow, Expr::from(2), pow));
+        assert_eq!(expr.to_string(), "2 ^ 2 ^ 3");
+    }
+
+    #[test]
+    fn display_alternate() {
+        assert_eq!(
+            format!("{:#}", parse!("1 * 2 + 3 ^ 4 AND 5 = 6")),
+            "((1 * 2) + (3 ^ 4)) AND (5 = 6)"
+        );
+        assert_eq!(format!("{:#}", parse!("(1 + 2) * -3")), "(1 + 2) * (-3)");
+        assert_eq!(format!("{:#}", parse!("NOT a OR b")), "(NOT a) OR b");
+    }
+
+    #[test]
+    fn accessors() {
+        let e = parse!("a + s[1]");
+        let b = e.as_binary().unwrap();
+        assert_eq!(b.lhs().as_var_name(), Some(&VarName::from("a")));
+        assert_eq!(b.rhs().as_var_name(), None);
+        assert_eq!(b.rhs().as_index().map(|(_, at)| at), Some(&Expr::from(1)));
+        assert!(b.rhs().is_index());
+        assert!(e.as_unary().is_none());
+        assert_eq!(e.clone().into_unary(), Err(e.clone()));
+        assert!(Binary::try_from(e).is_ok());
+    }
+
     #[test]
     fn mul_int_plus_int() {
         assert_eq!(
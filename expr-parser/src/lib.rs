@@ -1,31 +1,64 @@
 #[macro_use]
 extern crate lalrpop_util;
 
+mod arena;
 mod binary;
-mod constant;
+mod columnar;
+mod compile;
+mod const_fold;
+mod derivative;
+mod dialect;
+mod eval;
 mod expr;
+pub mod numeric;
+mod operators;
+mod pratt;
+mod rewrite;
+#[cfg(feature = "serde")]
+mod serialize;
+mod simplify;
+mod tokens;
+mod typecheck;
+mod types;
 mod unary;
 mod value;
 mod variable;
+mod visit;
 
-lalrpop_mod!(pub parsers);
+// The patterns of the tokens are only used in submodules of the generated code.
+lalrpop_mod!(#[allow(clippy::all, unused_imports)] pub parsers);
 
-pub use parsers::{
-    AtomicParser, ExprParser as Parser, ExprParser, UnaryOPParser, ValueParser, VarNameParser,
-};
+pub use parsers::{UnaryOPParser, ValueParser, VarNameParser};
 
+pub use arena::{ExprArena, ExprId, Node, Symbol, SymbolTable};
 pub use binary::{Binary, BinaryCode};
-pub use constant::ConstType;
+pub use columnar::{Batch, Column, ColumnError};
+pub use compile::{CompiledExpr, Schema};
+pub use const_fold::FoldWarning;
+pub use derivative::DiffError;
+pub use dialect::{translate, Dialect, Keyword};
+pub use eval::{Env, EvalError};
 pub use expr::Expr;
+pub use operators::{Associativity, Operator, OperatorCode, OperatorTable, Spelling};
+pub use pratt::MAX_DEPTH;
+pub use rewrite::{RewriteRule, Rewritten, RuleSet, Step};
+pub use simplify::Strictness;
+pub use tokens::{tokens, Spanned};
+pub use typecheck::{TypeEnv, TypeError, TypedExpr};
+pub use types::{Signature, Type};
 pub use unary::{Unary, UnaryCode};
 pub use value::{Float as TFloat, Int as TInt, Value};
 pub use variable::VarName;
+pub use visit::{
+    fold_children, walk_binary, walk_binary_mut, walk_expr, walk_expr_mut, walk_unary,
+    walk_unary_mut, walk_value, walk_value_mut, Fold, PostOrder, PreOrder, Visitor, VisitorMut,
+};
 
 impl Expr {
+    /// Parses an expression with the operators of [`OperatorTable::standard`].
     pub fn parse(
         s: &str,
-    ) -> Result<Expr, lalrpop_util::ParseError<usize, lalrpop_util::lexer::Token, &str>> {
-        let parser = Parser::new();
-        parser.parse(s)
+    ) -> Result<Expr, lalrpop_util::ParseError<usize, lexer::Token<'_>, &'static str>> {
+        OperatorTable::shared().parse(s)
     }
 }
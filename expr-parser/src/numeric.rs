@@ -0,0 +1,202 @@
+//! The arithmetic of `Int` and `Float` values, shared by everything that
+//! computes with them.
+//!
+//! - An operation on two `Int`s gives an `Int`, except `/` which always gives
+//!   a `Float`. A negative exponent of `^` on `Int`s is an error, so the type of
+//!   the result never depends on the values.
+//! - If either operand is a `Float`, the other one is promoted and the result
+//!   is a `Float`. Promotion is exact, every `Int` is representable as a `Float`.
+//! - `DIV` and `MOD` take `Int`s only. `DIV` rounds towards negative infinity,
+//!   and `MOD` takes the sign of the divisor, so `-7 DIV 2 = -4` and
+//!   `-7 MOD 2 = 1`. `a = b * (a DIV b) + a MOD b` holds for every `b /= 0`.
+//! - Dividing by zero, with `/`, `DIV` or `MOD`, is an error.
+//! - `Int` results out of the range of [`TInt`] are an error, they never wrap.
+//!   `Float`s follow IEEE 754, so they may overflow into infinity.
+//! - Numbers compare by value regardless of their type, `1 = 1.0` holds.
+
+use super::{BinaryCode, TFloat, TInt, Value};
+use derive_more::Display;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Display)]
+pub enum ArithmeticError {
+    #[display(fmt = "division by zero")]
+    DivisionByZero,
+    #[display(fmt = "overflow")]
+    Overflow,
+    #[display(fmt = "negative exponent")]
+    NegativeExponent,
+}
+
+/// The value of a number as a `Float`, `None` for other values.
+pub fn as_float(x: &Value) -> Option<TFloat> {
+    match *x {
+        Value::Int(x) => Some(x.into()),
+        Value::Float(x) => Some(x),
+        _ => None,
+    }
+}
+
+/// Applies an arithmetic operator to two numbers.
+///
+/// Returns `None` if `op` is not arithmetic or it is not defined for the operands.
+pub fn arithmetic(
+    op: BinaryCode,
+    lhs: &Value,
+    rhs: &Value,
+) -> Option<Result<Value, ArithmeticError>> {
+    use BinaryCode::*;
+    use Value::Int;
+    let checked = |x: Option<TInt>| Some(x.map(Int).ok_or(ArithmeticError::Overflow));
+    match (op, lhs, rhs) {
+        (IntDiv, Int(_), Int(0)) | (Mod, Int(_), Int(0)) => {
+            Some(Err(ArithmeticError::DivisionByZero))
+        }
+        (Add, &Int(a), &Int(b)) => checked(a.checked_add(b)),
+        (Sub, &Int(a), &Int(b)) => checked(a.checked_sub(b)),
+        (Mul, &Int(a), &Int(b)) => checked(a.checked_mul(b)),
+        (IntDiv, &Int(a), &Int(b)) => checked(div_floor(a, b)),
+        (Mod, &Int(a), &Int(b)) => checked(mod_floor(a, b)),
+        (Pow, Int(_), &Int(b)) if b < 0 => Some(Err(ArithmeticError::NegativeExponent)),
+        (Pow, &Int(a), &Int(b)) => checked(a.checked_pow(b as u32)),
+        (Add, _, _) | (Sub, _, _) | (Mul, _, _) | (Div, _, _) | (Pow, _, _) => {
+            let (a, b) = (as_float(lhs)?, as_float(rhs)?);
+            Some(Ok(Value::Float(match op {
+                Add => a + b,
+                Sub => a - b,
+                Mul => a * b,
+                Div if b == 0.0 => return Some(Err(ArithmeticError::DivisionByZero)),
+                Div => a / b,
+                _ => a.powf(b),
+            })))
+        }
+        _ => None,
+    }
+}
+
+fn div_floor(a: TInt, b: TInt) -> Option<TInt> {
+    let q = a.checked_div(b)?;
+    if a % b != 0 && (a < 0) != (b < 0) {
+        Some(q - 1)
+    } else {
+        Some(q)
+    }
+}
+
+fn mod_floor(a: TInt, b: TInt) -> Option<TInt> {
+    // `TInt::MIN % -1` overflows, although every Int is a multiple of -1.
+    if b == -1 {
+        return Some(0);
+    }
+    let r = a.checked_rem(b)?;
+    if r != 0 && (r < 0) != (b < 0) {
+        Some(r + b)
+    } else {
+        Some(r)
+    }
+}
+
+/// Compares two numbers by value, `None` if either is not a number or is NaN.
+pub fn compare(lhs: &Value, rhs: &Value) -> Option<Ordering> {
+    match (lhs, rhs) {
+        (Value::Int(a), Value::Int(b)) => Some(a.cmp(b)),
+        _ => as_float(lhs)?.partial_cmp(&as_float(rhs)?),
+    }
+}
+
+/// Converts a whole `Float` into an `Int`, `None` if it is out of range.
+pub fn float_to_int(x: TFloat) -> Option<TInt> {
+    if x >= TInt::MIN.into() && x <= TInt::MAX.into() {
+        Some(x as TInt)
+    } else {
+        None
+    }
+}
+
+/// Parses the digits of an integer literal.
+pub fn parse_int(digits: &str) -> Result<TInt, &'static str> {
+    digits
+        .parse::<u64>()
+        .ok()
+        .and_then(|x| TInt::try_from(x).ok())
+        .ok_or("integer literal is out of range")
+}
+
+/// Parses a float literal, like `1.5`, `2f` or `2.5f`. Literals too large to
+/// be finite are rejected, as they would print as `inf`.
+pub fn parse_float(literal: &str) -> Result<TFloat, &'static str> {
+    literal
+        .trim_end_matches('f')
+        .parse::<TFloat>()
+        .ok()
+        .filter(|x| x.is_finite())
+        .ok_or("float literal is out of range")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! test {
+        ($test_name:ident: $lhs:literal $op:ident $rhs:literal -> $res:expr) => {
+            #[test]
+            fn $test_name() {
+                let (lhs, rhs) = (Value::from($lhs), Value::from($rhs));
+                assert_eq!(arithmetic(BinaryCode::$op, &lhs, &rhs), $res);
+            }
+        };
+    }
+
+    test!(int_add: 1 Add 2 -> Some(Ok(3.into())));
+    test!(promote: 1 Add 0.5 -> Some(Ok(1.5.into())));
+    test!(real_div: 7 Div 2 -> Some(Ok(3.5.into())));
+    test!(div_zero: 7 Div 0 -> Some(Err(ArithmeticError::DivisionByZero)));
+    test!(int_div: 7 IntDiv 2 -> Some(Ok(3.into())));
+    test!(int_div_neg: -7 IntDiv 2 -> Some(Ok((-4).into())));
+    test!(int_div_neg_divisor: 7 IntDiv -2 -> Some(Ok((-4).into())));
+    test!(int_div_both_neg: -7 IntDiv -2 -> Some(Ok(3.into())));
+    test!(mod_neg: -7 Mod 2 -> Some(Ok(1.into())));
+    test!(mod_neg_divisor: 7 Mod -2 -> Some(Ok((-1).into())));
+    test!(mod_both_neg: -7 Mod -2 -> Some(Ok((-1).into())));
+    test!(mod_zero: 7 Mod 0 -> Some(Err(ArithmeticError::DivisionByZero)));
+    test!(mod_float: 7.0 Mod 2 -> None);
+    test!(int_pow: 3 Pow 3 -> Some(Ok(27.into())));
+    test!(neg_pow: 2 Pow -1 -> Some(Err(ArithmeticError::NegativeExponent)));
+    test!(float_neg_pow: 2.0 Pow -1 -> Some(Ok(0.5.into())));
+    test!(add_overflow: 2147483647 Add 1 -> Some(Err(ArithmeticError::Overflow)));
+    test!(pow_overflow: 2 Pow 31 -> Some(Err(ArithmeticError::Overflow)));
+    test!(div_overflow: -2147483648 IntDiv -1 -> Some(Err(ArithmeticError::Overflow)));
+    test!(mod_min_by_minus_one: -2147483648 Mod -1 -> Some(Ok(0.into())));
+    test!(not_a_number: "a" Add 1 -> None);
+    test!(not_arithmetic: 1 Eq 1 -> None);
+
+    #[test]
+    fn floored_division_identity() {
+        for a in -9..=9 {
+            for b in (-4..=4).filter(|&b| b != 0) {
+                let (q, r) = (div_floor(a, b).unwrap(), mod_floor(a, b).unwrap());
+                assert_eq!(b * q + r, a);
+                assert!(r == 0 || (r < 0) == (b < 0));
+            }
+        }
+    }
+
+    #[test]
+    fn compare_across_types() {
+        assert_eq!(compare(&5.into(), &0.1.into()), Some(Ordering::Greater));
+        assert_eq!(compare(&1.into(), &1.0.into()), Some(Ordering::Equal));
+        assert_eq!(compare(&1.into(), &TFloat::NAN.into()), None);
+        assert_eq!(compare(&1.into(), &'a'.into()), None);
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(parse_int("2147483647"), Ok(TInt::MAX));
+        assert!(parse_int("2147483648").is_err());
+        assert!(parse_int("99999999999999999999999").is_err());
+        assert_eq!(parse_float("2.5f"), Ok(2.5));
+        assert_eq!(parse_float("2f"), Ok(2.0));
+        assert!(parse_float(&format!("{}.0", "9".repeat(320))).is_err());
+    }
+}
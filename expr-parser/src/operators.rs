@@ -0,0 +1,279 @@
+//! The operators the Pratt parser of [`OperatorTable::parse`] is driven by.
+//!
+//! [`OperatorTable::standard`] holds the operators of the language. Host
+//! applications can register more, spelled with symbols or words, building
+//! their expressions out of the existing ones.
+
+use super::{Binary, BinaryCode, Dialect, Expr, Unary, UnaryCode};
+use derive_more::Display;
+use lexer::{Token, TokenKind};
+use std::sync::OnceLock;
+
+/// Which way a chain of operators of the same precedence is grouped.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, Display)]
+pub enum Associativity {
+    /// `a - b - c` is `(a - b) - c`.
+    #[display(fmt = "left")]
+    Left,
+    /// `a ^ b ^ c` is `a ^ (b ^ c)`.
+    #[display(fmt = "right")]
+    Right,
+}
+
+/// How an operator is written.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Spelling {
+    /// A token of the lexer, so a keyword matches in every dialect.
+    Token(TokenKind),
+    /// Tokens without space between them, like `**` or `<>`, or a word.
+    Text(String),
+}
+
+impl Spelling {
+    /// The number of tokens the spelling takes at the start of `tokens`.
+    fn matches(&self, tokens: &[Token]) -> Option<usize> {
+        match self {
+            Spelling::Token(kind) => match tokens.first() {
+                Some(token) if token.kind == *kind => Some(1),
+                _ => None,
+            },
+            Spelling::Text(text) => {
+                let mut rest = text.as_str();
+                let mut end = tokens.first()?.start;
+                for (i, token) in tokens.iter().enumerate() {
+                    if token.start != end || !rest.starts_with(token.text) {
+                        return None;
+                    }
+                    rest = &rest[token.text.len()..];
+                    end = token.end();
+                    if rest.is_empty() {
+                        return Some(i + 1);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+impl From<TokenKind> for Spelling {
+    fn from(kind: TokenKind) -> Self {
+        Spelling::Token(kind)
+    }
+}
+
+impl From<lexer::Keyword> for Spelling {
+    fn from(keyword: lexer::Keyword) -> Self {
+        Spelling::Token(TokenKind::Keyword(keyword))
+    }
+}
+
+impl From<&str> for Spelling {
+    fn from(text: &str) -> Self {
+        Spelling::Text(text.to_string())
+    }
+}
+
+/// What an operator builds out of its operands.
+#[derive(Debug, Copy, Clone)]
+pub enum OperatorCode {
+    Unary(UnaryCode),
+    Binary(BinaryCode),
+    /// A prefix operator of the host.
+    CustomPrefix(fn(Expr) -> Expr),
+    /// An infix operator of the host.
+    CustomInfix(fn(Expr, Expr) -> Expr),
+}
+
+/// An entry of an [`OperatorTable`].
+#[derive(Debug, Clone)]
+pub struct Operator {
+    spelling: Spelling,
+    code: OperatorCode,
+    precedence: u8,
+    associativity: Associativity,
+}
+
+impl Operator {
+    /// A prefix operator, whose operand holds the operators of at least
+    /// `precedence`.
+    pub fn prefix(spelling: impl Into<Spelling>, precedence: u8, code: UnaryCode) -> Self {
+        Operator::new(
+            spelling,
+            OperatorCode::Unary(code),
+            precedence,
+            Associativity::Right,
+        )
+    }
+
+    pub fn infix(
+        spelling: impl Into<Spelling>,
+        precedence: u8,
+        associativity: Associativity,
+        code: BinaryCode,
+    ) -> Self {
+        Operator::new(
+            spelling,
+            OperatorCode::Binary(code),
+            precedence,
+            associativity,
+        )
+    }
+
+    pub fn custom_prefix(
+        spelling: impl Into<Spelling>,
+        precedence: u8,
+        build: fn(Expr) -> Expr,
+    ) -> Self {
+        let code = OperatorCode::CustomPrefix(build);
+        Operator::new(spelling, code, precedence, Associativity::Right)
+    }
+
+    pub fn custom_infix(
+        spelling: impl Into<Spelling>,
+        precedence: u8,
+        associativity: Associativity,
+        build: fn(Expr, Expr) -> Expr,
+    ) -> Self {
+        let code = OperatorCode::CustomInfix(build);
+        Operator::new(spelling, code, precedence, associativity)
+    }
+
+    fn new(
+        spelling: impl Into<Spelling>,
+        code: OperatorCode,
+        precedence: u8,
+        associativity: Associativity,
+    ) -> Self {
+        Operator {
+            spelling: spelling.into(),
+            code,
+            precedence,
+            associativity,
+        }
+    }
+
+    pub fn spelling(&self) -> &Spelling {
+        &self.spelling
+    }
+
+    pub fn code(&self) -> OperatorCode {
+        self.code
+    }
+
+    /// Higher precedences bind tighter.
+    pub fn precedence(&self) -> u8 {
+        self.precedence
+    }
+
+    /// Always [`Associativity::Right`] for prefix operators.
+    pub fn associativity(&self) -> Associativity {
+        self.associativity
+    }
+
+    pub fn is_prefix(&self) -> bool {
+        matches!(
+            self.code,
+            OperatorCode::Unary(_) | OperatorCode::CustomPrefix(_)
+        )
+    }
+
+    /// Applies a prefix operator.
+    pub(crate) fn apply_prefix(&self, operand: Expr) -> Expr {
+        match self.code {
+            OperatorCode::Unary(code) => Unary::from((code, operand)).to_expr(),
+            OperatorCode::CustomPrefix(build) => build(operand),
+            _ => unreachable!("not a prefix operator"),
+        }
+    }
+
+    /// Applies an infix operator.
+    pub(crate) fn apply_infix(&self, lhs: Expr, rhs: Expr) -> Expr {
+        match self.code {
+            OperatorCode::Binary(code) => Binary::from((code, lhs, rhs)).to_expr(),
+            OperatorCode::CustomInfix(build) => build(lhs, rhs),
+            _ => unreachable!("not an infix operator"),
+        }
+    }
+}
+
+/// The operators an expression is parsed with, see the
+/// [module documentation](self).
+#[derive(Debug, Clone, Default)]
+pub struct OperatorTable {
+    operators: Vec<Operator>,
+}
+
+impl OperatorTable {
+    /// A table without operators, which only parses values, names and the
+    /// wrapped, indexed and sliced expressions.
+    pub fn new() -> Self {
+        OperatorTable::default()
+    }
+
+    /// The operators of the language, the ones [`Expr::parse`] uses, as
+    /// [`UnaryCode`] and [`BinaryCode`] describe them.
+    pub fn standard() -> Self {
+        let token = |spelling: &str| match lexer::lex(spelling).next() {
+            Some(token) => token.kind,
+            None => unreachable!("operators are spelled with a token"),
+        };
+        let mut table = OperatorTable::new();
+        for &code in UnaryCode::ALL.iter().filter(|code| !code.is_wrapping()) {
+            let spelling = token(code.spellings(Dialect::English)[0]);
+            table.register(Operator::prefix(spelling, code.precedence(), code));
+        }
+        for &code in &BinaryCode::ALL {
+            let spelling = token(code.spellings(Dialect::English)[0]);
+            let operator = Operator::infix(spelling, code.precedence(), code.associativity(), code);
+            table.register(operator);
+        }
+        table
+    }
+
+    /// The [`standard`](OperatorTable::standard) table, built once for the
+    /// parsers of the crate.
+    pub(crate) fn shared() -> &'static Self {
+        static STANDARD: OnceLock<OperatorTable> = OnceLock::new();
+        STANDARD.get_or_init(OperatorTable::standard)
+    }
+
+    /// Adds an operator, giving back the one of the same spelling and form
+    /// it replaces.
+    pub fn register(&mut self, operator: Operator) -> Option<Operator> {
+        let same =
+            |x: &Operator| x.spelling == operator.spelling && x.is_prefix() == operator.is_prefix();
+        match self.operators.iter_mut().find(|x| same(x)) {
+            Some(x) => Some(std::mem::replace(x, operator)),
+            None => {
+                self.operators.push(operator);
+                None
+            }
+        }
+    }
+
+    pub fn operators(&self) -> &[Operator] {
+        &self.operators
+    }
+
+    /// The prefix operator at the start of `tokens`, with the number of
+    /// tokens it's spelled with.
+    pub fn prefix(&self, tokens: &[Token]) -> Option<(&Operator, usize)> {
+        self.longest(tokens, true)
+    }
+
+    /// The infix operator at the start of `tokens`, with the number of
+    /// tokens it's spelled with.
+    pub fn infix(&self, tokens: &[Token]) -> Option<(&Operator, usize)> {
+        self.longest(tokens, false)
+    }
+
+    /// The operator with the longest spelling, so `**` is found instead of `*`.
+    fn longest(&self, tokens: &[Token], prefix: bool) -> Option<(&Operator, usize)> {
+        self.operators
+            .iter()
+            .filter(|x| x.is_prefix() == prefix)
+            .filter_map(|x| Some((x, x.spelling.matches(tokens)?)))
+            .max_by_key(|&(_, len)| len)
+    }
+}
@@ -0,0 +1,404 @@
+//! A Pratt parser of expressions, which looks the operators up in an
+//! [`OperatorTable`] instead of following the tiers of a grammar.
+//!
+//! It is the only parser of expressions, so the table is the only place the
+//! precedence of the operators is written down. Rewrite rules are parsed by it too.
+
+use super::numeric::{parse_float, parse_int};
+use super::rewrite::check_holes;
+use super::tokens::{parse_char, parse_string};
+use super::{
+    tokens, Associativity, Expr, Operator, OperatorTable, Unary, UnaryCode, Value, VarName,
+};
+use lalrpop_util::ParseError;
+use lexer::{Keyword, Token, TokenKind};
+
+type Error<'a> = ParseError<usize, Token<'a>, &'static str>;
+
+/// How deep brackets, prefix operators and right-associative operators may
+/// nest in an expression. Deeper expressions are parse errors instead of
+/// running out of stack. Chains of left-associative operators don't nest.
+pub const MAX_DEPTH: usize = 100;
+
+const TOO_DEEP: &str = "the expression is nested too deeply";
+
+/// Makes the nodes of the expressions the parser recognizes, so the same
+/// parser builds boxed [`Expr`]s and the nodes of an [`ExprArena`](crate::ExprArena).
+pub(crate) trait Builder {
+    type Node;
+
+    /// A literal or a variable.
+    fn value(&mut self, x: Value) -> Self::Node;
+
+    /// `(x)` or `|x|`.
+    fn wrap(&mut self, code: UnaryCode, x: Self::Node) -> Self::Node;
+
+    fn prefix(&mut self, op: &Operator, x: Self::Node) -> Self::Node;
+
+    fn infix(&mut self, op: &Operator, lhs: Self::Node, rhs: Self::Node) -> Self::Node;
+
+    fn index(&mut self, x: Self::Node, at: Self::Node) -> Self::Node;
+
+    fn slice(&mut self, x: Self::Node, start: Self::Node, end: Self::Node) -> Self::Node;
+}
+
+/// Builds boxed expressions.
+struct Boxed;
+
+impl Builder for Boxed {
+    type Node = Expr;
+
+    fn value(&mut self, x: Value) -> Expr {
+        x.to_expr()
+    }
+
+    fn wrap(&mut self, code: UnaryCode, x: Expr) -> Expr {
+        Unary::from((code, x)).to_expr()
+    }
+
+    fn prefix(&mut self, op: &Operator, x: Expr) -> Expr {
+        op.apply_prefix(x)
+    }
+
+    fn infix(&mut self, op: &Operator, lhs: Expr, rhs: Expr) -> Expr {
+        op.apply_infix(lhs, rhs)
+    }
+
+    fn index(&mut self, x: Expr, at: Expr) -> Expr {
+        Expr::Index(x.boxed(), at.boxed())
+    }
+
+    fn slice(&mut self, x: Expr, start: Expr, end: Expr) -> Expr {
+        Expr::Slice(x.boxed(), start.boxed(), end.boxed())
+    }
+}
+
+impl OperatorTable {
+    /// Parses an expression with the operators of the table.
+    ///
+    /// The errors are of the type the generated parsers give, but instead of
+    /// every token that would fit, they expect an `expression`, an `operator`
+    /// or the token closing a bracket.
+    pub fn parse<'a>(&self, s: &'a str) -> Result<Expr, Error<'a>> {
+        self.parse_with(s, &mut Boxed)
+    }
+
+    /// Parses an expression like [`OperatorTable::parse`], into the nodes of
+    /// `builder`.
+    pub(crate) fn parse_with<'a, B: Builder>(
+        &self,
+        s: &'a str,
+        builder: &mut B,
+    ) -> Result<B::Node, Error<'a>> {
+        let mut parser = Parser::new(self, builder, s);
+        let expr = parser.expr(0)?;
+        parser.end()?;
+        Ok(expr)
+    }
+
+    /// Parses a rewrite rule, `pattern => replacement`, whose sides are
+    /// expressions that may hold `?name` holes.
+    pub(crate) fn parse_rule<'a>(&self, s: &'a str) -> Result<(Expr, Expr), Error<'a>> {
+        let mut builder = Boxed;
+        let mut parser = Parser::new(self, &mut builder, s);
+        parser.holes = true;
+        let lhs = parser.expr(0)?;
+        parser.expect(TokenKind::Arrow, "\"=>\"")?;
+        let rhs = parser.expr(0)?;
+        parser.end()?;
+        check_holes(lhs, rhs).map_err(|error| ParseError::User { error })
+    }
+}
+
+struct Parser<'t, 'a, 'b, B> {
+    table: &'t OperatorTable,
+    builder: &'b mut B,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    /// The length of the source, where the end of input is reported.
+    len: usize,
+    /// Whether `?name` holes are values, as in rewrite rules.
+    holes: bool,
+    /// How many calls of [`Parser::expr`] are running.
+    nesting: usize,
+}
+
+impl<'t, 'a, 'b, B: Builder> Parser<'t, 'a, 'b, B> {
+    fn new(table: &'t OperatorTable, builder: &'b mut B, s: &'a str) -> Self {
+        Parser {
+            table,
+            builder,
+            tokens: tokens(s)
+                .filter_map(Result::ok)
+                .map(|(_, t, _)| t)
+                .collect(),
+            pos: 0,
+            len: s.len(),
+            holes: false,
+            nesting: 0,
+        }
+    }
+
+    /// Expects the end of input after a complete expression.
+    fn end(&self) -> Result<(), Error<'a>> {
+        match self.peek() {
+            Some(_) => Err(self.unexpected(&["operator"])),
+            None => Ok(()),
+        }
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, kind: TokenKind, name: &'static str) -> Result<(), Error<'a>> {
+        match self.peek() {
+            Some(t) if t.kind == kind => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.unexpected(&[name])),
+        }
+    }
+
+    fn unexpected(&self, expected: &[&str]) -> Error<'a> {
+        let expected = expected.iter().map(|x| x.to_string()).collect();
+        match self.peek() {
+            Some(t) => ParseError::UnrecognizedToken {
+                token: (t.start, t, t.end()),
+                expected,
+            },
+            None => ParseError::UnrecognizedEOF {
+                location: self.len,
+                expected,
+            },
+        }
+    }
+
+    /// An expression of the infix operators of at least `min` precedence.
+    fn expr(&mut self, min: u16) -> Result<B::Node, Error<'a>> {
+        if self.nesting == MAX_DEPTH {
+            return Err(ParseError::User { error: TOO_DEEP });
+        }
+        self.nesting += 1;
+        let table = self.table;
+        let mut lhs = self.prefix()?;
+        while let Some((op, len)) = table.infix(&self.tokens[self.pos..]) {
+            let precedence = u16::from(op.precedence());
+            if precedence < min {
+                break;
+            }
+            self.pos += len;
+            let rhs = match op.associativity() {
+                Associativity::Left => self.expr(precedence + 1)?,
+                Associativity::Right => self.expr(precedence)?,
+            };
+            lhs = self.builder.infix(op, lhs, rhs);
+        }
+        self.nesting -= 1;
+        Ok(lhs)
+    }
+
+    fn prefix(&mut self) -> Result<B::Node, Error<'a>> {
+        let table = self.table;
+        match table.prefix(&self.tokens[self.pos..]) {
+            Some((op, len)) => {
+                self.pos += len;
+                let operand = self.expr(op.precedence().into())?;
+                Ok(self.builder.prefix(op, operand))
+            }
+            None => self.postfix(),
+        }
+    }
+
+    /// An atom followed by any number of indexes and slices.
+    fn postfix(&mut self) -> Result<B::Node, Error<'a>> {
+        let mut expr = self.atom()?;
+        while self.peek().map(|t| t.kind) == Some(TokenKind::LBracket) {
+            self.pos += 1;
+            let at = self.expr(0)?;
+            expr = if self.peek().map(|t| t.kind) == Some(TokenKind::Colon) {
+                self.pos += 1;
+                let end = self.expr(0)?;
+                self.builder.slice(expr, at, end)
+            } else {
+                self.builder.index(expr, at)
+            };
+            self.expect(TokenKind::RBracket, "\"]\"")?;
+        }
+        Ok(expr)
+    }
+
+    /// A value, a name or a wrapped expression.
+    fn atom(&mut self) -> Result<B::Node, Error<'a>> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(self.unexpected(&["expression"])),
+        };
+        let user = |error| ParseError::User { error };
+        let value = match token.kind {
+            TokenKind::LParen | TokenKind::Bar => {
+                self.pos += 1;
+                let expr = self.expr(0)?;
+                let code = match token.kind {
+                    TokenKind::LParen => {
+                        self.expect(TokenKind::RParen, "\")\"")?;
+                        UnaryCode::Parentheses
+                    }
+                    _ => {
+                        self.expect(TokenKind::Bar, "\"|\"")?;
+                        UnaryCode::Abs
+                    }
+                };
+                return Ok(self.builder.wrap(code, expr));
+            }
+            TokenKind::Int => Value::Int(parse_int(token.text).map_err(user)?),
+            TokenKind::Float => Value::Float(parse_float(token.text).map_err(user)?),
+            TokenKind::Char => Value::Char(parse_char(token.text).map_err(user)?),
+            TokenKind::String => Value::String(parse_string(token.text).map_err(user)?),
+            TokenKind::Keyword(Keyword::True) => true.into(),
+            TokenKind::Keyword(Keyword::False) => false.into(),
+            TokenKind::Word => VarName::from(token.text).into(),
+            TokenKind::Hole if self.holes => VarName::from(token.text).into(),
+            _ => return Err(self.unexpected(&["expression"])),
+        };
+        self.pos += 1;
+        Ok(self.builder.value(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Associativity, Binary, BinaryCode, Expr, Operator, OperatorTable, Value, MAX_DEPTH,
+    };
+    use lalrpop_util::ParseError;
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $out:tt) => {
+            #[test]
+            fn $test_name() {
+                let e = OperatorTable::standard().parse($in).unwrap();
+                assert_eq!(format!("{:#}", e), $out);
+            }
+        };
+    }
+
+    test!(power_is_right_associative: "2 ^ 3 ^ 2" -> "2 ^ (3 ^ 2)");
+    test!(power_binds_tighter_than_search: "s @ t ^ 2 @ u" -> "(s @ (t ^ 2)) @ u");
+    test!(prefix_binds_tightest: "-x ^ 2 ^ -y" -> "(-x) ^ (2 ^ (-y))");
+    test!(tiers: "NOT a[1] OR 1 + 2 * 3 ^ 4 DIV 5 < 6 AND b" ->
+        "((NOT a[1]) OR ((1 + ((2 * (3 ^ 4)) DIV 5)) < 6)) AND b");
+    test!(wrapped: "|a - (b - c)|[i:j] - 1" -> "|a - (b - c)|[i:j] - 1");
+
+    #[test]
+    fn precedence_of_power() {
+        let parse = |s| format!("{:#}", Expr::parse(s).unwrap());
+        assert_eq!(parse("a @ b ^ c"), "a @ (b ^ c)");
+        assert_eq!(parse("a ^ b @ c"), "(a ^ b) @ c");
+        let e = Expr::parse("2 ^ 3 ^ 2").unwrap();
+        assert_eq!(e.eval(&Default::default()), Ok(Value::Int(512)));
+    }
+
+    #[test]
+    fn errors() {
+        let table = OperatorTable::standard();
+        assert_eq!(
+            table.parse("1 +"),
+            Err(ParseError::UnrecognizedEOF {
+                location: 3,
+                expected: vec!["expression".into()]
+            })
+        );
+        match table.parse("(1 2)") {
+            Err(ParseError::UnrecognizedToken {
+                token: (3, t, 4),
+                expected,
+            }) => {
+                assert_eq!(t.text, "2");
+                assert_eq!(expected, vec!["\")\""]);
+            }
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(
+            table.parse("'ab'"),
+            Err(ParseError::User {
+                error: "a character literal holds exactly one character"
+            })
+        );
+    }
+
+    #[test]
+    fn too_deep() {
+        let too_deep = Err(ParseError::User {
+            error: "the expression is nested too deeply",
+        });
+        let parens = |n| format!("{}x{}", "(".repeat(n), ")".repeat(n));
+        assert!(Expr::parse(&parens(MAX_DEPTH - 1)).is_ok());
+        assert_eq!(Expr::parse(&parens(MAX_DEPTH)), too_deep);
+        assert_eq!(Expr::parse(&parens(50_000)), too_deep);
+        assert_eq!(Expr::parse(&format!("{}x", "-".repeat(50_000))), too_deep);
+        assert_eq!(
+            Expr::parse(&format!("x{}", " ^ 1".repeat(50_000))),
+            too_deep
+        );
+        assert!(Expr::parse(&format!("x{}", " + 1".repeat(MAX_DEPTH * 10))).is_ok());
+    }
+
+    #[test]
+    fn custom_operators() {
+        let mut table = OperatorTable::standard();
+        let pow = BinaryCode::Pow;
+        table.register(Operator::infix("**", 6, Associativity::Right, pow));
+        table.register(Operator::infix(
+            "<>",
+            2,
+            Associativity::Left,
+            BinaryCode::Ne,
+        ));
+        table.register(Operator::custom_infix(
+            "MAX",
+            3,
+            Associativity::Left,
+            |a, b| {
+                let sum = Expr::from((BinaryCode::Add, a.clone(), b.clone()));
+                let diff = Expr::from((BinaryCode::Sub, a, b));
+                let sum = Expr::from((
+                    BinaryCode::Add,
+                    sum,
+                    Expr::from((crate::UnaryCode::Abs, diff)),
+                ));
+                Binary::from((BinaryCode::IntDiv, sum, Expr::from(2))).to_expr()
+            },
+        ));
+        table.register(Operator::custom_prefix("SQUARE", 7, |x| {
+            Expr::from((BinaryCode::Pow, x, Expr::from(2)))
+        }));
+        let parse = |s| table.parse(s).unwrap().to_string();
+        assert_eq!(parse("a ** b ** 2 * c"), "a ^ b ^ 2 * c");
+        assert_eq!(parse("a<>b"), "a /= b");
+        assert_eq!(parse("SQUARE a ** b"), "(a ^ 2) ^ b");
+        assert_eq!(parse("SQUARE a MAX 2"), "(a ^ 2 + 2 + |a ^ 2 - 2|) DIV 2");
+        assert!(table.parse("a < > b").is_err());
+        let env = Default::default();
+        let e = table.parse("3 MAX 5 MAX 4").unwrap();
+        assert_eq!(e.eval(&env), Ok(Value::Int(5)));
+    }
+
+    #[test]
+    fn registering_replaces() {
+        let mut table = OperatorTable::standard();
+        let pow = Operator::infix(
+            lexer::TokenKind::Caret,
+            6,
+            Associativity::Left,
+            BinaryCode::Pow,
+        );
+        let old = table.register(pow).unwrap();
+        assert_eq!(old.associativity(), Associativity::Right);
+        let e = table.parse("2 ^ 3 ^ 2").unwrap();
+        assert_eq!(e.eval(&Default::default()), Ok(Value::Int(64)));
+        assert_eq!(OperatorTable::new().parse("a[1]"), Expr::parse("a[1]"));
+        assert!(OperatorTable::new().parse("1 + 2").is_err());
+    }
+}
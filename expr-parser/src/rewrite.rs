@@ -0,0 +1,391 @@
+use super::{fold_children, tokens, Expr, Fold, OperatorTable, Unary, UnaryCode, Value, VarName};
+use lalrpop_util::ParseError;
+use lexer::Token;
+use std::collections::HashMap;
+use std::fmt;
+
+/// A transformation of expressions, like `?a + ?a => 2 * ?a`.
+///
+/// A hole, a name starting with `?`, matches any subexpression. A hole used more
+/// than once matches equal subexpressions only. Every other node matches itself,
+/// parentheses are ignored on both sides.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RewriteRule {
+    lhs: Expr,
+    rhs: Expr,
+}
+
+impl RewriteRule {
+    /// Parses a rule written as `pattern => replacement`, where both sides are
+    /// expressions of the standard operators extended with holes.
+    ///
+    /// The replacement may only use holes bound by the pattern.
+    pub fn parse(s: &str) -> Result<Self, ParseError<usize, Token<'_>, &'static str>> {
+        let (lhs, rhs) = OperatorTable::shared().parse_rule(s)?;
+        Ok(RewriteRule { lhs, rhs })
+    }
+
+    pub fn lhs(&self) -> &Expr {
+        &self.lhs
+    }
+
+    pub fn rhs(&self) -> &Expr {
+        &self.rhs
+    }
+
+    /// Rewrites `e` if the pattern matches it as a whole.
+    pub fn apply(&self, e: &Expr) -> Option<Expr> {
+        let mut bindings = HashMap::new();
+        if matches(&self.lhs, e, &mut bindings) {
+            Some(Substitute(&bindings).fold_expr(self.rhs.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for RewriteRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} => {}", self.lhs, self.rhs)
+    }
+}
+
+/// Rules applied together, see [`RuleSet::rewrite`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct RuleSet {
+    rules: Vec<RewriteRule>,
+}
+
+impl RuleSet {
+    pub fn new(rules: Vec<RewriteRule>) -> Self {
+        RuleSet { rules }
+    }
+
+    /// Parses one rule per line, lines without a token, like empty lines and
+    /// comments, are skipped.
+    ///
+    /// The locations in the error are relative to the whole string.
+    pub fn parse(s: &str) -> Result<Self, ParseError<usize, Token<'_>, &'static str>> {
+        let mut rules = Vec::new();
+        let mut offset = 0;
+        for line in s.split('\n') {
+            if tokens(line).next().is_some() {
+                let rule = RewriteRule::parse(line).map_err(|e| {
+                    e.map_location(|l| l + offset).map_token(|t| Token {
+                        start: t.start + offset,
+                        ..t
+                    })
+                })?;
+                rules.push(rule);
+            }
+            offset += line.len() + 1;
+        }
+        Ok(RuleSet { rules })
+    }
+
+    pub fn rules(&self) -> &[RewriteRule] {
+        &self.rules
+    }
+
+    /// Rewrites `e` until no rule matches any of its subexpressions.
+    ///
+    /// Subexpressions are rewritten innermost first, trying the rules in order.
+    /// The parentheses around a rewritten subexpression are dropped, formatting
+    /// puts back the ones the precedence of the operators needs.
+    /// Rewriting stops after `step_limit` rewrites, because rules like
+    /// `?a + ?b => ?b + ?a` never reach a fixpoint.
+    pub fn rewrite(&self, e: Expr, step_limit: usize) -> Rewritten {
+        let mut rewriter = Rewriter {
+            rules: &self.rules,
+            step_limit,
+            steps: Vec::new(),
+            complete: true,
+        };
+        let expr = rewriter.fold_expr(e);
+        Rewritten {
+            expr,
+            steps: rewriter.steps,
+            complete: rewriter.complete,
+        }
+    }
+}
+
+impl std::iter::FromIterator<RewriteRule> for RuleSet {
+    fn from_iter<I: IntoIterator<Item = RewriteRule>>(iter: I) -> Self {
+        RuleSet::new(iter.into_iter().collect())
+    }
+}
+
+/// The outcome of [`RuleSet::rewrite`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Rewritten {
+    expr: Expr,
+    steps: Vec<Step>,
+    complete: bool,
+}
+
+impl Rewritten {
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn into_expr(self) -> Expr {
+        self.expr
+    }
+
+    /// Every rewrite, in the order it was made.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Whether no rule matches the result, `false` if the step limit was reached.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
+
+    pub fn into_parts(self) -> (Expr, Vec<Step>) {
+        (self.expr, self.steps)
+    }
+}
+
+/// A rewrite of a subexpression.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Step {
+    rule: String,
+    before: Expr,
+    after: Expr,
+}
+
+impl Step {
+    /// The rule that was applied, like `"?x + 0 => ?x"`.
+    pub fn rule(&self) -> &str {
+        &self.rule
+    }
+
+    /// The subexpression the rule was applied to.
+    pub fn before(&self) -> &Expr {
+        &self.before
+    }
+
+    pub fn after(&self) -> &Expr {
+        &self.after
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}  ->  {}  ({})", self.before, self.after, self.rule)
+    }
+}
+
+struct Rewriter<'a> {
+    rules: &'a [RewriteRule],
+    step_limit: usize,
+    steps: Vec<Step>,
+    complete: bool,
+}
+
+impl Fold for Rewriter<'_> {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        let mut e = match e {
+            Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => {
+                let (code, rhs) = x.into();
+                let steps = self.steps.len();
+                let rhs = self.fold_expr(*rhs);
+                if self.steps.len() > steps {
+                    return rhs;
+                }
+                Unary::new(code, rhs.boxed()).to_expr()
+            }
+            e => fold_children(self, e),
+        };
+        while let Some((rule, after)) = self
+            .rules
+            .iter()
+            .find_map(|rule| rule.apply(&e).map(|after| (rule, after)))
+        {
+            if self.steps.len() == self.step_limit {
+                self.complete = false;
+                break;
+            }
+            self.steps.push(Step {
+                rule: rule.to_string(),
+                before: e,
+                after: after.clone(),
+            });
+            e = fold_children(self, after);
+        }
+        e
+    }
+}
+
+/// Replaces the holes with the subexpressions bound to them.
+struct Substitute<'a>(&'a HashMap<VarName, Expr>);
+
+impl Fold for Substitute<'_> {
+    fn fold_value(&mut self, v: Value) -> Expr {
+        match v {
+            Value::VarName(name) if is_hole(&name) => self.0[&name].clone(),
+            v => v.to_expr(),
+        }
+    }
+}
+
+fn is_hole(name: &VarName) -> bool {
+    name.as_str().starts_with('?')
+}
+
+fn skip_parentheses(mut e: &Expr) -> &Expr {
+    while let Some(x) = e
+        .as_unary()
+        .filter(|x| *x.op_code() == UnaryCode::Parentheses)
+    {
+        e = x.rhs();
+    }
+    e
+}
+
+fn matches(pattern: &Expr, e: &Expr, bindings: &mut HashMap<VarName, Expr>) -> bool {
+    let e = skip_parentheses(e);
+    if let Some(name) = pattern.as_var_name().filter(|name| is_hole(name)) {
+        return match bindings.get(name) {
+            Some(bound) => matches(bound, e, &mut HashMap::new()),
+            None => {
+                bindings.insert(name.clone(), strip_parentheses(e.clone()));
+                true
+            }
+        };
+    }
+    let same_node = match (pattern, e) {
+        (Expr::Value(a), Expr::Value(b)) => a == b,
+        (Expr::Unary(a), Expr::Unary(b)) => a.op_code() == b.op_code(),
+        (Expr::Binary(a), Expr::Binary(b)) => a.op_code() == b.op_code(),
+        (Expr::Index(..), Expr::Index(..)) | (Expr::Slice(..), Expr::Slice(..)) => true,
+        _ => false,
+    };
+    same_node
+        && pattern
+            .children()
+            .zip(e.children())
+            .all(|(pattern, e)| matches(pattern, e, bindings))
+}
+
+/// Removes the `Parentheses` nodes, the grouping is held by the tree.
+pub(crate) fn strip_parentheses(e: Expr) -> Expr {
+    struct Strip;
+    impl Fold for Strip {
+        fn fold_expr(&mut self, e: Expr) -> Expr {
+            match e {
+                Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => {
+                    let (_, rhs) = x.into();
+                    self.fold_expr(*rhs)
+                }
+                e => fold_children(self, e),
+            }
+        }
+    }
+    Strip.fold_expr(e)
+}
+
+/// Checks the holes of a parsed rule, called by the parser of rules.
+pub(crate) fn check_holes(lhs: Expr, rhs: Expr) -> Result<(Expr, Expr), &'static str> {
+    let (lhs, rhs) = (strip_parentheses(lhs), strip_parentheses(rhs));
+    let holes = |e: &Expr| -> Vec<VarName> {
+        e.pre_order()
+            .filter_map(Expr::as_var_name)
+            .filter(|name| is_hole(name))
+            .cloned()
+            .collect()
+    };
+    let bound = holes(&lhs);
+    if holes(&rhs).iter().all(|name| bound.contains(name)) {
+        Ok((lhs, rhs))
+    } else {
+        Err("the replacement uses a hole the pattern does not bind")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewrite(rules: &str, e: &str) -> String {
+        let rules = RuleSet::parse(rules).unwrap();
+        let rewritten = rules.rewrite(Expr::parse(e).unwrap(), 100);
+        assert!(rewritten.is_complete());
+        rewritten.expr().to_string()
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $rules:tt, $in:tt -> $res:tt) => {
+            #[test]
+            fn $test_name() {
+                assert_eq!(rewrite($rules, $in), $res);
+            }
+        };
+    }
+
+    test!(repeated_hole: "?a + ?a => 2 * ?a", "x * y + x * y" -> "2 * (x * y)");
+    test!(different_subexprs: "?a + ?a => 2 * ?a", "x + y" -> "x + y");
+    test!(parentheses: "NOT (?a < ?b) => ?a >= ?b", "NOT ((x) < y + 1)" -> "x >= y + 1");
+    test!(literal: "?x * 1 => ?x", "(a * 1) * 1" -> "a");
+    test!(nested: "?a + ?a => 2 * ?a", "(x + x) + (x + x)" -> "2 * (2 * x)");
+    test!(index: "?s[0] => FIRST", "s[0] + t[1]" -> "FIRST + t[1]");
+    test!(
+        in_order: "?a - ?a => 0\n\n?a + 0 => ?a",
+        "(x - x) + y - (y + 0)" -> "0 + y - y"
+    );
+    test!(fixpoint: "NOT NOT ?b => ?b\n?a = TRUE => ?a", "NOT (NOT (b = TRUE))" -> "b");
+
+    #[test]
+    fn step_limit() {
+        let rules = RuleSet::parse("?a + ?b => ?b + ?a").unwrap();
+        let rewritten = rules.rewrite(Expr::parse("x + y").unwrap(), 3);
+        assert!(!rewritten.is_complete());
+        assert_eq!(rewritten.steps().len(), 3);
+        assert_eq!(rewritten.expr().to_string(), "y + x");
+    }
+
+    #[test]
+    fn steps() {
+        let rules = RuleSet::parse("?a + ?a => 2 * ?a").unwrap();
+        let (_, steps) = rules
+            .rewrite(Expr::parse("x + x").unwrap(), 10)
+            .into_parts();
+        assert_eq!(
+            steps[0].to_string(),
+            "x + x  ->  2 * x  (?a + ?a => 2 * ?a)"
+        );
+    }
+
+    #[test]
+    fn display() {
+        let rule = RewriteRule::parse("NOT (?a < ?b) => ?a >= ?b").unwrap();
+        assert_eq!(rule.to_string(), "NOT (?a < ?b) => ?a >= ?b");
+        assert_eq!(RewriteRule::parse(&rule.to_string()), Ok(rule));
+    }
+
+    #[test]
+    fn unbound_hole() {
+        assert_eq!(
+            RewriteRule::parse("?a * 0 => ?b"),
+            Err(ParseError::User {
+                error: "the replacement uses a hole the pattern does not bind"
+            })
+        );
+    }
+
+    #[test]
+    fn error_location() {
+        let error = RuleSet::parse("?a => ?a\n?a =>").unwrap_err();
+        assert!(matches!(
+            error,
+            ParseError::UnrecognizedEOF { location: 14, .. }
+        ));
+    }
+
+    #[test]
+    fn no_holes_in_expressions() {
+        assert!(Expr::parse("?a + 1").is_err());
+    }
+}
@@ -0,0 +1,185 @@
+//! The JSON representation of the expression tree.
+//!
+//! Every expression is an object tagged with its `kind`:
+//!
+//! ```json
+//! {"kind": "Value", "type": "Int", "value": 1}
+//! {"kind": "Unary", "op": "Neg", "operand": <expr>}
+//! {"kind": "Binary", "op": "Add", "lhs": <expr>, "rhs": <expr>}
+//! {"kind": "Index", "expr": <expr>, "at": <expr>}
+//! {"kind": "Slice", "expr": <expr>, "start": <expr>, "end": <expr>}
+//! ```
+//!
+//! Variable names are stored as `{"type": "VarName", "value": "x"}` values.
+//! `Float`s that are not finite have no JSON numbers, so they fail to serialize.
+
+use super::{Binary, BinaryCode, Expr, TFloat, Unary, UnaryCode, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Writes a finite float, instead of the `null` JSON has for the others,
+/// which wouldn't read back.
+pub(crate) fn finite<S: Serializer>(x: &TFloat, serializer: S) -> Result<S::Ok, S::Error> {
+    match x.is_finite() {
+        true => serializer.serialize_f64(*x),
+        false => Err(serde::ser::Error::custom(format!(
+            "{} has no JSON number",
+            x
+        ))),
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "kind")]
+enum ExprRef<'a> {
+    Value(&'a Value),
+    Unary(&'a Unary),
+    Binary(&'a Binary),
+    Index {
+        expr: &'a Expr,
+        at: &'a Expr,
+    },
+    Slice {
+        expr: &'a Expr,
+        start: &'a Expr,
+        end: &'a Expr,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "kind")]
+enum ExprOwned {
+    Value(Value),
+    Unary(Unary),
+    Binary(Binary),
+    Index {
+        expr: Box<Expr>,
+        at: Box<Expr>,
+    },
+    Slice {
+        expr: Box<Expr>,
+        start: Box<Expr>,
+        end: Box<Expr>,
+    },
+}
+
+impl Serialize for Expr {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Expr::Value(x) => ExprRef::Value(x),
+            Expr::Unary(x) => ExprRef::Unary(x),
+            Expr::Binary(x) => ExprRef::Binary(x),
+            Expr::Index(expr, at) => ExprRef::Index { expr, at },
+            Expr::Slice(expr, start, end) => ExprRef::Slice { expr, start, end },
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Expr {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match ExprOwned::deserialize(deserializer)? {
+            ExprOwned::Value(x) => Expr::Value(x),
+            ExprOwned::Unary(x) => Expr::Unary(x),
+            ExprOwned::Binary(x) => Expr::Binary(x),
+            ExprOwned::Index { expr, at } => Expr::Index(expr, at),
+            ExprOwned::Slice { expr, start, end } => Expr::Slice(expr, start, end),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct UnaryRepr<E> {
+    op: UnaryCode,
+    operand: E,
+}
+
+impl Serialize for Unary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        UnaryRepr {
+            op: *self.op_code(),
+            operand: self.rhs(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Unary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let x = UnaryRepr::<Box<Expr>>::deserialize(deserializer)?;
+        Ok(Unary::new(x.op, x.operand))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct BinaryRepr<E> {
+    op: BinaryCode,
+    lhs: E,
+    rhs: E,
+}
+
+impl Serialize for Binary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BinaryRepr {
+            op: *self.op_code(),
+            lhs: self.lhs(),
+            rhs: self.rhs(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Binary {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let x = BinaryRepr::<Box<Expr>>::deserialize(deserializer)?;
+        Ok(Binary::new(x.op, x.lhs, x.rhs))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $json:expr) => {
+            #[test]
+            fn $test_name() {
+                let expr = Expr::parse($in).unwrap();
+                let json = $json;
+                assert_eq!(serde_json::to_value(&expr).unwrap(), json);
+                assert_eq!(serde_json::from_value::<Expr>(json).unwrap(), expr);
+            }
+        };
+    }
+
+    test!(int: "1" -> json!({"kind": "Value", "type": "Int", "value": 1}));
+    test!(string: r#""a""# -> json!({"kind": "Value", "type": "String", "value": "a"}));
+    test!(var_name: "x" -> json!({"kind": "Value", "type": "VarName", "value": "x"}));
+    test!(unary: "-x" -> json!({
+        "kind": "Unary",
+        "op": "Neg",
+        "operand": {"kind": "Value", "type": "VarName", "value": "x"},
+    }));
+    test!(binary: "TRUE AND 'c'" -> json!({
+        "kind": "Binary",
+        "op": "And",
+        "lhs": {"kind": "Value", "type": "Boolean", "value": true},
+        "rhs": {"kind": "Value", "type": "Char", "value": "c"},
+    }));
+    test!(slice: "s[1][2:3.0]" -> json!({
+        "kind": "Slice",
+        "expr": {
+            "kind": "Index",
+            "expr": {"kind": "Value", "type": "VarName", "value": "s"},
+            "at": {"kind": "Value", "type": "Int", "value": 1},
+        },
+        "start": {"kind": "Value", "type": "Int", "value": 2},
+        "end": {"kind": "Value", "type": "Float", "value": 3.0},
+    }));
+
+    #[test]
+    fn not_finite() {
+        let e = Value::Float(TFloat::INFINITY).to_expr();
+        assert!(serde_json::to_value(&e).is_err());
+    }
+}
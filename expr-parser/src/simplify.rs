@@ -0,0 +1,177 @@
+use super::rewrite::strip_parentheses;
+use super::{Expr, RuleSet, Step};
+use std::sync::OnceLock;
+
+/// How freely [`Expr::simplify`] may rewrite an expression.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Strictness {
+    /// Only rewrites that keep the value of every well-typed expression.
+    #[default]
+    Strict,
+    /// Also rewrites that treat numbers as reals, so they may change the result
+    /// for `NaN`s, infinities, the sign of zero, or turn an `Int` into a `Float`.
+    /// `x * 0` to `0` and `NOT (a < b)` to `a >= b` are such rewrites, and
+    /// `- -x` to `x`, which hides the overflow of negating the smallest `Int`. They may
+    /// also drop an operand that is evaluated first, with its runtime errors,
+    /// like `b AND FALSE` to `FALSE`.
+    Relaxed,
+}
+
+impl Expr {
+    /// Simplifies the expression with algebraic identities like `?x * 1 => ?x`,
+    /// `NOT NOT ?b => ?b` and `?b = TRUE => ?b`, see [`RuleSet::rewrite`].
+    ///
+    /// `Parentheses` nodes are removed, the tree already holds the grouping and
+    /// formatting puts back the parentheses the precedence of the operators needs.
+    /// Constant subexpressions are left to [`Expr::fold_constants`].
+    pub fn simplify(self, strictness: Strictness) -> Expr {
+        self.simplify_steps(strictness).0
+    }
+
+    /// Simplifies the expression like [`Expr::simplify`], also returning every
+    /// rewrite in the order it was made, to show how the expression simplifies.
+    pub fn simplify_steps(self, strictness: Strictness) -> (Expr, Vec<Step>) {
+        // Every rule makes the expression smaller, so the step limit is never reached.
+        let e = strip_parentheses(self);
+        rules(strictness).rewrite(e, usize::MAX).into_parts()
+    }
+}
+
+const STRICT_RULES: &str = "
++?x => ?x
+NOT NOT ?b => ?b
+NOT (?a = ?b) => ?a /= ?b
+NOT (?a /= ?b) => ?a = ?b
+?x - 0 => ?x
+?x * 1 => ?x
+1 * ?x => ?x
+?x ^ 1 => ?x
+?b AND TRUE => ?b
+TRUE AND ?b => ?b
+FALSE AND ?b => FALSE
+?b OR FALSE => ?b
+FALSE OR ?b => ?b
+TRUE OR ?b => TRUE
+?b = TRUE => ?b
+TRUE = ?b => ?b
+?b /= FALSE => ?b
+FALSE /= ?b => ?b
+?b = FALSE => NOT ?b
+FALSE = ?b => NOT ?b
+?b /= TRUE => NOT ?b
+TRUE /= ?b => NOT ?b
+";
+
+const RELAXED_RULES: &str = "
+NOT (?a < ?b) => ?a >= ?b
+NOT (?a <= ?b) => ?a > ?b
+NOT (?a > ?b) => ?a <= ?b
+NOT (?a >= ?b) => ?a < ?b
+- -?x => ?x
+-(?a - ?b) => ?b - ?a
+?x + 0 => ?x
+0 + ?x => ?x
+?x + 0.0 => ?x
+0.0 + ?x => ?x
+?x - 0.0 => ?x
+?x - ?x => 0
+0 - ?x => -?x
+?x * 1.0 => ?x
+1.0 * ?x => ?x
+?x * 0 => 0
+0 * ?x => 0
+?x / 1 => ?x
+0 / ?x => 0
+?x ^ 0 => 1
+?b AND FALSE => FALSE
+?b OR TRUE => TRUE
+";
+
+fn rules(strictness: Strictness) -> &'static RuleSet {
+    static STRICT: OnceLock<RuleSet> = OnceLock::new();
+    static RELAXED: OnceLock<RuleSet> = OnceLock::new();
+    let parse = |s: &str| RuleSet::parse(s).expect("the simplifier rules are well-formed");
+    match strictness {
+        Strictness::Strict => STRICT.get_or_init(|| parse(STRICT_RULES)),
+        Strictness::Relaxed => {
+            RELAXED.get_or_init(|| parse(&format!("{}{}", STRICT_RULES, RELAXED_RULES)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Env, TInt};
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $res:tt) => {
+            test!($test_name: $in, Strict -> $res);
+        };
+        ($test_name:ident: $in:tt, $strictness:ident -> $res:tt) => {
+            #[test]
+            fn $test_name() {
+                let e = Expr::parse($in).unwrap().simplify(Strictness::$strictness);
+                assert_eq!(e.to_string(), $res);
+            }
+        };
+    }
+
+    test!(sub_zero: "x - 0" -> "x");
+    test!(mul_one: "1 * (x * 1)" -> "x");
+    test!(not_not: "NOT NOT b" -> "b");
+    test!(and_true: "b AND TRUE" -> "b");
+    test!(true_or: "TRUE OR b" -> "TRUE");
+    test!(parentheses: "((a + b)) * (c)" -> "(a + b) * c");
+    test!(eq_true: "(a < b) = TRUE" -> "a < b");
+    test!(eq_false: "(a < b) = FALSE" -> "NOT (a < b)");
+    test!(not_eq: "NOT (a = b)" -> "a /= b");
+    test!(cascading: "NOT ((a /= b) = TRUE) AND TRUE" -> "a = b");
+    test!(nested: "(x - 0) * (y ^ 1)" -> "x * y");
+
+    test!(strict_add_zero: "x + 0" -> "x + 0");
+    test!(relaxed_add_zero: "0 + x", Relaxed -> "x");
+    test!(strict_and_false: "1 DIV 0 = 1 AND FALSE" -> "1 DIV 0 = 1 AND FALSE");
+    test!(relaxed_and_false: "b AND FALSE", Relaxed -> "FALSE");
+    test!(strict_or_true: "b OR TRUE" -> "b OR TRUE");
+    test!(relaxed_or_true: "b OR TRUE", Relaxed -> "TRUE");
+    test!(strict_float_zero: "x + 0.0" -> "x + 0.0");
+    test!(relaxed_float_zero: "x + 0.0", Relaxed -> "x");
+    test!(strict_mul_zero: "x * 0" -> "x * 0");
+    test!(relaxed_mul_zero: "x * 0", Relaxed -> "0");
+    test!(strict_not_lt: "NOT (a < b)" -> "NOT (a < b)");
+    test!(relaxed_not_lt: "NOT (a < b)", Relaxed -> "a >= b");
+    test!(relaxed_sub_self: "(x + 1) - (x + 1)", Relaxed -> "0");
+    test!(strict_neg_neg: "-(-x)" -> "--x");
+    test!(relaxed_neg_neg: "-(-x)", Relaxed -> "x");
+    test!(relaxed_neg_sub: "-(a - b)", Relaxed -> "b - a");
+
+    #[test]
+    fn keeps_overflow() {
+        let mut env = Env::new();
+        env.set("x".into(), TInt::MIN.into());
+        let e = Expr::parse("- -x").unwrap();
+        let error = e.eval(&env);
+        assert!(error.is_err());
+        assert_eq!(e.clone().simplify(Strictness::Strict).eval(&env), error);
+        assert_eq!(
+            e.simplify(Strictness::Relaxed).eval(&env),
+            Ok(TInt::MIN.into())
+        );
+    }
+
+    #[test]
+    fn steps() {
+        let e = Expr::parse("NOT NOT (b = TRUE)").unwrap();
+        let (e, steps) = e.simplify_steps(Strictness::Strict);
+        assert_eq!(e.to_string(), "b");
+        let steps: Vec<_> = steps.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            steps,
+            vec![
+                "b = TRUE  ->  b  (?b = TRUE => ?b)",
+                "NOT NOT b  ->  b  (NOT NOT ?b => ?b)",
+            ]
+        );
+    }
+}
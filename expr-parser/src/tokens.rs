@@ -0,0 +1,76 @@
+use super::Keyword;
+use lexer::{Token, TokenKind};
+
+/// A token with its byte range, the items the generated parsers consume.
+pub type Spanned<'a> = Result<(usize, Token<'a>, usize), &'static str>;
+
+/// The tokens of an expression, prepared for the parsers of this crate.
+///
+/// Whitespace, newlines and comments are dropped, and the keywords of
+/// statements and declarations are names in an expression, so `ki + 1`
+/// adds one to the variable `ki`.
+pub fn tokens(s: &str) -> impl Iterator<Item = Spanned<'_>> {
+    lexer::lex(s)
+        .filter(|t| !t.is_trivia() && t.kind != TokenKind::Newline)
+        .map(|mut t| {
+            if let TokenKind::Keyword(keyword) = t.kind {
+                if Keyword::of(keyword).is_none() {
+                    t.kind = TokenKind::Word;
+                }
+            }
+            Ok((t.start, t, t.end()))
+        })
+}
+
+pub(crate) fn parse_char(literal: &str) -> Result<char, &'static str> {
+    let s = lexer::unquote(literal)?;
+    let mut chars = s.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err("a character literal holds exactly one character"),
+    }
+}
+
+pub(crate) fn parse_string(literal: &str) -> Result<String, &'static str> {
+    lexer::unquote(literal)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Expr, VarName};
+    use lalrpop_util::ParseError;
+
+    #[test]
+    fn trivia() {
+        assert_eq!(Expr::parse("1 +\n 2 // two"), Expr::parse("1 + 2"));
+    }
+
+    #[test]
+    fn statement_keywords_are_names() {
+        assert_eq!(Expr::parse("ki"), Ok(VarName::from("ki").to_expr()));
+        let e = Expr::parse("be + String").unwrap();
+        assert_eq!(e.to_string(), "be + String");
+    }
+
+    #[test]
+    fn unknown_token() {
+        match Expr::parse("1 # 2") {
+            Err(ParseError::UnrecognizedToken {
+                token: (2, t, 3), ..
+            }) => assert_eq!(t.text, "#"),
+            x => panic!("{:?}", x),
+        }
+        assert_eq!(
+            Expr::parse("'ab'"),
+            Err(ParseError::User {
+                error: "a character literal holds exactly one character"
+            })
+        );
+        assert_eq!(
+            Expr::parse(&format!("{}.0", "9".repeat(320))),
+            Err(ParseError::User {
+                error: "float literal is out of range"
+            })
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use super::types::display_types;
+use super::{Expr, Type, Value, VarName};
+use derive_more::Display;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The declared types of the variables an expression is checked against.
+pub type TypeEnv = HashMap<VarName, Type>;
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum TypeError {
+    #[display(fmt = "variable `{}` is not declared", _0)]
+    Undeclared(VarName),
+    #[display(fmt = "`{}` is not defined for {}", op, "display_types(operands)")]
+    Mismatch { op: String, operands: Vec<Type> },
+}
+
+/// An expression together with the type of each of its nodes.
+///
+/// The types are kept in pre-order, the same order [`Expr::pre_order`] visits
+/// the nodes in. A node has no type if it, or one of its children, is ill-typed.
+#[derive(Debug, PartialEq, Clone)]
+pub struct TypedExpr {
+    expr: Expr,
+    types: Vec<Option<Type>>,
+}
+
+impl TypedExpr {
+    /// Infers the type of every node of `expr`, pushing the errors found to `errors`.
+    ///
+    /// An error is reported once, at the innermost node it is found in.
+    pub fn check(expr: Expr, env: &TypeEnv, errors: &mut Vec<TypeError>) -> Self {
+        let mut types = Vec::new();
+        infer(&expr, env, &mut types, errors);
+        TypedExpr { expr, types }
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn into_expr(self) -> Expr {
+        self.expr
+    }
+
+    /// The type of the whole expression.
+    pub fn ty(&self) -> Option<&Type> {
+        self.types[0].as_ref()
+    }
+
+    /// The types of the nodes, in pre-order.
+    pub fn types(&self) -> &[Option<Type>] {
+        &self.types
+    }
+
+    /// Iterates over the nodes and their types, parents first.
+    pub fn iter(&self) -> impl Iterator<Item = (&Expr, Option<&Type>)> {
+        self.expr
+            .pre_order()
+            .zip(self.types.iter().map(Option::as_ref))
+    }
+}
+
+impl fmt::Display for TypedExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.expr.fmt(f)
+    }
+}
+
+impl Expr {
+    /// The type of the expression, or every error that prevents it from having one.
+    pub fn type_of(&self, env: &TypeEnv) -> Result<Type, Vec<TypeError>> {
+        let mut errors = Vec::new();
+        let ty = infer(self, env, &mut Vec::new(), &mut errors);
+        ty.ok_or(errors)
+    }
+}
+
+/// Pushes the types of `e` and its children to `types` in pre-order, returns the type of `e`.
+fn infer(
+    e: &Expr,
+    env: &TypeEnv,
+    types: &mut Vec<Option<Type>>,
+    errors: &mut Vec<TypeError>,
+) -> Option<Type> {
+    let at = types.len();
+    types.push(None);
+    let children: Vec<_> = e.children().map(|x| infer(x, env, types, errors)).collect();
+    let operands = children.into_iter().collect::<Option<Vec<_>>>()?;
+    let (op, ty) = match (e, operands.as_slice()) {
+        (Expr::Value(Value::VarName(name)), _) => match env.get(name) {
+            Some(t) => (None, Some(t.clone())),
+            None => {
+                errors.push(TypeError::Undeclared(name.clone()));
+                return None;
+            }
+        },
+        (Expr::Value(x), _) => (None, x.type_of()),
+        (Expr::Unary(x), [t]) => (Some(x.op_code().to_string()), x.op_code().result_type(t)),
+        (Expr::Binary(x), [l, r]) => (Some(x.op_code().to_string()), x.op_code().result_type(l, r)),
+        (Expr::Index(..), [Type::String, Type::Int]) => (None, Some(Type::Char)),
+        (Expr::Index(..), _) => (Some("[]".to_string()), None),
+        (Expr::Slice(..), [Type::String, Type::Int, Type::Int]) => (None, Some(Type::String)),
+        (Expr::Slice(..), _) => (Some("[:]".to_string()), None),
+        _ => unreachable!(),
+    };
+    match (op, &ty) {
+        (Some(op), None) => errors.push(TypeError::Mismatch { op, operands }),
+        _ => types[at] = ty.clone(),
+    }
+    ty
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env() -> TypeEnv {
+        vec![
+            ("i".into(), Type::Int),
+            ("x".into(), Type::Float),
+            ("s".into(), Type::String),
+            ("c".into(), Type::Char),
+            ("b".into(), Type::Bool),
+            ("a".into(), Type::Array(Box::new(Type::Int))),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> $res:expr) => {
+            #[test]
+            fn $test_name() {
+                let result = Expr::parse($in).unwrap().type_of(&env());
+                assert_eq!(result, $res)
+            }
+        };
+    }
+
+    test!(int: "i + 1" -> Ok(Type::Int));
+    test!(promoted: "i * x" -> Ok(Type::Float));
+    test!(div: "i / 2" -> Ok(Type::Float));
+    test!(sin: "SIN i" -> Ok(Type::Float));
+    test!(round: "ROUND x" -> Ok(Type::Int));
+    test!(concat: "s + c" -> Ok(Type::String));
+    test!(search: "s @ c AND b" -> Ok(Type::Bool));
+    test!(index: "s[i]" -> Ok(Type::Char));
+    test!(slice: "UPPER s[0:i]" -> Ok(Type::String));
+    test!(compare: "i < x OR s = \"a\"" -> Ok(Type::Bool));
+    test!(is_digit: "IS_DIGIT s[0]" -> Ok(Type::Bool));
+
+    test!(sin_of_string: "SIN s" -> Err(vec![TypeError::Mismatch {
+        op: "SIN".into(),
+        operands: vec![Type::String],
+    }]));
+    test!(and_of_int: "b AND i" -> Err(vec![TypeError::Mismatch {
+        op: "AND".into(),
+        operands: vec![Type::Bool, Type::Int],
+    }]));
+    test!(search_in_char: "c @ s" -> Err(vec![TypeError::Mismatch {
+        op: "@".into(),
+        operands: vec![Type::Char, Type::String],
+    }]));
+    test!(index_of_int: "i[0]" -> Err(vec![TypeError::Mismatch {
+        op: "[]".into(),
+        operands: vec![Type::Int, Type::Int],
+    }]));
+    test!(compare_arrays: "a = a" -> Err(vec![TypeError::Mismatch {
+        op: "=".into(),
+        operands: vec![Type::Array(Box::new(Type::Int)); 2],
+    }]));
+    test!(undeclared: "y + 1" -> Err(vec![TypeError::Undeclared("y".into())]));
+    test!(not_cascading: "NOT (s - 1) OR y" -> Err(vec![
+        TypeError::Mismatch {
+            op: "-".into(),
+            operands: vec![Type::String, Type::Int],
+        },
+        TypeError::Undeclared("y".into()),
+    ]));
+
+    #[test]
+    fn typed_nodes() {
+        let mut errors = Vec::new();
+        let typed = TypedExpr::check(Expr::parse("s[i] = 'a'").unwrap(), &env(), &mut errors);
+        assert!(errors.is_empty());
+        let types: Vec<_> = typed
+            .iter()
+            .map(|(e, t)| format!("{}: {}", e, t.unwrap()))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                "s[i] = 'a': Bool",
+                "s[i]: Char",
+                "s: String",
+                "i: Int",
+                "'a': Char"
+            ]
+        );
+    }
+
+    #[test]
+    fn error_message() {
+        let errors = Expr::parse("s - 1").unwrap().type_of(&env()).unwrap_err();
+        assert_eq!(
+            errors[0].to_string(),
+            "`-` is not defined for String and Int"
+        );
+    }
+}
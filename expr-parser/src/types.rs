@@ -0,0 +1,208 @@
+use super::{Dialect, Value, VarName};
+use derive_more::Constructor;
+use std::fmt;
+
+/// The type of a value or of a variable, shared by every crate of the workspace.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Type {
+    Bool,
+    Int,
+    Float,
+    Char,
+    String,
+    /// A sequence of elements of the same type.
+    Array(Box<Type>),
+    /// Named fields, in declaration order.
+    Record(Vec<(VarName, Type)>),
+    Function(Box<Signature>),
+}
+
+/// The parameter and return types of a function.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Constructor)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Signature {
+    params: Vec<Type>,
+    ret: Type,
+}
+
+impl Signature {
+    pub fn params(&self) -> &[Type] {
+        &self.params
+    }
+
+    pub fn ret(&self) -> &Type {
+        &self.ret
+    }
+}
+
+impl Type {
+    /// The types a [`Value`] can hold, every type but arrays, records and
+    /// functions.
+    pub const SCALARS: [Type; 5] = [Type::Bool, Type::Int, Type::Float, Type::Char, Type::String];
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+
+    pub fn is_text(&self) -> bool {
+        matches!(self, Type::Char | Type::String)
+    }
+
+    /// Whether a [`Value`] can hold the type.
+    pub fn is_scalar(&self) -> bool {
+        !matches!(self, Type::Array(_) | Type::Record(_) | Type::Function(_))
+    }
+
+    /// The value a declared variable holds before it is first assigned,
+    /// `None` if the type is not scalar.
+    pub fn default_value(&self) -> Option<Value> {
+        Some(match self {
+            Type::Bool => Value::Boolean(false),
+            Type::Int => Value::Int(0),
+            Type::Float => Value::Float(0.0),
+            Type::Char => Value::Char(' '),
+            Type::String => Value::String(String::new()),
+            _ => return None,
+        })
+    }
+
+    /// The spelling of the type in `dialect`.
+    pub fn name(&self, dialect: Dialect) -> String {
+        Named(self, dialect).to_string()
+    }
+}
+
+struct Named<'a>(&'a Type, Dialect);
+
+impl fmt::Display for Named<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Dialect::*;
+        let Named(t, dialect) = *self;
+        let list = |f: &mut fmt::Formatter, types: &mut dyn Iterator<Item = &Type>| {
+            for (i, t) in types.enumerate() {
+                let sep = if i == 0 { "" } else { ", " };
+                write!(f, "{}{}", sep, Named(t, dialect))?;
+            }
+            Ok(())
+        };
+        match (t, dialect) {
+            (Type::Bool, English) => write!(f, "Bool"),
+            (Type::Int, English) => write!(f, "Int"),
+            (Type::Float, English) => write!(f, "Float"),
+            (Type::Char, English) => write!(f, "Char"),
+            (Type::String, English) => write!(f, "String"),
+            (Type::Bool, Hungarian) => write!(f, "Logikai"),
+            (Type::Int, Hungarian) => write!(f, "Egész"),
+            (Type::Float, Hungarian) => write!(f, "Valós"),
+            (Type::Char, Hungarian) => write!(f, "Karakter"),
+            (Type::String, Hungarian) => write!(f, "Szöveg"),
+            (Type::Array(elem), _) => {
+                let array = if dialect == English { "Array" } else { "Tömb" };
+                write!(f, "{}({})", array, Named(elem, dialect))
+            }
+            (Type::Record(fields), _) => {
+                let record = if dialect == English {
+                    "Record"
+                } else {
+                    "Rekord"
+                };
+                write!(f, "{}(", record)?;
+                for (i, (name, t)) in fields.iter().enumerate() {
+                    let sep = if i == 0 { "" } else { ", " };
+                    write!(f, "{}{}: {}", sep, name, Named(t, dialect))?;
+                }
+                write!(f, ")")
+            }
+            (Type::Function(sig), _) => {
+                let function = if dialect == English {
+                    "Function"
+                } else {
+                    "Függvény"
+                };
+                write!(f, "{}(", function)?;
+                list(f, &mut sig.params.iter())?;
+                write!(f, ") -> {}", Named(&sig.ret, dialect))
+            }
+        }
+    }
+}
+
+/// Writes the English name of the type.
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Named(self, Dialect::English).fmt(f)
+    }
+}
+
+impl Value {
+    /// The type of the value, `None` for a variable name.
+    pub fn type_of(&self) -> Option<Type> {
+        Some(match self {
+            Value::Boolean(_) => Type::Bool,
+            Value::Int(_) => Type::Int,
+            Value::Float(_) => Type::Float,
+            Value::Char(_) => Type::Char,
+            Value::String(_) => Type::String,
+            Value::VarName(_) => return None,
+        })
+    }
+}
+
+/// Lists the types of the operands of an operator, for error messages.
+pub(crate) fn display_types(types: &[Type]) -> String {
+    let types: Vec<_> = types.iter().map(ToString::to_string).collect();
+    types.join(" and ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn type_of() {
+        assert_eq!(Value::from(1).type_of(), Some(Type::Int));
+        assert_eq!(Value::from("a").type_of(), Some(Type::String));
+        assert_eq!(Value::from(VarName::from("a")).type_of(), None);
+    }
+
+    #[test]
+    fn names() {
+        let point = Type::Record(vec![("x".into(), Type::Float), ("y".into(), Type::Float)]);
+        let f = Type::Function(Box::new(Signature::new(
+            vec![Type::Array(Box::new(point.clone())), Type::Int],
+            Type::Bool,
+        )));
+        assert_eq!(point.to_string(), "Record(x: Float, y: Float)");
+        assert_eq!(
+            f.to_string(),
+            "Function(Array(Record(x: Float, y: Float)), Int) -> Bool"
+        );
+        assert_eq!(
+            f.name(Dialect::Hungarian),
+            "Függvény(Tömb(Rekord(x: Valós, y: Valós)), Egész) -> Logikai"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        use serde_json::json;
+        let t = Type::Function(Box::new(Signature::new(
+            vec![Type::Record(vec![("x".into(), Type::Int)])],
+            Type::Array(Box::new(Type::Char)),
+        )));
+        let json = json!({"Function": {
+            "params": [{"Record": [["x", "Int"]]}],
+            "ret": {"Array": "Char"},
+        }});
+        assert_eq!(serde_json::to_value(&t).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Type>(json).unwrap(), t);
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(Type::Char.default_value(), Some(Value::Char(' ')));
+        assert_eq!(Type::Array(Box::new(Type::Int)).default_value(), None);
+    }
+}
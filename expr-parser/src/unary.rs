@@ -1,50 +1,166 @@
-use super::Expr;
-use derive_more::{Constructor, Display, From, Into};
+use super::{Associativity, Dialect, Expr, Signature, Type};
+use derive_more::{Constructor, From, Into};
+use std::fmt;
 
-#[derive(Debug, PartialEq, Copy, Clone, Hash, Display)]
+/// The unary operators, described by their methods like
+/// [`BinaryCode`](crate::BinaryCode).
+#[derive(Debug, PartialEq, Copy, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnaryCode {
     Abs,
     Parentheses,
-    #[display(fmt = "+")]
     Plus,
-    #[display(fmt = "-")]
     Neg,
-    #[display(fmt = "RND")]
     Random,
-    #[display(fmt = "SIN")]
     Sin,
-    #[display(fmt = "COS")]
     Cos,
-    #[display(fmt = "TAN")]
     Tan,
-    #[display(fmt = "EXP")]
     Exp,
-    #[display(fmt = "LOG")]
     Log,
-    #[display(fmt = "ARCSIN")]
     Arcsin,
-    #[display(fmt = "ARCCOS")]
     Arccos,
-    #[display(fmt = "ARCTAN")]
     Arctan,
-    #[display(fmt = "NOT")]
     Not,
-    #[display(fmt = "UPPER")]
     ToUpper,
-    #[display(fmt = "CHAR")]
     IsWordChar,
-    #[display(fmt = "ROUND")]
     Round,
-    #[display(fmt = "LOWER")]
     ToLower,
-    #[display(fmt = "DIGIT")]
     IsDigit,
-    #[display(fmt = "FLOAT")]
     ToFloat,
-    #[display(fmt = "INT")]
     ToInt,
 }
 
+impl UnaryCode {
+    /// Every operator. Bytecode files store operators by their position here, so
+    /// new ones go at the end.
+    pub const ALL: [UnaryCode; 21] = {
+        use UnaryCode::*;
+        [
+            Abs,
+            Parentheses,
+            Plus,
+            Neg,
+            Random,
+            Sin,
+            Cos,
+            Tan,
+            Exp,
+            Log,
+            Arcsin,
+            Arccos,
+            Arctan,
+            Not,
+            ToUpper,
+            IsWordChar,
+            Round,
+            ToLower,
+            IsDigit,
+            ToFloat,
+            ToInt,
+        ]
+    };
+
+    /// Wrapping operators enclose their operand instead of preceding it.
+    pub fn is_wrapping(self) -> bool {
+        matches!(self, UnaryCode::Abs | UnaryCode::Parentheses)
+    }
+
+    /// Prefix operators bind tighter than every binary operator, so `-x ^ 2`
+    /// is `(-x) ^ 2`, see [`BinaryCode::precedence`](crate::BinaryCode::precedence).
+    /// Wrapping operators bind tightest.
+    pub fn precedence(self) -> u8 {
+        match self.is_wrapping() {
+            true => u8::MAX,
+            false => 7,
+        }
+    }
+
+    /// Prefix operators nest to the right, `- - x` is `-(-x)`.
+    pub fn associativity(self) -> Associativity {
+        Associativity::Right
+    }
+
+    /// The number of operands, always 1.
+    pub fn arity(self) -> usize {
+        1
+    }
+
+    /// The spellings of the operator in `dialect`, the canonical one first.
+    /// Hungarian words are listed with and without accents, the lexer also
+    /// takes them with any accents that fold to the same letters, like `BETÜ`.
+    /// Wrapping operators are listed with both of their delimiters.
+    pub fn spellings(self, dialect: Dialect) -> &'static [&'static str] {
+        use Dialect::*;
+        use UnaryCode::*;
+        match (self, dialect) {
+            (Abs, _) => &["||"],
+            (Parentheses, _) => &["()"],
+            (Plus, _) => &["+"],
+            (Neg, _) => &["-"],
+            (Random, _) => &["RND", "rnd"],
+            (Sin, _) => &["SIN", "sin"],
+            (Cos, _) => &["COS", "cos"],
+            (Tan, _) => &["TAN", "tan"],
+            (Exp, _) => &["EXP", "exp"],
+            (Log, _) => &["LOG", "log"],
+            (Arcsin, _) => &["ARCSIN", "arcsin"],
+            (Arccos, _) => &["ARCCOS", "arccos"],
+            (Arctan, _) => &["ARCTAN", "arctan"],
+            (Not, English) => &["NOT", "not"],
+            (Not, Hungarian) => &["NEM", "nem"],
+            (ToUpper, English) => &["UPPER", "upper"],
+            (ToUpper, Hungarian) => &["NAGY", "nagy"],
+            (IsWordChar, English) => &["IS_CHAR", "is_char"],
+            (IsWordChar, Hungarian) => &["BETU", "betu", "BETŰ", "betű"],
+            (Round, English) => &["ROUND", "round"],
+            (Round, Hungarian) => &["KEREK", "kerek"],
+            (ToLower, English) => &["LOWER", "lower"],
+            (ToLower, Hungarian) => &["KICSI", "kicsi"],
+            (IsDigit, English) => &["IS_DIGIT", "is_digit"],
+            (IsDigit, Hungarian) => &["SZÁM", "szám", "SZAM", "szam"],
+            (ToFloat, English) => &["FLOAT", "Float", "float"],
+            (ToFloat, Hungarian) => &["VALÓS", "Valós", "valós", "VALOS", "Valos", "valos"],
+            (ToInt, English) => &["INT", "Int", "int"],
+            (ToInt, Hungarian) => &["EGÉSZ", "Egész", "egész", "EGESZ", "Egesz", "egesz"],
+        }
+    }
+
+    /// The type of the result, `None` if the operator doesn't take an
+    /// operand of this type.
+    pub fn result_type(self, t: &Type) -> Option<Type> {
+        use Type::*;
+        use UnaryCode::*;
+        Some(match self {
+            Parentheses => t.clone(),
+            Plus | Neg | Abs | Random if t.is_number() => t.clone(),
+            Sin | Cos | Tan | Exp | Log | Arcsin | Arccos | Arctan | ToFloat if t.is_number() => {
+                Float
+            }
+            Round | ToInt if t.is_number() => Int,
+            Not if *t == Bool => Bool,
+            ToUpper | ToLower if t.is_text() => t.clone(),
+            IsWordChar | IsDigit if *t == Char => Bool,
+            _ => return None,
+        })
+    }
+
+    /// The operand and result types of every overload of the operator.
+    /// Parentheses take operands of any type, not only the scalars listed.
+    pub fn signatures(self) -> Vec<Signature> {
+        Type::SCALARS
+            .iter()
+            .filter_map(|t| Some(Signature::new(vec![t.clone()], self.result_type(t)?)))
+            .collect()
+    }
+}
+
+/// The canonical English spelling.
+impl fmt::Display for UnaryCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.spellings(Dialect::English)[0])
+    }
+}
+
 #[derive(Debug, PartialEq, From, Clone, Into, Constructor)]
 pub struct Unary(UnaryCode, Box<Expr>);
 
@@ -57,11 +173,33 @@ impl Unary {
         &self.1
     }
 
+    pub fn rhs_mut(&mut self) -> &mut Expr {
+        &mut self.1
+    }
+
     pub fn to_expr(self) -> Expr {
         self.into()
     }
 }
 
+impl fmt::Display for Unary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rhs = self.rhs();
+        match self.op_code() {
+            UnaryCode::Parentheses => write!(f, "({})", rhs),
+            UnaryCode::Abs => write!(f, "|{}|", rhs),
+            code @ (UnaryCode::Plus | UnaryCode::Neg) => {
+                write!(f, "{}", code)?;
+                rhs.fmt_operand(f, !rhs.is_unary_operand())
+            }
+            code => {
+                write!(f, "{} ", code)?;
+                rhs.fmt_operand(f, !rhs.is_unary_operand())
+            }
+        }
+    }
+}
+
 impl From<(UnaryCode, Expr)> for Unary {
     fn from((code, expr): (UnaryCode, Expr)) -> Self {
         (code, Box::new(expr)).into()
@@ -73,8 +211,7 @@ macro_rules! test {
     ($test_name:ident: $in:tt -> $code:tt + $expr:expr) => {
         #[test]
         fn $test_name() {
-            let parser = crate::Parser::new();
-            let result = parser.parse($in).unwrap();
+            let result = crate::Expr::parse($in).unwrap();
             let expected = Unary::from((crate::UnaryCode::$code, Box::new($expr)));
             assert_eq!(result, expected.to_expr())
         }
@@ -90,4 +227,61 @@ mod tests {
 
     test!(parentheses: "(1)" -> Parentheses + 1.into());
     test!(abs: "|1|" -> Abs + 1.into());
+
+    #[test]
+    fn display_codes_parse_back() {
+        for &code in UnaryCode::ALL.iter().filter(|code| !code.is_wrapping()) {
+            let expected = Unary::from((code, Expr::from(1)));
+            assert_eq!(
+                crate::Expr::parse(&expected.to_string()),
+                Ok(expected.to_expr())
+            );
+        }
+    }
+
+    #[test]
+    fn spellings_parse() {
+        for &code in UnaryCode::ALL.iter().filter(|code| !code.is_wrapping()) {
+            for &dialect in &[Dialect::English, Dialect::Hungarian] {
+                for spelling in code.spellings(dialect) {
+                    let expected = Unary::from((code, Expr::from(1)));
+                    assert_eq!(
+                        crate::Expr::parse(&format!("{} 1", spelling)),
+                        Ok(expected.to_expr())
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn lexer_spellings_are_listed() {
+        for &code in &UnaryCode::ALL {
+            let listed = [
+                code.spellings(Dialect::English),
+                code.spellings(Dialect::Hungarian),
+            ]
+            .concat();
+            if let Some(keyword) = lexer::Keyword::parse(listed[0]) {
+                for spelling in keyword.spellings() {
+                    assert!(listed.contains(spelling), "{} of {:?}", spelling, code);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn signatures() {
+        use Type::*;
+        let signature = |param, ret| Signature::new(vec![param], ret);
+        assert_eq!(UnaryCode::Not.signatures(), vec![signature(Bool, Bool)]);
+        assert_eq!(
+            UnaryCode::Round.signatures(),
+            vec![signature(Int, Int), signature(Float, Int)]
+        );
+        assert_eq!(
+            UnaryCode::Parentheses.signatures().len(),
+            Type::SCALARS.len()
+        );
+    }
 }
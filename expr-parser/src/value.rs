@@ -1,13 +1,22 @@
-use super::{Expr, VarName};
-use derive_more::{Display, From};
+use super::{numeric, Expr, VarName};
+use derive_more::From;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
 
 pub type Int = i32;
 pub type Float = f64;
 
-#[derive(Debug, PartialEq, Clone, PartialOrd, From, Display)]
+#[derive(Debug, PartialEq, Clone, From)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(tag = "type", content = "value")
+)]
 pub enum Value {
     Boolean(bool),
     Int(Int),
+    #[cfg_attr(feature = "serde", serde(serialize_with = "crate::serialize::finite"))]
     Float(Float),
     Char(char),
     String(String),
@@ -60,6 +69,117 @@ impl Value {
     }
 }
 
+macro_rules! accessors {
+    ($variant:ident: $T:ty, $is:ident, $as:ident) => {
+        pub fn $is(&self) -> bool {
+            matches!(self, Self::$variant(_))
+        }
+
+        pub fn $as(&self) -> Option<$T> {
+            if let Self::$variant(x) = self {
+                Some(*x)
+            } else {
+                None
+            }
+        }
+    };
+}
+
+impl Value {
+    accessors!(Boolean: bool, is_boolean, as_boolean);
+    accessors!(Int: Int, is_int, as_int);
+    accessors!(Float: Float, is_float, as_float);
+    accessors!(Char: char, is_char, as_char);
+
+    pub fn is_string(&self) -> bool {
+        matches!(self, Self::String(_))
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        if let Self::String(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+
+    pub fn is_var_name(&self) -> bool {
+        matches!(self, Self::VarName(_))
+    }
+
+    pub fn as_var_name(&self) -> Option<&VarName> {
+        if let Self::VarName(x) = self {
+            Some(x)
+        } else {
+            None
+        }
+    }
+}
+
+macro_rules! try_from_value {
+    ($variant:ident: $T:ty) => {
+        /// Gives the value back if it holds a different variant.
+        impl TryFrom<Value> for $T {
+            type Error = Value;
+            fn try_from(x: Value) -> Result<Self, Self::Error> {
+                if let Value::$variant(x) = x {
+                    Ok(x)
+                } else {
+                    Err(x)
+                }
+            }
+        }
+    };
+}
+
+try_from_value!(Boolean: bool);
+try_from_value!(Int: Int);
+try_from_value!(Float: Float);
+try_from_value!(Char: char);
+try_from_value!(String: String);
+try_from_value!(VarName: VarName);
+
+/// Numbers are ordered by value, see [`numeric::compare`], other values only
+/// against values of the same variant.
+///
+/// An `Int` and a `Float` of the same value are unordered, because they are not `==`.
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+            (Value::Char(a), Value::Char(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::VarName(a), Value::VarName(b)) => a.partial_cmp(b),
+            _ => numeric::compare(self, other).filter(|&o| o != Ordering::Equal || self == other),
+        }
+    }
+}
+
+/// Formats the value as a literal that parses back to the same value.
+///
+/// Negative numbers are printed with a leading `-`, which the parser reads
+/// as a [`UnaryCode::Neg`](crate::UnaryCode::Neg) applied to the absolute value.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Boolean(true) => write!(f, "TRUE"),
+            Value::Boolean(false) => write!(f, "FALSE"),
+            Value::Int(x) => write!(f, "{}", x),
+            Value::Float(x) => {
+                let s = x.to_string();
+                if x.is_finite() && !s.contains('.') {
+                    write!(f, "{}.0", s)
+                } else {
+                    write!(f, "{}", s)
+                }
+            }
+            Value::Char(x) => f.write_str(&lexer::quote(&x.to_string(), '\'')),
+            Value::String(x) => f.write_str(&lexer::quote(x, '"')),
+            Value::VarName(x) => write!(f, "{}", x),
+        }
+    }
+}
+
 impl From<&str> for Value {
     fn from(x: &str) -> Self {
         x.to_string().into()
@@ -71,8 +191,7 @@ macro_rules! test {
     ($test_name:ident: $in:tt -> $res:expr) => {
         #[test]
         fn $test_name() {
-            let parser = crate::Parser::new();
-            let result = parser.parse($in).unwrap();
+            let result = crate::Expr::parse($in).unwrap();
             let expected: crate::Value = $res;
             let expected = expected.to_expr();
             assert_eq!(result, expected)
@@ -80,6 +199,59 @@ macro_rules! test {
     };
 }
 
+#[cfg(test)]
+macro_rules! display {
+    ($test_name:ident: $val:expr => $out:tt) => {
+        #[test]
+        fn $test_name() {
+            let val: crate::Value = $val;
+            assert_eq!(val.to_string(), $out);
+            assert_eq!(crate::Expr::parse($out).unwrap(), val.to_expr());
+        }
+    };
+}
+
+#[cfg(test)]
+mod accessors {
+    use crate::{Value, VarName};
+    use std::convert::TryFrom;
+
+    #[test]
+    fn as_() {
+        assert_eq!(Value::from(1).as_int(), Some(1));
+        assert_eq!(Value::from(1).as_float(), None);
+        assert_eq!(Value::from("a").as_str(), Some("a"));
+        assert_eq!(Value::from('a').as_str(), None);
+        assert_eq!(
+            Value::from(VarName::from("a")).as_var_name(),
+            Some(&"a".into())
+        );
+        assert!(Value::from(true).is_boolean());
+        assert!(!Value::from(true).is_char());
+    }
+
+    #[test]
+    fn try_from() {
+        assert_eq!(String::try_from(Value::from("a")), Ok("a".to_string()));
+        assert_eq!(bool::try_from(Value::from(1.0)), Err(Value::from(1.0)));
+    }
+}
+
+#[cfg(test)]
+mod display {
+    display!(bool_true: true.into() => "TRUE");
+    display!(bool_false: false.into() => "FALSE");
+    display!(int: 42.into() => "42");
+    display!(whole_float: 100.0.into() => "100.0");
+    display!(float: 0.25.into() => "0.25");
+    display!(char: 'a'.into() => "'a'");
+    display!(string: "natus enim".into() => "\"natus enim\"");
+    display!(empty_string: "".into() => "\"\"");
+    display!(escaped_string: "say \"hi\"\n".into() => r#""say \"hi\"\n""#);
+    display!(escaped_char: '\''.into() => r"'\''");
+    display!(var_name: crate::VarName::from("foo").into() => "foo");
+}
+
 #[cfg(test)]
 mod bool {
     test!(uppercase_igaz: "IGAZ" -> true.into());
@@ -99,6 +271,31 @@ mod int {
     test!(one: "1" -> 1.into());
 
     test!(hundred: "100" -> 100.into());
+    test!(max: "2147483647" -> 2147483647.into());
+
+    #[test]
+    fn out_of_range() {
+        assert!(crate::Expr::parse("2147483648").is_err());
+    }
+}
+
+#[cfg(test)]
+mod ordering {
+    use crate::Value;
+
+    #[test]
+    fn numbers_by_value() {
+        assert!(Value::from(5) > Value::from(0.1));
+        assert!(Value::from(-1.5) < Value::from(0));
+        assert!(Value::from(1) <= Value::from(1));
+    }
+
+    #[test]
+    fn unordered() {
+        assert_eq!(Value::from(1).partial_cmp(&Value::from(1.0)), None);
+        assert_eq!(Value::from(1).partial_cmp(&Value::from("1")), None);
+        assert!(Value::from("a") < Value::from("b"));
+    }
 }
 
 #[cfg(test)]
@@ -116,9 +313,8 @@ mod float {
 mod char {
     test!(a: "'a'" -> 'a'.into());
     test!(digit: "'8'" -> '8'.into());
-
-    // TODO: single quote support
-    // test!(single_quote: r"'\'''" -> '\''.into());
+    test!(single_quote: r"'\''" -> '\''.into());
+    test!(escape: r"'\n'" -> '\n'.into());
 }
 
 #[cfg(test)]
@@ -127,7 +323,6 @@ mod string {
     test!(one_char: r#""a""# -> "a".into());
     test!(words: r#""natus enim harum""# -> "natus enim harum".into());
     test!(specials: "\"natus\n\0 enim harum\"" -> "natus\n\0 enim harum".into());
-
-    // TODO: double quote support
-    // test!(double_quote: r#""natus\" enim harum""# -> "natus\" enim harum".into());
+    test!(double_quote: r#""natus\" enim harum""# -> "natus\" enim harum".into());
+    test!(escapes: r#""a\tb\\""# -> "a\tb\\".into());
 }
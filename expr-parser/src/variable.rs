@@ -2,13 +2,29 @@ use derive_more::{Constructor, Display, From};
 
 use super::{Expr, Value};
 
-#[derive(Debug, PartialEq, Clone, PartialOrd, From, Display, Constructor)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, PartialOrd, From, Display, Constructor)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct VarName(String);
 
 impl VarName {
     pub fn to_expr(self) -> Expr {
         Value::VarName(self).into()
     }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Lets maps keyed by names be looked up with a `&str`.
+impl std::borrow::Borrow<str> for VarName {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
 }
 
 impl From<&str> for VarName {
@@ -0,0 +1,277 @@
+use super::{Binary, Expr, Unary, Value, VarName};
+
+/// Walks an expression tree by reference.
+///
+/// Every method defaults to walking the children of its node, so an
+/// implementation only has to override the nodes it is interested in.
+/// Call the matching `walk_*` function from an override to keep descending.
+pub trait Visitor {
+    fn visit_expr(&mut self, e: &Expr) {
+        walk_expr(self, e)
+    }
+
+    fn visit_value(&mut self, v: &Value) {
+        walk_value(self, v)
+    }
+
+    fn visit_var_name(&mut self, _: &VarName) {}
+
+    fn visit_unary(&mut self, u: &Unary) {
+        walk_unary(self, u)
+    }
+
+    fn visit_binary(&mut self, b: &Binary) {
+        walk_binary(self, b)
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, e: &Expr) {
+    match e {
+        Expr::Value(x) => v.visit_value(x),
+        Expr::Unary(x) => v.visit_unary(x),
+        Expr::Binary(x) => v.visit_binary(x),
+        Expr::Index(x, at) => {
+            v.visit_expr(x);
+            v.visit_expr(at);
+        }
+        Expr::Slice(x, start, end) => {
+            v.visit_expr(x);
+            v.visit_expr(start);
+            v.visit_expr(end);
+        }
+    }
+}
+
+pub fn walk_value<V: Visitor + ?Sized>(v: &mut V, x: &Value) {
+    if let Value::VarName(name) = x {
+        v.visit_var_name(name)
+    }
+}
+
+pub fn walk_unary<V: Visitor + ?Sized>(v: &mut V, u: &Unary) {
+    v.visit_expr(u.rhs())
+}
+
+pub fn walk_binary<V: Visitor + ?Sized>(v: &mut V, b: &Binary) {
+    v.visit_expr(b.lhs());
+    v.visit_expr(b.rhs());
+}
+
+/// Walks an expression tree by mutable reference, see [`Visitor`].
+pub trait VisitorMut {
+    fn visit_expr_mut(&mut self, e: &mut Expr) {
+        walk_expr_mut(self, e)
+    }
+
+    fn visit_value_mut(&mut self, v: &mut Value) {
+        walk_value_mut(self, v)
+    }
+
+    fn visit_var_name_mut(&mut self, _: &mut VarName) {}
+
+    fn visit_unary_mut(&mut self, u: &mut Unary) {
+        walk_unary_mut(self, u)
+    }
+
+    fn visit_binary_mut(&mut self, b: &mut Binary) {
+        walk_binary_mut(self, b)
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, e: &mut Expr) {
+    match e {
+        Expr::Value(x) => v.visit_value_mut(x),
+        Expr::Unary(x) => v.visit_unary_mut(x),
+        Expr::Binary(x) => v.visit_binary_mut(x),
+        Expr::Index(x, at) => {
+            v.visit_expr_mut(x);
+            v.visit_expr_mut(at);
+        }
+        Expr::Slice(x, start, end) => {
+            v.visit_expr_mut(x);
+            v.visit_expr_mut(start);
+            v.visit_expr_mut(end);
+        }
+    }
+}
+
+pub fn walk_value_mut<V: VisitorMut + ?Sized>(v: &mut V, x: &mut Value) {
+    if let Value::VarName(name) = x {
+        v.visit_var_name_mut(name)
+    }
+}
+
+pub fn walk_unary_mut<V: VisitorMut + ?Sized>(v: &mut V, u: &mut Unary) {
+    v.visit_expr_mut(u.rhs_mut())
+}
+
+pub fn walk_binary_mut<V: VisitorMut + ?Sized>(v: &mut V, b: &mut Binary) {
+    v.visit_expr_mut(b.lhs_mut());
+    v.visit_expr_mut(b.rhs_mut());
+}
+
+/// Rebuilds an expression tree bottom-up.
+///
+/// [`fold_children`] folds the children of a node and puts it back together,
+/// overrides can rewrite the node before or after calling it.
+pub trait Fold {
+    fn fold_expr(&mut self, e: Expr) -> Expr {
+        fold_children(self, e)
+    }
+
+    fn fold_value(&mut self, v: Value) -> Expr {
+        Expr::Value(v)
+    }
+}
+
+pub fn fold_children<F: Fold + ?Sized>(f: &mut F, e: Expr) -> Expr {
+    match e {
+        Expr::Value(x) => f.fold_value(x),
+        Expr::Unary(x) => {
+            let (code, rhs) = x.into();
+            Unary::new(code, f.fold_expr(*rhs).boxed()).to_expr()
+        }
+        Expr::Binary(x) => {
+            let (code, lhs, rhs) = x.into();
+            Binary::new(code, f.fold_expr(*lhs).boxed(), f.fold_expr(*rhs).boxed()).to_expr()
+        }
+        Expr::Index(x, at) => Expr::Index(f.fold_expr(*x).boxed(), f.fold_expr(*at).boxed()),
+        Expr::Slice(x, start, end) => Expr::Slice(
+            f.fold_expr(*x).boxed(),
+            f.fold_expr(*start).boxed(),
+            f.fold_expr(*end).boxed(),
+        ),
+    }
+}
+
+/// Iterates over an expression and all of its subexpressions, parents first.
+pub struct PreOrder<'a> {
+    stack: Vec<&'a Expr>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = &'a Expr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let e = self.stack.pop()?;
+        self.stack.extend(e.children().rev());
+        Some(e)
+    }
+}
+
+/// Iterates over an expression and all of its subexpressions, children first.
+pub struct PostOrder<'a> {
+    stack: Vec<(&'a Expr, bool)>,
+}
+
+impl<'a> Iterator for PostOrder<'a> {
+    type Item = &'a Expr;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (e, expanded) = self.stack.pop()?;
+            if expanded {
+                return Some(e);
+            }
+            self.stack.push((e, true));
+            self.stack.extend(e.children().rev().map(|x| (x, false)));
+        }
+    }
+}
+
+impl Expr {
+    /// The direct subexpressions, from left to right.
+    pub fn children(&self) -> impl DoubleEndedIterator<Item = &Expr> {
+        let children = match self {
+            Expr::Value(_) => [None, None, None],
+            Expr::Unary(x) => [Some(x.rhs()), None, None],
+            Expr::Binary(x) => [Some(x.lhs()), Some(x.rhs()), None],
+            Expr::Index(x, at) => [Some(&**x), Some(&**at), None],
+            Expr::Slice(x, start, end) => [Some(&**x), Some(&**start), Some(&**end)],
+        };
+        IntoIterator::into_iter(children).flatten()
+    }
+
+    pub fn pre_order(&self) -> PreOrder<'_> {
+        PreOrder { stack: vec![self] }
+    }
+
+    pub fn post_order(&self) -> PostOrder<'_> {
+        PostOrder {
+            stack: vec![(self, false)],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BinaryCode, UnaryCode};
+
+    fn strings(it: impl Iterator<Item = impl ToString>) -> Vec<String> {
+        it.map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn pre_order() {
+        let e = Expr::parse("-a + b[1]").unwrap();
+        assert_eq!(
+            strings(e.pre_order()),
+            vec!["-a + b[1]", "-a", "a", "b[1]", "b", "1"]
+        );
+    }
+
+    #[test]
+    fn post_order() {
+        let e = Expr::parse("-a + b[1]").unwrap();
+        assert_eq!(
+            strings(e.post_order()),
+            vec!["a", "-a", "b", "1", "b[1]", "-a + b[1]"]
+        );
+    }
+
+    #[test]
+    fn visitor() {
+        #[derive(Default)]
+        struct Names(Vec<String>);
+        impl Visitor for Names {
+            fn visit_var_name(&mut self, name: &VarName) {
+                self.0.push(name.to_string())
+            }
+        }
+        let mut names = Names::default();
+        names.visit_expr(&Expr::parse("a[i:j] @ SIN (b + 1)").unwrap());
+        assert_eq!(names.0, vec!["a", "i", "j", "b"]);
+    }
+
+    #[test]
+    fn visitor_mut() {
+        struct Rename;
+        impl VisitorMut for Rename {
+            fn visit_var_name_mut(&mut self, name: &mut VarName) {
+                *name = format!("{}_1", name).into()
+            }
+        }
+        let mut e = Expr::parse("a + b * a").unwrap();
+        Rename.visit_expr_mut(&mut e);
+        assert_eq!(e, Expr::parse("a_1 + b_1 * a_1").unwrap());
+    }
+
+    #[test]
+    fn fold() {
+        struct NegToSub;
+        impl Fold for NegToSub {
+            fn fold_expr(&mut self, e: Expr) -> Expr {
+                match fold_children(self, e) {
+                    Expr::Unary(x) if *x.op_code() == UnaryCode::Neg => {
+                        let (_, rhs) = x.into();
+                        Expr::from((BinaryCode::Sub, 0, *rhs))
+                    }
+                    e => e,
+                }
+            }
+        }
+        let e = NegToSub.fold_expr(Expr::parse("-a * -(-b)").unwrap());
+        assert_eq!(e.to_string(), "(0 - a) * (0 - (0 - b))");
+    }
+}
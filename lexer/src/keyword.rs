@@ -0,0 +1,145 @@
+use std::borrow::Cow;
+
+/// A reserved word of any part of the language, in either dialect.
+///
+/// The lexer does not know where a word stands, so a word that is a keyword
+/// only in statements, like `ki`, is still a keyword inside an expression.
+/// The parsers treat the keywords of other parts of the language as names.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Keyword {
+    // statements
+    Variables,
+    Read,
+    Print,
+    If,
+    Then,
+    Else,
+    EndIf,
+    While,
+    EndWhile,
+    // type names, `Int` and `Float` are also conversions
+    Bool,
+    Char,
+    String,
+    // expressions
+    Int,
+    Float,
+    Rnd,
+    Sin,
+    Cos,
+    Tan,
+    Exp,
+    Log,
+    Arcsin,
+    Arccos,
+    Arctan,
+    Not,
+    Upper,
+    Lower,
+    Round,
+    IsChar,
+    IsDigit,
+    And,
+    Or,
+    Div,
+    Mod,
+    True,
+    False,
+}
+
+/// Lists the spellings of every keyword once, for both [`Keyword::parse`] and
+/// [`Keyword::spellings`].
+macro_rules! spellings {
+    ($($keyword:ident => $($spelling:literal)|+,)*) => {
+        impl Keyword {
+            /// Recognizes every spelling of every keyword.
+            ///
+            /// Hungarian keywords may be written without accents, `EGESZ` is `EGÉSZ`.
+            pub fn parse(word: &str) -> Option<Self> {
+                Some(match &*fold_accents(word) {
+                    $($($spelling)|+ => Keyword::$keyword,)*
+                    _ => return None,
+                })
+            }
+
+            /// The spellings [`Keyword::parse`] takes, in both dialects. Hungarian
+            /// words are listed without accents.
+            pub fn spellings(self) -> &'static [&'static str] {
+                match self {
+                    $(Keyword::$keyword => &[$($spelling),+],)*
+                }
+            }
+        }
+    };
+}
+
+spellings! {
+    Variables => "VARIABLES" | "variables" | "VALTOZOK" | "valtozok",
+    Read => "READ" | "read" | "BE" | "be",
+    Print => "PRINT" | "print" | "KI" | "ki",
+    If => "IF" | "if" | "HA" | "ha",
+    Then => "THEN" | "then" | "AKKOR" | "akkor",
+    Else => "ELSE" | "else" | "KULONBEN" | "kulonben",
+    EndIf => "END_IF" | "end_if" | "IF_END" | "if_end" | "FI" | "fi" | "HA_VEGE" | "ha_vege",
+    While => "WHILE" | "while" | "CIKLUS AMIG" | "ciklus amig",
+    EndWhile => "END_WHILE" | "end_while" | "WHILE_END" | "while_end" | "DONE" | "done"
+        | "CIKLUS_VEGE" | "ciklus_vege",
+    Bool => "BOOL" | "Bool" | "bool" | "BOOLEAN" | "Boolean" | "boolean" | "LOGIKAI"
+        | "Logikai" | "logikai",
+    Char => "CHAR" | "Char" | "char" | "CHARACTER" | "Character" | "character" | "KARAKTER"
+        | "Karakter" | "karakter",
+    String => "STRING" | "String" | "string" | "SZOVEG" | "Szoveg" | "szoveg",
+    Int => "INT" | "Int" | "int" | "EGESZ" | "Egesz" | "egesz",
+    Float => "FLOAT" | "Float" | "float" | "VALOS" | "Valos" | "valos",
+    Rnd => "RND" | "rnd",
+    Sin => "SIN" | "sin",
+    Cos => "COS" | "cos",
+    Tan => "TAN" | "tan",
+    Exp => "EXP" | "exp",
+    Log => "LOG" | "log",
+    Arcsin => "ARCSIN" | "arcsin",
+    Arccos => "ARCCOS" | "arccos",
+    Arctan => "ARCTAN" | "arctan",
+    Not => "NOT" | "not" | "NEM" | "nem",
+    Upper => "UPPER" | "upper" | "NAGY" | "nagy",
+    Lower => "LOWER" | "lower" | "KICSI" | "kicsi",
+    Round => "ROUND" | "round" | "KEREK" | "kerek",
+    IsChar => "IS_CHAR" | "is_char" | "BETU" | "betu",
+    IsDigit => "IS_DIGIT" | "is_digit" | "SZAM" | "szam",
+    And => "AND" | "and" | "ES" | "es",
+    Or => "OR" | "or" | "VAGY" | "vagy",
+    Div => "DIV",
+    Mod => "MOD",
+    True => "TRUE" | "True" | "true" | "IGAZ" | "igaz",
+    False => "FALSE" | "False" | "false" | "HAMIS" | "hamis",
+}
+
+impl Keyword {
+    /// Whether the keyword names a type in a declaration.
+    pub fn is_type(self) -> bool {
+        use Keyword::*;
+        matches!(self, Bool | Char | String | Int | Float)
+    }
+}
+
+/// Replaces the accented vowels of Hungarian with their plain versions.
+fn fold_accents(word: &str) -> Cow<'_, str> {
+    if word.is_ascii() {
+        return Cow::Borrowed(word);
+    }
+    word.chars()
+        .map(|c| match c {
+            'Á' => 'A',
+            'É' => 'E',
+            'Í' => 'I',
+            'Ó' | 'Ö' | 'Ő' => 'O',
+            'Ú' | 'Ü' | 'Ű' => 'U',
+            'á' => 'a',
+            'é' => 'e',
+            'í' => 'i',
+            'ó' | 'ö' | 'ő' => 'o',
+            'ú' | 'ü' | 'ű' => 'u',
+            c => c,
+        })
+        .collect()
+}
@@ -0,0 +1,343 @@
+//! The lexer shared by the parsers of planggy, and usable by syntax highlighters.
+//!
+//! [`lex`] splits the source into [`Token`]s without ever failing: every byte
+//! belongs to exactly one token, including whitespace, newlines and comments,
+//! and text that starts no token becomes an [`Unknown`](TokenKind::Unknown) one.
+
+mod keyword;
+mod literal;
+
+pub use keyword::Keyword;
+pub use literal::{quote, unquote};
+
+use std::fmt;
+use std::ops::Range;
+
+/// What a [`Token`] is.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum TokenKind {
+    /// Spaces and tabs, never a line break.
+    Whitespace,
+    /// `\n`, which ends statements.
+    Newline,
+    /// `//` up to the end of the line.
+    Comment,
+    /// A name that is not a keyword, `[\p{Alphabetic}_][\p{Alphanumeric}_]*`.
+    Word,
+    Keyword(Keyword),
+    /// `12`
+    Int,
+    /// `1.5`, `2f` or `2.5f`
+    Float,
+    /// `'a'` or `'\''`
+    Char,
+    /// `"ab"` or `"say \"hi\""`
+    String,
+    /// `?x`, a metavariable of a rewrite rule.
+    Hole,
+    /// `(`
+    LParen,
+    /// `)`
+    RParen,
+    /// `[`
+    LBracket,
+    /// `]`
+    RBracket,
+    /// `|`
+    Bar,
+    /// `:`
+    Colon,
+    /// `,`
+    Comma,
+    /// `:=`
+    Assign,
+    /// `=>`
+    Arrow,
+    /// `+`
+    Plus,
+    /// `-`
+    Minus,
+    /// `*`
+    Star,
+    /// `/`
+    Slash,
+    /// `^`
+    Caret,
+    /// `@`
+    At,
+    /// `=`
+    Eq,
+    /// `/=`
+    Ne,
+    /// `<`
+    Lt,
+    /// `<=`
+    Le,
+    /// `>`
+    Gt,
+    /// `>=`
+    Ge,
+    /// A character that starts no token, or a literal missing its closing quote.
+    Unknown,
+}
+
+/// A piece of the source code.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Token<'a> {
+    pub kind: TokenKind,
+    pub text: &'a str,
+    /// The byte offset of the token in the source.
+    pub start: usize,
+}
+
+impl Token<'_> {
+    pub fn end(&self) -> usize {
+        self.start + self.text.len()
+    }
+
+    pub fn span(&self) -> Range<usize> {
+        self.start..self.end()
+    }
+
+    /// Whether the token is whitespace or a comment, which the grammars skip.
+    /// Newlines are not trivia, they end statements.
+    pub fn is_trivia(&self) -> bool {
+        matches!(self.kind, TokenKind::Whitespace | TokenKind::Comment)
+    }
+}
+
+/// Formats the token as its text, the way parse errors show it.
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.text)
+    }
+}
+
+/// Splits `source` into tokens, see the [crate documentation](crate).
+pub fn lex(source: &str) -> Lexer<'_> {
+    Lexer { source, pos: 0 }
+}
+
+/// The iterator returned by [`lex`].
+#[derive(Debug, Clone)]
+pub struct Lexer<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let rest = &self.source[self.pos..];
+        let c = rest.chars().next()?;
+        let (kind, len) = match c {
+            '\n' => (TokenKind::Newline, 1),
+            c if c.is_whitespace() => {
+                let len = rest
+                    .find(|c: char| c == '\n' || !c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (TokenKind::Whitespace, len)
+            }
+            '/' if rest.starts_with("//") => {
+                let len = rest.find(['\r', '\n']);
+                (TokenKind::Comment, len.unwrap_or(rest.len()))
+            }
+            c if is_word_start(c) => word(rest),
+            '0'..='9' => number(rest),
+            '\'' | '"' => literal(rest, c),
+            '?' if rest[1..].starts_with(is_word_start) => {
+                (TokenKind::Hole, 1 + word_len(&rest[1..]))
+            }
+            _ => punct(rest),
+        };
+        let token = Token {
+            kind,
+            text: &rest[..len],
+            start: self.pos,
+        };
+        self.pos += len;
+        Some(token)
+    }
+}
+
+fn is_word_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn word_len(s: &str) -> usize {
+    s.find(|c| !is_word_char(c)).unwrap_or(s.len())
+}
+
+/// A word, or a keyword, which is `CIKLUS AMÍG` for the two words.
+fn word(s: &str) -> (TokenKind, usize) {
+    let mut len = word_len(s);
+    if let Some(next) = s[len..].strip_prefix(' ') {
+        let two_words = len + 1 + word_len(next);
+        if Keyword::parse(&s[..two_words]) == Some(Keyword::While) {
+            len = two_words;
+        }
+    }
+    match Keyword::parse(&s[..len]) {
+        Some(keyword) => (TokenKind::Keyword(keyword), len),
+        None => (TokenKind::Word, len),
+    }
+}
+
+fn number(s: &str) -> (TokenKind, usize) {
+    let digits = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let mut len = digits(s);
+    let mut kind = TokenKind::Int;
+    if s[len..].starts_with('.') && s[len + 1..].starts_with(|c: char| c.is_ascii_digit()) {
+        len += 1 + digits(&s[len + 1..]);
+        kind = TokenKind::Float;
+    }
+    if s[len..].starts_with('f') {
+        len += 1;
+        kind = TokenKind::Float;
+    }
+    (kind, len)
+}
+
+/// A quoted literal, which may span lines. If it is not closed, it is an
+/// unknown token up to the end of the line.
+fn literal(s: &str, quote: char) -> (TokenKind, usize) {
+    let kind = match quote {
+        '\'' => TokenKind::Char,
+        _ => TokenKind::String,
+    };
+    let mut chars = s.char_indices().skip(1);
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c == quote => return (kind, i + 1),
+            _ => {}
+        }
+    }
+    (TokenKind::Unknown, s.find('\n').unwrap_or(s.len()))
+}
+
+fn punct(s: &str) -> (TokenKind, usize) {
+    use TokenKind::*;
+    const PUNCTS: [(&str, TokenKind); 21] = [
+        (":=", Assign),
+        ("=>", Arrow),
+        ("/=", Ne),
+        ("<=", Le),
+        (">=", Ge),
+        ("(", LParen),
+        (")", RParen),
+        ("[", LBracket),
+        ("]", RBracket),
+        ("|", Bar),
+        (":", Colon),
+        (",", Comma),
+        ("+", Plus),
+        ("-", Minus),
+        ("*", Star),
+        ("/", Slash),
+        ("^", Caret),
+        ("@", At),
+        ("=", Eq),
+        ("<", Lt),
+        (">", Gt),
+    ];
+    match PUNCTS.iter().find(|(text, _)| s.starts_with(text)) {
+        Some(&(text, kind)) => (kind, text.len()),
+        None => (Unknown, s.chars().next().map_or(0, char::len_utf8)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TokenKind::*;
+
+    fn kinds(source: &str) -> Vec<(TokenKind, &str)> {
+        lex(source).map(|t| (t.kind, t.text)).collect()
+    }
+
+    macro_rules! test {
+        ($test_name:ident: $in:tt -> [$(($kind:expr, $text:tt)),* $(,)?]) => {
+            #[test]
+            fn $test_name() {
+                assert_eq!(kinds($in), vec![$(($kind, $text)),*]);
+            }
+        };
+    }
+
+    test!(arithmetic: "1+2.5 * x" -> [
+        (Int, "1"), (Plus, "+"), (Float, "2.5"), (Whitespace, " "),
+        (Star, "*"), (Whitespace, " "), (Word, "x"),
+    ]);
+    test!(float_suffix: "2f 3.5f 4.x" -> [
+        (Float, "2f"), (Whitespace, " "), (Float, "3.5f"), (Whitespace, " "),
+        (Int, "4"), (Unknown, "."), (Word, "x"),
+    ]);
+    test!(two_char_punct: "a:=b/=c<=d>=e=>f" -> [
+        (Word, "a"), (Assign, ":="), (Word, "b"), (Ne, "/="), (Word, "c"), (Le, "<="),
+        (Word, "d"), (Ge, ">="), (Word, "e"), (Arrow, "=>"), (Word, "f"),
+    ]);
+    test!(literals: r#"'a' '\'' "say \"hi\"""# -> [
+        (Char, "'a'"), (Whitespace, " "), (Char, r"'\''"), (Whitespace, " "),
+        (String, r#""say \"hi\"""#),
+    ]);
+    test!(multiline: "\"a\nb\"" -> [(String, "\"a\nb\"")]);
+    test!(unterminated: "\"abc\nx" -> [(Unknown, "\"abc"), (Newline, "\n"), (Word, "x")]);
+    test!(comment: "x := 1 // one\r\n" -> [
+        (Word, "x"), (Whitespace, " "), (Assign, ":="), (Whitespace, " "), (Int, "1"),
+        (Whitespace, " "), (Comment, "// one"), (Whitespace, "\r"), (Newline, "\n"),
+    ]);
+    test!(keywords: "IF athena THEN" -> [
+        (Keyword(super::Keyword::If), "IF"), (Whitespace, " "), (Word, "athena"),
+        (Whitespace, " "), (Keyword(super::Keyword::Then), "THEN"),
+    ]);
+    test!(accents: "ÉS és ES árvíztűrő" -> [
+        (Keyword(super::Keyword::And), "ÉS"), (Whitespace, " "),
+        (Keyword(super::Keyword::And), "és"), (Whitespace, " "),
+        (Keyword(super::Keyword::And), "ES"), (Whitespace, " "), (Word, "árvíztűrő"),
+    ]);
+    test!(two_word_keyword: "CIKLUS AMÍG x\nciklus  amig" -> [
+        (Keyword(super::Keyword::While), "CIKLUS AMÍG"), (Whitespace, " "), (Word, "x"),
+        (Newline, "\n"), (Word, "ciklus"), (Whitespace, "  "), (Word, "amig"),
+    ]);
+    test!(holes: "?x + ? 1" -> [
+        (Hole, "?x"), (Whitespace, " "), (Plus, "+"), (Whitespace, " "), (Unknown, "?"),
+        (Whitespace, " "), (Int, "1"),
+    ]);
+
+    #[test]
+    fn lossless() {
+        let source = "VÁLTOZÓK: a: Egész\n  HA a > 1 AKKOR // ☃\n\tKI: \"é\\\"\"\n#";
+        let tokens: Vec<_> = lex(source).collect();
+        assert_eq!(
+            tokens
+                .iter()
+                .map(|t| t.text)
+                .collect::<std::string::String>(),
+            source
+        );
+        for pair in tokens.windows(2) {
+            assert_eq!(pair[0].end(), pair[1].start);
+        }
+        assert_eq!(tokens.last().map(|t| t.kind), Some(Unknown));
+    }
+
+    #[test]
+    fn unquoting() {
+        assert_eq!(unquote(r#""a\tb\\""#), Ok("a\tb\\".to_string()));
+        assert_eq!(unquote(r"'\''"), Ok("'".to_string()));
+        assert_eq!(unquote(r#""\q""#), Err("invalid escape sequence"));
+        assert_eq!(unquote("\"abc"), Err("unterminated literal"));
+        let s = "say \"hi\"\n\\ 'o'";
+        assert_eq!(unquote(&quote(s, '"')), Ok(s.to_string()));
+        assert_eq!(quote(s, '"'), r#""say \"hi\"\n\\ 'o'""#);
+        assert_eq!(quote("'", '\''), r"'\''");
+    }
+}
@@ -0,0 +1,54 @@
+/// The characters that follow a `\` in a literal, with what they stand for.
+const ESCAPES: [(char, char); 10] = [
+    ('"', '"'),
+    ('\'', '\''),
+    ('\\', '\\'),
+    ('a', '\x07'),
+    ('b', '\x08'),
+    ('f', '\x0c'),
+    ('n', '\n'),
+    ('r', '\r'),
+    ('t', '\t'),
+    ('v', '\x0b'),
+];
+
+/// The contents of a [`Char`](crate::TokenKind::Char) or
+/// [`String`](crate::TokenKind::String) token, with the quotes removed and
+/// the escapes resolved.
+pub fn unquote(literal: &str) -> Result<String, &'static str> {
+    let mut chars = literal.chars();
+    let (open, close) = (chars.next(), chars.next_back());
+    if open.is_none() || open != close {
+        return Err("unterminated literal");
+    }
+    let mut out = String::with_capacity(literal.len());
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let escaped = chars.next().ok_or("unterminated literal")?;
+        match ESCAPES.iter().find(|(x, _)| *x == escaped) {
+            Some((_, x)) => out.push(*x),
+            None => return Err("invalid escape sequence"),
+        }
+    }
+    Ok(out)
+}
+
+/// Writes `s` between two `quote`s, escaping what [`unquote`] resolves.
+pub fn quote(s: &str, quote: char) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push(quote);
+    for c in s.chars() {
+        match ESCAPES.iter().find(|(_, x)| *x == c) {
+            Some(&(x, _)) if c == quote || c == '\\' || x.is_ascii_alphabetic() => {
+                out.push('\\');
+                out.push(x);
+            }
+            _ => out.push(c),
+        }
+    }
+    out.push(quote);
+    out
+}
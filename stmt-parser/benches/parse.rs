@@ -0,0 +1,105 @@
+//! The throughput of parsing programs, statements and expressions together,
+//! on the shapes of generated code. The time should grow linearly with the size.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use stmt_parser::{Program, Span, SyntaxTree, TextEdit};
+
+/// Every kind of statement, repeated.
+fn flat(stmts: usize) -> String {
+    let mut s = String::from("VARIABLES: i: Int, s: String\n");
+    for k in 0..stmts {
+        s += &match k % 4 {
+            0 => format!("i := i + {}\n", k),
+            1 => "IF i > 3 THEN\n    PRINT: \"then\"\nELSE\n    PRINT: i\nFI\n".to_string(),
+            2 => "WHILE i < 10\n    i := i + 1\nDONE\n".to_string(),
+            _ => "READ: s\n".to_string(),
+        }
+    }
+    s
+}
+
+/// `depth` nested `IF` and `WHILE` statements.
+fn nested(depth: usize) -> String {
+    let mut s = String::new();
+    for d in 0..depth {
+        let indent = "  ".repeat(d);
+        s += &match d % 2 {
+            0 => format!("{}IF x < {} THEN\n", indent, d),
+            _ => format!("{}WHILE x > {}\n", indent, d),
+        };
+    }
+    s += &format!("{}x := x + 1\n", "  ".repeat(depth));
+    for d in (0..depth).rev() {
+        s += &format!(
+            "{}{}\n",
+            "  ".repeat(d),
+            if d % 2 == 0 { "FI" } else { "DONE" }
+        );
+    }
+    s
+}
+
+/// An assignment of an expression with `terms` terms.
+fn long_line(terms: usize) -> String {
+    format!("x := {}1\n", "a * 2 - b / 3 + ".repeat(terms / 4))
+}
+
+/// A print of a string literal of `len` characters.
+fn long_string(len: usize) -> String {
+    format!("PRINT: \"{}\"\n", "planggy \\\"then\\\" ".repeat(len / 18))
+}
+
+fn parse(source: &str) {
+    Program::parse(source).unwrap().parse_exprs().unwrap();
+}
+
+fn bench(c: &mut Criterion, name: &str, sizes: &[usize], generate: fn(usize) -> String) {
+    let mut group = c.benchmark_group(name);
+    for &size in sizes {
+        let source = generate(size);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &source, |b, s| {
+            b.iter(|| parse(s))
+        });
+    }
+    group.finish();
+}
+
+/// Typing a character into the middle of a program, against parsing the
+/// edited program from scratch.
+fn edit(c: &mut Criterion) {
+    let mut group = c.benchmark_group("edit");
+    for &size in &[1_000, 10_000] {
+        let tree = SyntaxTree::parse(&flat(size));
+        let offset = tree.source().len() / 2;
+        let offset = offset + tree.source()[offset..].find(" + ").unwrap();
+        let edit = TextEdit::new(Span::new(offset + 3, offset + 3), "9");
+        group.bench_with_input(BenchmarkId::new("reparse", size), &tree, |b, t| {
+            b.iter(|| t.edit(&edit))
+        });
+        group.bench_with_input(BenchmarkId::new("parse", size), &tree, |b, t| {
+            b.iter(|| SyntaxTree::parse(&edit.apply(t.source())))
+        });
+    }
+    group.finish();
+}
+
+fn benches(c: &mut Criterion) {
+    bench(c, "flat", &[1_000, 10_000, 100_000], flat);
+    bench(c, "deep_nesting", &[25, 50, 100], nested);
+    bench(c, "long_lines", &[1_000, 10_000, 50_000], long_line);
+    bench(
+        c,
+        "long_strings",
+        &[10_000, 100_000, 1_000_000],
+        long_string,
+    );
+    edit(c);
+}
+
+criterion_group! {
+    name = parse_benches;
+    config = Criterion::default().sample_size(10);
+    targets = benches
+}
+criterion_main!(parse_benches);
@@ -0,0 +1,602 @@
+//! A typed view of the nodes of a [`SyntaxTree`](crate::SyntaxTree).
+//!
+//! The view borrows the tree, and gives access to the tokens too, so tools can
+//! find the keyword of a statement or the operator of an expression as the user
+//! spelled it. Since the tree may come from broken code, the parts of a node are
+//! optional. The `lower` methods convert the view into the trees of
+//! [`crate::Program`], [`crate::Stmt`] and [`expr_parser::Expr`].
+
+use crate::cst::{NodeKind, SyntaxError, SyntaxNode, SyntaxToken};
+use crate::{Span, Type, VarName};
+use expr_parser::{Binary as B, BinaryCode, Expr as E, Unary as U, UnaryCode, Value};
+use lexer::{Keyword, TokenKind};
+use vars_parser::Variable;
+
+/// A typed view of a node.
+pub trait AstNode<'a>: Sized {
+    fn cast(node: SyntaxNode<'a>) -> Option<Self>;
+    fn syntax(&self) -> SyntaxNode<'a>;
+}
+
+macro_rules! ast_node {
+    ($(#[$attr:meta])* $name:ident) => {
+        $(#[$attr])*
+        #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+        pub struct $name<'a>(SyntaxNode<'a>);
+
+        impl<'a> AstNode<'a> for $name<'a> {
+            fn cast(node: SyntaxNode<'a>) -> Option<Self> {
+                match node.kind() {
+                    NodeKind::$name => Some($name(node)),
+                    _ => None,
+                }
+            }
+
+            fn syntax(&self) -> SyntaxNode<'a> {
+                self.0
+            }
+        }
+    };
+}
+
+macro_rules! ast_enum {
+    ($(#[$attr:meta])* $name:ident { $($variant:ident),* $(,)? }) => {
+        $(#[$attr])*
+        #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+        pub enum $name<'a> {
+            $($variant($variant<'a>)),*
+        }
+
+        impl<'a> AstNode<'a> for $name<'a> {
+            fn cast(node: SyntaxNode<'a>) -> Option<Self> {
+                match node.kind() {
+                    $(NodeKind::$variant => Some($name::$variant($variant(node))),)*
+                    _ => None,
+                }
+            }
+
+            fn syntax(&self) -> SyntaxNode<'a> {
+                match self {
+                    $($name::$variant(x) => x.syntax(),)*
+                }
+            }
+        }
+    };
+}
+
+ast_node!(
+    /// The whole source.
+    Program
+);
+ast_node!(
+    /// `VARIABLES: a, b: Int, s: String`
+    Variables
+);
+ast_node!(
+    /// `a, b: Int`
+    Declaration
+);
+ast_node!(Block);
+ast_node!(
+    /// `a := 1`
+    Assign
+);
+ast_node!(
+    /// `READ: a`
+    Read
+);
+ast_node!(
+    /// `PRINT: a`
+    Print
+);
+ast_node!(
+    /// `IF a THEN ... ELSE ... END_IF`
+    If
+);
+ast_node!(
+    /// The `ELSE` branch of an [`If`].
+    Else
+);
+ast_node!(
+    /// `WHILE a ... END_WHILE`
+    While
+);
+ast_enum!(Stmt {
+    Assign,
+    Read,
+    Print,
+    If,
+    While
+});
+
+ast_node!(
+    /// `1`, `2.5`, `'a'`, `"ab"`, `TRUE` or `FALSE`
+    Literal
+);
+ast_node!(
+    /// A variable.
+    Name
+);
+ast_node!(
+    /// `(a)`
+    Paren
+);
+ast_node!(
+    /// `|a|`
+    Abs
+);
+ast_node!(
+    /// `-a` or `NOT a`
+    Unary
+);
+ast_node!(
+    /// `a + b`
+    Binary
+);
+ast_node!(
+    /// `a[i]`
+    Index
+);
+ast_node!(
+    /// `a[i:j]`
+    Slice
+);
+ast_enum!(Expr {
+    Literal,
+    Name,
+    Paren,
+    Abs,
+    Unary,
+    Binary,
+    Index,
+    Slice,
+});
+
+fn children<'a, N: AstNode<'a> + 'a>(node: SyntaxNode<'a>) -> impl Iterator<Item = N> + 'a {
+    node.child_nodes().filter_map(N::cast)
+}
+
+fn child<'a, N: AstNode<'a> + 'a>(node: SyntaxNode<'a>) -> Option<N> {
+    children(node).next()
+}
+
+/// The tokens directly under the node, without trivia.
+fn tokens(node: SyntaxNode<'_>) -> impl Iterator<Item = SyntaxToken<'_>> {
+    node.child_tokens().filter(|t| !t.is_trivia())
+}
+
+fn keyword<'a>(node: SyntaxNode<'a>, keyword: Keyword) -> Option<SyntaxToken<'a>> {
+    tokens(node).find(|t| t.kind() == TokenKind::Keyword(keyword))
+}
+
+/// The error of a node missing a part.
+fn missing(node: SyntaxNode, expected: &str) -> SyntaxError {
+    let end = node.span().end();
+    SyntaxError::new(Span::new(end, end), format!("expected {}", expected))
+}
+
+impl<'a> Program<'a> {
+    pub fn variables(&self) -> Option<Variables<'a>> {
+        child(self.0)
+    }
+
+    pub fn block(&self) -> Option<Block<'a>> {
+        child(self.0)
+    }
+
+    /// The statements of the program, not counting nested ones.
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt<'a>> {
+        self.block().into_iter().flat_map(|block| block.stmts())
+    }
+
+    /// Converts the program into one with parsed expressions and the spans of
+    /// the statements, like [`crate::Program::parse_exprs`] does.
+    pub fn lower(&self) -> Result<crate::Program<E>, SyntaxError> {
+        let variables = match self.variables() {
+            Some(variables) => variables.lower()?,
+            None => Vec::new(),
+        };
+        let mut spans = Vec::new();
+        let stmts = match self.block() {
+            Some(block) => block.lower(&mut spans)?,
+            None => Vec::new(),
+        };
+        Ok(crate::Program::with_spans(variables, stmts, spans))
+    }
+}
+
+impl<'a> Variables<'a> {
+    pub fn keyword(&self) -> Option<SyntaxToken<'a>> {
+        keyword(self.0, Keyword::Variables)
+    }
+
+    pub fn declarations(&self) -> impl Iterator<Item = Declaration<'a>> {
+        children(self.0)
+    }
+
+    pub fn lower(&self) -> Result<Vec<Variable>, SyntaxError> {
+        let mut variables = Vec::new();
+        for declaration in self.declarations() {
+            variables.extend(declaration.lower()?);
+        }
+        Ok(variables)
+    }
+}
+
+impl<'a> Declaration<'a> {
+    /// The names, up to the colon.
+    pub fn names(&self) -> impl Iterator<Item = SyntaxToken<'a>> {
+        tokens(self.0)
+            .take_while(|t| t.kind() != TokenKind::Colon)
+            .filter(|t| t.kind() != TokenKind::Comma)
+    }
+
+    /// The name of the type, after the colon.
+    pub fn type_name(&self) -> Option<SyntaxToken<'a>> {
+        tokens(self.0)
+            .skip_while(|t| t.kind() != TokenKind::Colon)
+            .nth(1)
+    }
+
+    pub fn its_type(&self) -> Option<Type> {
+        match self.type_name()?.kind() {
+            TokenKind::Keyword(Keyword::Int) => Some(Type::Int),
+            TokenKind::Keyword(Keyword::Float) => Some(Type::Float),
+            TokenKind::Keyword(Keyword::Bool) => Some(Type::Bool),
+            TokenKind::Keyword(Keyword::Char) => Some(Type::Char),
+            TokenKind::Keyword(Keyword::String) => Some(Type::String),
+            _ => None,
+        }
+    }
+
+    pub fn lower(&self) -> Result<Vec<Variable>, SyntaxError> {
+        let its_type = self.its_type().ok_or_else(|| missing(self.0, "type"))?;
+        Ok(self
+            .names()
+            .map(|name| Variable::new(name.text().into(), its_type.clone()))
+            .collect())
+    }
+}
+
+impl<'a> Block<'a> {
+    pub fn stmts(&self) -> impl Iterator<Item = Stmt<'a>> {
+        children(self.0)
+    }
+
+    /// Converts the statements, pushing their spans in pre-order.
+    fn lower(&self, spans: &mut Vec<Span>) -> Result<Vec<crate::Stmt<E>>, SyntaxError> {
+        self.stmts().map(|stmt| stmt.lower_into(spans)).collect()
+    }
+}
+
+impl<'a> Stmt<'a> {
+    /// The expression the statement evaluates itself, not counting nested statements.
+    pub fn expr(&self) -> Option<Expr<'a>> {
+        match self {
+            Stmt::Assign(x) => x.expr(),
+            Stmt::Print(x) => x.expr(),
+            Stmt::If(x) => x.condition(),
+            Stmt::While(x) => x.condition(),
+            Stmt::Read(_) => None,
+        }
+    }
+
+    pub fn lower(&self) -> Result<crate::Stmt<E>, SyntaxError> {
+        self.lower_into(&mut Vec::new())
+    }
+
+    fn lower_into(&self, spans: &mut Vec<Span>) -> Result<crate::Stmt<E>, SyntaxError> {
+        spans.push(self.syntax().span());
+        let node = self.syntax();
+        let expr = || match self.expr() {
+            Some(e) => e.lower(),
+            None => Err(missing(node, "expression")),
+        };
+        let block = |block: Option<Block>, spans: &mut Vec<Span>| match block {
+            Some(block) => block.lower(spans),
+            None => Err(missing(node, "statement")),
+        };
+        Ok(match self {
+            Stmt::Assign(x) => crate::Stmt::Assign(x.name().text().into(), expr()?),
+            Stmt::Print(_) => crate::Stmt::Print(expr()?),
+            Stmt::Read(x) => match x.name() {
+                Some(name) => crate::Stmt::Read(name.text().into()),
+                None => return Err(missing(node, "name")),
+            },
+            Stmt::If(x) => {
+                let cond = expr()?;
+                let inner = block(x.block(), spans)?;
+                match x.else_branch() {
+                    Some(else_branch) => {
+                        crate::Stmt::IfElse(cond, inner, block(else_branch.block(), spans)?)
+                    }
+                    None => crate::Stmt::If(cond, inner),
+                }
+            }
+            Stmt::While(x) => crate::Stmt::While(expr()?, block(x.block(), spans)?),
+        })
+    }
+}
+
+impl<'a> Assign<'a> {
+    pub fn name(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn expr(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> Read<'a> {
+    pub fn keyword(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn name(&self) -> Option<SyntaxToken<'a>> {
+        tokens(self.0).nth(2)
+    }
+}
+
+impl<'a> Print<'a> {
+    pub fn keyword(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn expr(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> If<'a> {
+    pub fn keyword(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn condition(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+
+    pub fn then_keyword(&self) -> Option<SyntaxToken<'a>> {
+        keyword(self.0, Keyword::Then)
+    }
+
+    pub fn block(&self) -> Option<Block<'a>> {
+        child(self.0)
+    }
+
+    pub fn else_branch(&self) -> Option<Else<'a>> {
+        child(self.0)
+    }
+
+    pub fn end_keyword(&self) -> Option<SyntaxToken<'a>> {
+        keyword(self.0, Keyword::EndIf)
+    }
+}
+
+impl<'a> Else<'a> {
+    pub fn keyword(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn block(&self) -> Option<Block<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> While<'a> {
+    pub fn keyword(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn condition(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+
+    pub fn block(&self) -> Option<Block<'a>> {
+        child(self.0)
+    }
+
+    pub fn end_keyword(&self) -> Option<SyntaxToken<'a>> {
+        keyword(self.0, Keyword::EndWhile)
+    }
+}
+
+impl<'a> Expr<'a> {
+    /// Converts the expression into the tree [`expr_parser::Expr::parse`] gives.
+    pub fn lower(&self) -> Result<E, SyntaxError> {
+        let node = self.syntax();
+        let operand = |e: Option<Expr>| match e {
+            Some(e) => e.lower(),
+            None => Err(missing(node, "expression")),
+        };
+        let mut operands = children::<Expr>(node);
+        Ok(match self {
+            Expr::Literal(x) => x.value()?.to_expr(),
+            Expr::Name(x) => VarName::from(x.token().text()).to_expr(),
+            Expr::Paren(_) => {
+                U::from((UnaryCode::Parentheses, operand(operands.next())?)).to_expr()
+            }
+            Expr::Abs(_) => U::from((UnaryCode::Abs, operand(operands.next())?)).to_expr(),
+            Expr::Unary(x) => {
+                let code = x.code().ok_or_else(|| missing(node, "operator"))?;
+                U::from((code, operand(operands.next())?)).to_expr()
+            }
+            Expr::Binary(x) => {
+                let code = x.code().ok_or_else(|| missing(node, "operator"))?;
+                let lhs = operand(operands.next())?;
+                B::from((code, lhs, operand(operands.next())?)).to_expr()
+            }
+            Expr::Index(_) => {
+                let value = operand(operands.next())?;
+                E::Index(value.boxed(), operand(operands.next())?.boxed())
+            }
+            Expr::Slice(_) => {
+                let value = operand(operands.next())?;
+                let start = operand(operands.next())?;
+                E::Slice(
+                    value.boxed(),
+                    start.boxed(),
+                    operand(operands.next())?.boxed(),
+                )
+            }
+        })
+    }
+}
+
+impl<'a> Literal<'a> {
+    pub fn token(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn value(&self) -> Result<Value, SyntaxError> {
+        let token = self.token();
+        expr_parser::ValueParser::new()
+            .parse(expr_parser::tokens(token.text()))
+            .map_err(|e| match e {
+                lalrpop_util::ParseError::User { error } => SyntaxError::new(token.span(), error),
+                e => SyntaxError::new(token.span(), e.to_string()),
+            })
+    }
+}
+
+impl<'a> Name<'a> {
+    pub fn token(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+}
+
+impl<'a> Paren<'a> {
+    pub fn expr(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> Abs<'a> {
+    pub fn expr(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> Unary<'a> {
+    pub fn op(&self) -> SyntaxToken<'a> {
+        tokens(self.0).next().unwrap()
+    }
+
+    pub fn code(&self) -> Option<UnaryCode> {
+        match self.op().kind() {
+            TokenKind::Plus => Some(UnaryCode::Plus),
+            TokenKind::Minus => Some(UnaryCode::Neg),
+            TokenKind::Keyword(keyword) => match expr_parser::Keyword::of(keyword)? {
+                expr_parser::Keyword::Unary(code) => Some(code),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    pub fn operand(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+}
+
+impl<'a> Binary<'a> {
+    pub fn lhs(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+
+    pub fn op(&self) -> Option<SyntaxToken<'a>> {
+        tokens(self.0).next()
+    }
+
+    pub fn code(&self) -> Option<BinaryCode> {
+        crate::cst_parser::binary_code(self.op()?.kind())
+    }
+
+    pub fn rhs(&self) -> Option<Expr<'a>> {
+        children(self.0).nth(1)
+    }
+}
+
+impl<'a> Index<'a> {
+    pub fn value(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+
+    pub fn index(&self) -> Option<Expr<'a>> {
+        children(self.0).nth(1)
+    }
+}
+
+impl<'a> Slice<'a> {
+    pub fn value(&self) -> Option<Expr<'a>> {
+        child(self.0)
+    }
+
+    pub fn start(&self) -> Option<Expr<'a>> {
+        children(self.0).nth(1)
+    }
+
+    pub fn end(&self) -> Option<Expr<'a>> {
+        children(self.0).nth(2)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SyntaxTree;
+
+    #[test]
+    fn keywords_keep_their_spelling() {
+        let tree = SyntaxTree::parse("HA a AKKOR\n  KI: a\nkülönben\nHA_VÉGE\n");
+        let stmt = tree.program().stmts().next().unwrap();
+        let x = match stmt {
+            Stmt::If(x) => x,
+            _ => panic!("{:?}", stmt),
+        };
+        assert_eq!(x.keyword().text(), "HA");
+        assert_eq!(x.then_keyword().unwrap().text(), "AKKOR");
+        assert_eq!(x.else_branch().unwrap().keyword().text(), "különben");
+        assert_eq!(x.end_keyword().unwrap().text(), "HA_VÉGE");
+        let print = x.block().unwrap().stmts().next().unwrap();
+        assert_eq!(print.syntax().to_string(), "KI: a");
+    }
+
+    #[test]
+    fn declarations() {
+        let tree = SyntaxTree::parse("VÁLTOZÓK: a, b: Egész,\n  s: String\n");
+        let variables = tree.program().variables().unwrap();
+        assert_eq!(variables.keyword().unwrap().text(), "VÁLTOZÓK");
+        let declarations: Vec<_> = variables
+            .declarations()
+            .map(|d| {
+                let names: Vec<_> = d.names().map(|t| t.text()).collect();
+                (names, d.type_name().unwrap().text(), d.its_type())
+            })
+            .collect();
+        assert_eq!(
+            declarations,
+            vec![
+                (vec!["a", "b"], "Egész", Some(Type::Int)),
+                (vec!["s"], "String", Some(Type::String)),
+            ]
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        let tree = SyntaxTree::parse("x := (a+1) * b[2:3] DIV 2");
+        let e = tree.program().stmts().next().unwrap().expr().unwrap();
+        let binary = match e {
+            Expr::Binary(x) => x,
+            _ => panic!("{:?}", e),
+        };
+        assert_eq!(binary.op().unwrap().text(), "DIV");
+        assert_eq!(binary.code(), Some(BinaryCode::IntDiv));
+        let lhs = binary.lhs().unwrap().syntax();
+        assert_eq!(lhs.to_string(), "(a+1) * b[2:3]");
+        assert_eq!(binary.rhs().unwrap().syntax().to_string(), "2");
+        assert_eq!(e.lower(), Ok(E::parse("(a+1) * b[2:3] DIV 2").unwrap()));
+    }
+}
@@ -0,0 +1,369 @@
+//! A compact form of programs for [`Bytecode::run`], which executes them
+//! faster than walking the syntax tree.
+//!
+//! Expressions compile to instructions of a stack machine. Variables live in
+//! numbered slots and constants in a pool, so instructions only hold indices.
+//! Statements compile to jumps around the instructions of their blocks.
+
+use crate::{Program, Span, Stmt, VarName};
+use expr_parser::{BinaryCode, Expr, Type, UnaryCode, Value};
+use std::fmt;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Instr {
+    /// Pushes a constant of the pool.
+    Const(u32),
+    /// Pushes the value of a slot.
+    Load(u32),
+    /// Pops a value into a slot, checking it against the type of the slot.
+    Store(u32),
+    /// Reads a line of input into a slot.
+    Read(u32),
+    /// Pops a value and prints it.
+    Print,
+    Unary(UnaryCode),
+    /// Pops the rhs, then the lhs, and pushes the result.
+    Binary(BinaryCode),
+    Index,
+    Slice,
+    Jump(u32),
+    /// Pops a condition and jumps if it is `FALSE`, it is an error if it is not a `Bool`.
+    JumpUnless(u32),
+    /// Jumps, keeping the value on the stack, if the top of the stack is the given `Bool`.
+    /// This is how `AND` and `OR` short-circuit.
+    JumpIfKeep(bool, u32),
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Instr::Const(i) => write!(f, "CONST {}", i),
+            Instr::Load(i) => write!(f, "LOAD {}", i),
+            Instr::Store(i) => write!(f, "STORE {}", i),
+            Instr::Read(i) => write!(f, "READ {}", i),
+            Instr::Print => write!(f, "PRINT"),
+            Instr::Unary(op) => write!(f, "UNARY {}", op),
+            Instr::Binary(op) => write!(f, "BINARY {}", op),
+            Instr::Index => write!(f, "INDEX"),
+            Instr::Slice => write!(f, "SLICE"),
+            Instr::Jump(to) => write!(f, "JUMP {}", to),
+            Instr::JumpUnless(to) => write!(f, "JUMP_UNLESS {}", to),
+            Instr::JumpIfKeep(x, to) => write!(f, "JUMP_IF_KEEP {} {}", Value::from(*x), to),
+        }
+    }
+}
+
+/// A variable of a compiled program.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Slot {
+    pub(crate) name: VarName,
+    /// The declared type, undeclared variables take any value.
+    pub(crate) ty: Option<Type>,
+}
+
+impl Slot {
+    pub fn name(&self) -> &VarName {
+        &self.name
+    }
+
+    pub fn ty(&self) -> Option<&Type> {
+        self.ty.as_ref()
+    }
+}
+
+/// A compiled program, see the [module documentation](self).
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytecode {
+    pub(crate) slots: Vec<Slot>,
+    pub(crate) constants: Vec<Value>,
+    pub(crate) code: Vec<Instr>,
+    /// The pre-order index of the statement every instruction belongs to.
+    pub(crate) stmts: Vec<u32>,
+    /// The spans of the statements of the source program, in pre-order.
+    pub(crate) spans: Vec<Span>,
+}
+
+impl Bytecode {
+    /// The declared variables first, then the others in the order they first appear in.
+    pub fn slots(&self) -> &[Slot] {
+        &self.slots
+    }
+
+    pub fn constants(&self) -> &[Value] {
+        &self.constants
+    }
+
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    /// The span of the statement the `pc`th instruction belongs to.
+    pub fn span(&self, pc: usize) -> Option<Span> {
+        self.spans.get(*self.stmts.get(pc)? as usize).copied()
+    }
+}
+
+/// Lists the slots, the constants and the instructions.
+impl fmt::Display for Bytecode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, slot) in self.slots.iter().enumerate() {
+            match &slot.ty {
+                Some(t) => writeln!(f, "slot {}: {}: {}", i, slot.name, t)?,
+                None => writeln!(f, "slot {}: {}", i, slot.name)?,
+            }
+        }
+        for (i, x) in self.constants.iter().enumerate() {
+            writeln!(f, "const {}: {}", i, x)?;
+        }
+        for (pc, instr) in self.code.iter().enumerate() {
+            writeln!(f, "{:04} {}", pc, instr)?;
+        }
+        Ok(())
+    }
+}
+
+impl Program<Expr> {
+    pub fn compile(&self) -> Bytecode {
+        let mut compiler = Compiler {
+            bytecode: Bytecode {
+                slots: self
+                    .variables()
+                    .iter()
+                    .map(|var| Slot {
+                        name: var.name().clone(),
+                        ty: Some(var.its_type().clone()),
+                    })
+                    .collect(),
+                constants: Vec::new(),
+                code: Vec::new(),
+                stmts: Vec::new(),
+                spans: self.spans().to_vec(),
+            },
+            at: 0,
+        };
+        compiler.block(self.stmts());
+        compiler.bytecode
+    }
+}
+
+struct Compiler {
+    bytecode: Bytecode,
+    /// The pre-order index of the statement being compiled.
+    at: u32,
+}
+
+impl Compiler {
+    fn emit(&mut self, instr: Instr) -> usize {
+        self.bytecode.code.push(instr);
+        self.bytecode.stmts.push(self.at);
+        self.bytecode.code.len() - 1
+    }
+
+    fn pc(&self) -> u32 {
+        self.bytecode.code.len() as u32
+    }
+
+    /// Points the jump at `pc` to the next instruction.
+    fn patch(&mut self, pc: usize) {
+        let to = self.pc();
+        match &mut self.bytecode.code[pc] {
+            Instr::Jump(x) | Instr::JumpUnless(x) | Instr::JumpIfKeep(_, x) => *x = to,
+            _ => unreachable!(),
+        }
+    }
+
+    fn slot(&mut self, name: &VarName) -> u32 {
+        let slots = &mut self.bytecode.slots;
+        let i = match slots.iter().position(|slot| slot.name == *name) {
+            Some(i) => i,
+            None => {
+                slots.push(Slot {
+                    name: name.clone(),
+                    ty: None,
+                });
+                slots.len() - 1
+            }
+        };
+        i as u32
+    }
+
+    fn constant(&mut self, x: &Value) -> u32 {
+        let constants = &mut self.bytecode.constants;
+        let i = match constants.iter().position(|c| c == x) {
+            Some(i) => i,
+            None => {
+                constants.push(x.clone());
+                constants.len() - 1
+            }
+        };
+        i as u32
+    }
+
+    fn block(&mut self, stmts: &[Stmt<Expr>]) {
+        for s in stmts {
+            self.stmt(s)
+        }
+    }
+
+    fn stmt(&mut self, s: &Stmt<Expr>) {
+        let at = self.at;
+        let mut next = at + 1;
+        match s {
+            Stmt::Assign(var, e) => {
+                self.expr(e);
+                let slot = self.slot(var);
+                self.emit(Instr::Store(slot));
+            }
+            Stmt::Print(e) => {
+                self.expr(e);
+                self.emit(Instr::Print);
+            }
+            Stmt::Read(var) => {
+                let slot = self.slot(var);
+                self.emit(Instr::Read(slot));
+            }
+            Stmt::If(cond, inner) => {
+                self.expr(cond);
+                let skip = self.emit(Instr::JumpUnless(0));
+                self.nested(inner, &mut next);
+                self.patch(skip);
+            }
+            Stmt::IfElse(cond, inner, else_inner) => {
+                self.expr(cond);
+                let to_else = self.emit(Instr::JumpUnless(0));
+                self.nested(inner, &mut next);
+                let to_end = self.emit(Instr::Jump(0));
+                self.patch(to_else);
+                self.nested(else_inner, &mut next);
+                self.patch(to_end);
+            }
+            Stmt::While(cond, inner) => {
+                let start = self.pc();
+                self.expr(cond);
+                let to_end = self.emit(Instr::JumpUnless(0));
+                self.nested(inner, &mut next);
+                self.emit(Instr::Jump(start));
+                self.patch(to_end);
+            }
+        }
+        self.at = next;
+    }
+
+    /// Compiles a block nested in the current statement, starting at the
+    /// `next`th statement in pre-order.
+    fn nested(&mut self, stmts: &[Stmt<Expr>], next: &mut u32) {
+        let at = self.at;
+        self.at = *next;
+        self.block(stmts);
+        *next = self.at;
+        self.at = at;
+    }
+
+    fn expr(&mut self, e: &Expr) {
+        match e {
+            Expr::Value(Value::VarName(name)) => {
+                let slot = self.slot(name);
+                self.emit(Instr::Load(slot));
+            }
+            Expr::Value(x) => {
+                let i = self.constant(x);
+                self.emit(Instr::Const(i));
+            }
+            Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => self.expr(x.rhs()),
+            Expr::Unary(x) => {
+                self.expr(x.rhs());
+                self.emit(Instr::Unary(*x.op_code()));
+            }
+            Expr::Binary(x) => {
+                let op = *x.op_code();
+                self.expr(x.lhs());
+                let short_circuit = match op {
+                    BinaryCode::And => Some(self.emit(Instr::JumpIfKeep(false, 0))),
+                    BinaryCode::Or => Some(self.emit(Instr::JumpIfKeep(true, 0))),
+                    _ => None,
+                };
+                self.expr(x.rhs());
+                self.emit(Instr::Binary(op));
+                if let Some(pc) = short_circuit {
+                    self.patch(pc)
+                }
+            }
+            Expr::Index(x, at) => {
+                self.expr(x);
+                self.expr(at);
+                self.emit(Instr::Index);
+            }
+            Expr::Slice(x, start, end) => {
+                self.expr(x);
+                self.expr(start);
+                self.expr(end);
+                self.emit(Instr::Slice);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str) -> Bytecode {
+        Program::parse(source)
+            .unwrap()
+            .parse_exprs()
+            .unwrap()
+            .compile()
+    }
+
+    #[test]
+    fn listing() {
+        let bytecode = compile("VARIABLES: i: Int\nWHILE i < 3 AND TRUE\n    i := i + 1\nDONE");
+        assert_eq!(
+            bytecode.to_string(),
+            "\
+slot 0: i: Int
+const 0: 3
+const 1: TRUE
+const 2: 1
+0000 LOAD 0
+0001 CONST 0
+0002 BINARY <
+0003 JUMP_IF_KEEP FALSE 6
+0004 CONST 1
+0005 BINARY AND
+0006 JUMP_UNLESS 12
+0007 LOAD 0
+0008 CONST 2
+0009 BINARY +
+0010 STORE 0
+0011 JUMP 0
+"
+        );
+    }
+
+    #[test]
+    fn statement_of_instructions() {
+        let source = "IF x THEN\n    y := 1\nELSE\n    y := 2\nFI\nPRINT: y";
+        let bytecode = compile(source);
+        let stmts: Vec<_> = (0..bytecode.code().len())
+            .map(|pc| {
+                let span = bytecode.span(pc).unwrap();
+                source[span.start()..span.end()].lines().next().unwrap()
+            })
+            .collect();
+        assert_eq!(
+            stmts,
+            vec![
+                "IF x THEN",
+                "IF x THEN",
+                "y := 1",
+                "y := 1",
+                "IF x THEN",
+                "y := 2",
+                "y := 2",
+                "PRINT: y",
+                "PRINT: y",
+            ]
+        );
+    }
+}
@@ -0,0 +1,561 @@
+//! The file format of [`Bytecode`], so compiled programs can be cached.
+//!
+//! A file starts with the magic bytes `PLBC` and the format version as a
+//! little-endian `u16`. The slots, the constants, the instructions with the
+//! statements they belong to, and the statement spans follow. Counts and
+//! indices are LEB128 varints, strings are their byte length and UTF-8 bytes.
+
+use crate::bytecode::{Bytecode, Instr, Slot};
+use crate::{Span, Type, VarName};
+use derive_more::{Display, From};
+use expr_parser::{BinaryCode, Signature, UnaryCode, Value};
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+/// The version of the file format [`Bytecode::write_to`] writes.
+pub const BYTECODE_VERSION: u16 = 1;
+
+const MAGIC: &[u8; 4] = b"PLBC";
+
+/// An error while loading a bytecode file.
+#[derive(Debug, Display, From)]
+pub enum LoadError {
+    #[display(fmt = "not a bytecode file")]
+    BadMagic,
+    #[display(
+        fmt = "bytecode version {} is not supported, expected {}",
+        _0,
+        BYTECODE_VERSION
+    )]
+    #[from(ignore)]
+    UnsupportedVersion(u16),
+    #[display(fmt = "corrupt bytecode: {}", _0)]
+    #[from(ignore)]
+    Corrupt(&'static str),
+    Io(io::Error),
+}
+
+impl Bytecode {
+    /// Whether `bytes` start like a bytecode file, as opposed to source code.
+    pub fn is_bytecode(bytes: &[u8]) -> bool {
+        bytes.starts_with(MAGIC)
+    }
+
+    /// Writes the bytecode in the current version of the file format.
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut e = Encoder(w);
+        e.0.write_all(MAGIC)?;
+        e.0.write_all(&BYTECODE_VERSION.to_le_bytes())?;
+        e.uint(self.slots.len())?;
+        for slot in &self.slots {
+            e.str(slot.name.as_str())?;
+            match &slot.ty {
+                Some(t) => {
+                    e.byte(1)?;
+                    e.ty(t)?;
+                }
+                None => e.byte(0)?,
+            }
+        }
+        e.uint(self.constants.len())?;
+        for x in &self.constants {
+            e.value(x)?;
+        }
+        e.uint(self.code.len())?;
+        for (instr, stmt) in self.code.iter().zip(&self.stmts) {
+            e.instr(*instr)?;
+            e.uint(*stmt as usize)?;
+        }
+        e.uint(self.spans.len())?;
+        for span in &self.spans {
+            e.uint(span.start())?;
+            e.uint(span.end())?;
+        }
+        Ok(())
+    }
+
+    /// Reads bytecode written by [`Bytecode::write_to`].
+    ///
+    /// Files of other versions are rejected, and so are the ones referring
+    /// to slots, constants, instructions or statements they do not have, or
+    /// popping values the stack does not hold.
+    pub fn read_from(r: &mut impl Read) -> Result<Bytecode, LoadError> {
+        let mut d = Decoder(r);
+        let mut magic = [0; 4];
+        d.bytes(&mut magic)?;
+        if magic != *MAGIC {
+            return Err(LoadError::BadMagic);
+        }
+        let mut version = [0; 2];
+        d.bytes(&mut version)?;
+        let version = u16::from_le_bytes(version);
+        if version != BYTECODE_VERSION {
+            return Err(LoadError::UnsupportedVersion(version));
+        }
+        let mut bytecode = Bytecode {
+            slots: Vec::new(),
+            constants: Vec::new(),
+            code: Vec::new(),
+            stmts: Vec::new(),
+            spans: Vec::new(),
+        };
+        for _ in 0..d.uint()? {
+            let name = VarName::new(d.string()?);
+            let ty = match d.byte()? {
+                0 => None,
+                1 => Some(d.ty()?),
+                _ => return Err(LoadError::Corrupt("invalid slot type")),
+            };
+            bytecode.slots.push(Slot { name, ty });
+        }
+        for _ in 0..d.uint()? {
+            bytecode.constants.push(d.value()?);
+        }
+        for _ in 0..d.uint()? {
+            bytecode.code.push(d.instr()?);
+            bytecode.stmts.push(d.index()?);
+        }
+        for _ in 0..d.uint()? {
+            bytecode.spans.push(Span::new(d.uint()?, d.uint()?));
+        }
+        bytecode.validate()?;
+        Ok(bytecode)
+    }
+
+    /// Checks that the indices in the instructions are in range and the stack
+    /// is balanced, so running the bytecode can not index out of bounds.
+    fn validate(&self) -> Result<(), LoadError> {
+        let in_range = |i: u32, len: usize| (i as usize) < len;
+        for instr in &self.code {
+            let valid = match *instr {
+                Instr::Const(i) => in_range(i, self.constants.len()),
+                Instr::Load(i) | Instr::Store(i) | Instr::Read(i) => in_range(i, self.slots.len()),
+                Instr::Jump(to) | Instr::JumpUnless(to) | Instr::JumpIfKeep(_, to) => {
+                    in_range(to, self.code.len() + 1)
+                }
+                _ => true,
+            };
+            if !valid {
+                return Err(LoadError::Corrupt("an instruction refers to nothing"));
+            }
+        }
+        if !self.spans.is_empty() && self.stmts.iter().any(|&i| !in_range(i, self.spans.len())) {
+            return Err(LoadError::Corrupt("an instruction belongs to no statement"));
+        }
+        self.validate_stack()
+    }
+
+    /// Follows every path through the code with the depth of the stack, which
+    /// must be the same on all the paths reaching an instruction, and must be
+    /// deep enough for what the instruction pops.
+    fn validate_stack(&self) -> Result<(), LoadError> {
+        let mut depths = vec![None; self.code.len() + 1];
+        let mut todo = vec![(0, 0usize)];
+        while let Some((pc, depth)) = todo.pop() {
+            match depths[pc] {
+                Some(d) if d == depth => continue,
+                Some(_) => return Err(LoadError::Corrupt("the stack is unbalanced")),
+                None => depths[pc] = Some(depth),
+            }
+            let instr = match self.code.get(pc) {
+                Some(instr) => *instr,
+                None => continue,
+            };
+            let (pops, pushes) = match instr {
+                Instr::Const(_) | Instr::Load(_) => (0, 1),
+                Instr::Read(_) | Instr::Jump(_) => (0, 0),
+                Instr::Store(_) | Instr::Print | Instr::JumpUnless(_) => (1, 0),
+                Instr::Unary(_) | Instr::JumpIfKeep(..) => (1, 1),
+                Instr::Binary(_) | Instr::Index => (2, 1),
+                Instr::Slice => (3, 1),
+            };
+            let depth = match depth.checked_sub(pops) {
+                Some(depth) => depth + pushes,
+                None => return Err(LoadError::Corrupt("the stack is unbalanced")),
+            };
+            match instr {
+                Instr::Jump(to) => todo.push((to as usize, depth)),
+                Instr::JumpUnless(to) | Instr::JumpIfKeep(_, to) => {
+                    todo.push((to as usize, depth));
+                    todo.push((pc + 1, depth));
+                }
+                _ => todo.push((pc + 1, depth)),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The operators in the order of their codes in the file.
+fn encode_op<T: PartialEq>(codes: &[T], op: T) -> u8 {
+    codes
+        .iter()
+        .position(|x| *x == op)
+        .expect("every operator has a code") as u8
+}
+
+fn decode_op<T: Copy>(codes: &[T], code: u8) -> Result<T, LoadError> {
+    codes
+        .get(code as usize)
+        .copied()
+        .ok_or(LoadError::Corrupt("invalid operator"))
+}
+
+struct Encoder<W>(W);
+
+impl<W: Write> Encoder<W> {
+    fn byte(&mut self, x: u8) -> io::Result<()> {
+        self.0.write_all(&[x])
+    }
+
+    fn uint(&mut self, mut x: usize) -> io::Result<()> {
+        loop {
+            let low = (x & 0x7f) as u8;
+            x >>= 7;
+            if x == 0 {
+                return self.byte(low);
+            }
+            self.byte(low | 0x80)?;
+        }
+    }
+
+    fn str(&mut self, s: &str) -> io::Result<()> {
+        self.uint(s.len())?;
+        self.0.write_all(s.as_bytes())
+    }
+
+    fn value(&mut self, x: &Value) -> io::Result<()> {
+        match x {
+            Value::Boolean(b) => {
+                self.byte(0)?;
+                self.byte(*b as u8)
+            }
+            Value::Int(i) => {
+                self.byte(1)?;
+                self.0.write_all(&i.to_le_bytes())
+            }
+            Value::Float(f) => {
+                self.byte(2)?;
+                self.0.write_all(&f.to_bits().to_le_bytes())
+            }
+            Value::Char(c) => {
+                self.byte(3)?;
+                self.uint(*c as usize)
+            }
+            Value::String(s) => {
+                self.byte(4)?;
+                self.str(s)
+            }
+            Value::VarName(name) => {
+                self.byte(5)?;
+                self.str(name.as_str())
+            }
+        }
+    }
+
+    fn ty(&mut self, t: &Type) -> io::Result<()> {
+        match t {
+            Type::Bool => self.byte(0),
+            Type::Int => self.byte(1),
+            Type::Float => self.byte(2),
+            Type::Char => self.byte(3),
+            Type::String => self.byte(4),
+            Type::Array(t) => {
+                self.byte(5)?;
+                self.ty(t)
+            }
+            Type::Record(fields) => {
+                self.byte(6)?;
+                self.uint(fields.len())?;
+                for (name, t) in fields {
+                    self.str(name.as_str())?;
+                    self.ty(t)?;
+                }
+                Ok(())
+            }
+            Type::Function(signature) => {
+                self.byte(7)?;
+                self.uint(signature.params().len())?;
+                for t in signature.params() {
+                    self.ty(t)?;
+                }
+                self.ty(signature.ret())
+            }
+        }
+    }
+
+    fn instr(&mut self, instr: Instr) -> io::Result<()> {
+        match instr {
+            Instr::Const(i) => self.with_index(0, i),
+            Instr::Load(i) => self.with_index(1, i),
+            Instr::Store(i) => self.with_index(2, i),
+            Instr::Read(i) => self.with_index(3, i),
+            Instr::Print => self.byte(4),
+            Instr::Unary(op) => {
+                self.byte(5)?;
+                self.byte(encode_op(&UnaryCode::ALL, op))
+            }
+            Instr::Binary(op) => {
+                self.byte(6)?;
+                self.byte(encode_op(&BinaryCode::ALL, op))
+            }
+            Instr::Index => self.byte(7),
+            Instr::Slice => self.byte(8),
+            Instr::Jump(to) => self.with_index(9, to),
+            Instr::JumpUnless(to) => self.with_index(10, to),
+            Instr::JumpIfKeep(x, to) => {
+                self.byte(11)?;
+                self.byte(x as u8)?;
+                self.uint(to as usize)
+            }
+        }
+    }
+
+    fn with_index(&mut self, opcode: u8, i: u32) -> io::Result<()> {
+        self.byte(opcode)?;
+        self.uint(i as usize)
+    }
+}
+
+struct Decoder<R>(R);
+
+impl<R: Read> Decoder<R> {
+    fn bytes(&mut self, buf: &mut [u8]) -> Result<(), LoadError> {
+        self.0.read_exact(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => LoadError::Corrupt("the file ends early"),
+            _ => e.into(),
+        })
+    }
+
+    fn byte(&mut self) -> Result<u8, LoadError> {
+        let mut buf = [0];
+        self.bytes(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    fn uint(&mut self) -> Result<usize, LoadError> {
+        let mut x = 0usize;
+        for shift in (0..usize::BITS).step_by(7) {
+            let byte = self.byte()?;
+            x |= ((byte & 0x7f) as usize)
+                .checked_shl(shift)
+                .filter(|y| y >> shift == (byte & 0x7f) as usize)
+                .ok_or(LoadError::Corrupt("a number is too large"))?;
+            if byte & 0x80 == 0 {
+                return Ok(x);
+            }
+        }
+        Err(LoadError::Corrupt("a number is too large"))
+    }
+
+    fn index(&mut self) -> Result<u32, LoadError> {
+        u32::try_from(self.uint()?).map_err(|_| LoadError::Corrupt("an index is too large"))
+    }
+
+    fn bool(&mut self) -> Result<bool, LoadError> {
+        match self.byte()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(LoadError::Corrupt("invalid Bool")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let mut buf = Vec::new();
+        let len = self.uint()? as u64;
+        (&mut self.0).take(len).read_to_end(&mut buf)?;
+        if buf.len() as u64 != len {
+            return Err(LoadError::Corrupt("the file ends early"));
+        }
+        String::from_utf8(buf).map_err(|_| LoadError::Corrupt("invalid UTF-8"))
+    }
+
+    fn value(&mut self) -> Result<Value, LoadError> {
+        Ok(match self.byte()? {
+            0 => Value::Boolean(self.bool()?),
+            1 => {
+                let mut buf = [0; 4];
+                self.bytes(&mut buf)?;
+                Value::Int(i32::from_le_bytes(buf))
+            }
+            2 => {
+                let mut buf = [0; 8];
+                self.bytes(&mut buf)?;
+                Value::Float(f64::from_bits(u64::from_le_bytes(buf)))
+            }
+            3 => u32::try_from(self.uint()?)
+                .ok()
+                .and_then(char::from_u32)
+                .map(Value::Char)
+                .ok_or(LoadError::Corrupt("invalid Char"))?,
+            4 => Value::String(self.string()?),
+            5 => Value::VarName(VarName::new(self.string()?)),
+            _ => return Err(LoadError::Corrupt("invalid constant")),
+        })
+    }
+
+    fn ty(&mut self) -> Result<Type, LoadError> {
+        Ok(match self.byte()? {
+            0 => Type::Bool,
+            1 => Type::Int,
+            2 => Type::Float,
+            3 => Type::Char,
+            4 => Type::String,
+            5 => Type::Array(Box::new(self.ty()?)),
+            6 => {
+                let mut fields = Vec::new();
+                for _ in 0..self.uint()? {
+                    fields.push((VarName::new(self.string()?), self.ty()?));
+                }
+                Type::Record(fields)
+            }
+            7 => {
+                let mut params = Vec::new();
+                for _ in 0..self.uint()? {
+                    params.push(self.ty()?);
+                }
+                Type::Function(Box::new(Signature::new(params, self.ty()?)))
+            }
+            _ => return Err(LoadError::Corrupt("invalid type")),
+        })
+    }
+
+    fn instr(&mut self) -> Result<Instr, LoadError> {
+        Ok(match self.byte()? {
+            0 => Instr::Const(self.index()?),
+            1 => Instr::Load(self.index()?),
+            2 => Instr::Store(self.index()?),
+            3 => Instr::Read(self.index()?),
+            4 => Instr::Print,
+            5 => Instr::Unary(decode_op(&UnaryCode::ALL, self.byte()?)?),
+            6 => Instr::Binary(decode_op(&BinaryCode::ALL, self.byte()?)?),
+            7 => Instr::Index,
+            8 => Instr::Slice,
+            9 => Instr::Jump(self.index()?),
+            10 => Instr::JumpUnless(self.index()?),
+            11 => Instr::JumpIfKeep(self.bool()?, self.index()?),
+            _ => return Err(LoadError::Corrupt("invalid instruction")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    fn compile(source: &str) -> Bytecode {
+        Program::parse(source)
+            .unwrap()
+            .parse_exprs()
+            .unwrap()
+            .compile()
+    }
+
+    fn encode(bytecode: &Bytecode) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytecode.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut bytecode = compile(
+            r#"
+            VARIABLES: n: Int, x: Float, c: Char
+            READ: n
+            x := 0.1 * -2.5
+            c := 'ő'
+            WHILE NOT (n <= 0 OR -n >= 1000)
+                n := n - 1
+                PRINT: "számláló: " + n
+            DONE
+            s := t[0][1:2]
+            "#,
+        );
+        // compound types can not be declared in source code yet
+        bytecode.slots.push(Slot {
+            name: "f".into(),
+            ty: Some(Type::Function(Box::new(Signature::new(
+                vec![Type::Array(Box::new(Type::String))],
+                Type::Record(vec![("x".into(), Type::Bool)]),
+            )))),
+        });
+        let bytes = encode(&bytecode);
+        assert!(Bytecode::is_bytecode(&bytes));
+        let decoded = Bytecode::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(decoded, bytecode);
+        assert_eq!(decoded.to_string(), bytecode.to_string());
+    }
+
+    #[test]
+    fn decoded_runs() {
+        let bytes = encode(&compile("READ: a\nPRINT: a + \"!\""));
+        let decoded = Bytecode::read_from(&mut bytes.as_slice()).unwrap();
+        let mut output = Vec::new();
+        decoded.run(&mut "hi\n".as_bytes(), &mut output).unwrap();
+        assert_eq!(output, b"hi!\n");
+    }
+
+    #[test]
+    fn bad_magic() {
+        let result = Bytecode::read_from(&mut "PRINT: 1".as_bytes());
+        assert!(matches!(result, Err(LoadError::BadMagic)));
+        assert!(!Bytecode::is_bytecode(b"PRINT: 1"));
+    }
+
+    #[test]
+    fn other_version() {
+        let mut bytes = encode(&compile("PRINT: 1"));
+        bytes[4..6].copy_from_slice(&7u16.to_le_bytes());
+        let error = Bytecode::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "bytecode version 7 is not supported, expected 1"
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = encode(&compile("PRINT: 1"));
+        let error = Bytecode::read_from(&mut &bytes[..bytes.len() - 1]).unwrap_err();
+        assert_eq!(error.to_string(), "corrupt bytecode: the file ends early");
+    }
+
+    #[test]
+    fn missing_constant() {
+        let mut bytecode = compile("PRINT: 1");
+        bytecode.constants.clear();
+        let bytes = encode(&bytecode);
+        let error = Bytecode::read_from(&mut bytes.as_slice()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "corrupt bytecode: an instruction refers to nothing"
+        );
+    }
+
+    #[test]
+    fn unbalanced_stack() {
+        let error = |bytes: &[u8]| {
+            Bytecode::read_from(&mut &bytes[..])
+                .unwrap_err()
+                .to_string()
+        };
+        // a PRINT with nothing to print
+        assert_eq!(
+            error(b"PLBC\x01\x00\x00\x00\x01\x04\x00\x00"),
+            "corrupt bytecode: the stack is unbalanced"
+        );
+        // a loop pushing a constant on every turn
+        let mut bytecode = compile("PRINT: 1");
+        bytecode.code = vec![Instr::Const(0), Instr::Jump(0)];
+        bytecode.stmts = vec![0, 0];
+        let bytes = encode(&bytecode);
+        assert_eq!(error(&bytes), "corrupt bytecode: the stack is unbalanced");
+        // the compiler balances the stack of every program
+        let bytes = encode(&compile(
+            "WHILE i < 3 AND (TRUE OR i = 1)\n    IF i = 0 THEN\n        PRINT: \"a\"[0:1]\n    ELSE\n        i := -i\n    FI\n    i := i + 1\nDONE",
+        ));
+        assert!(Bytecode::read_from(&mut bytes.as_slice()).is_ok());
+    }
+}
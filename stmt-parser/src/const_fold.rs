@@ -0,0 +1,219 @@
+use crate::{Diagnostic, Program, Span, Stmt, StmtVisitor, VarName};
+use expr_parser::{Env, Expr, Type, Value, Visitor};
+use std::collections::{HashMap, HashSet};
+
+impl Program<Expr> {
+    /// Folds the constant subexpressions of every statement, see [`Expr::fold_constants`].
+    ///
+    /// A variable counts as a constant where every path to the statement assigns it
+    /// the same constant. `READ` and loops make it unknown again: a loop condition
+    /// and body only see the constants that none of the iterations assign.
+    /// Subexpressions that would fail at run time are kept and reported.
+    pub fn fold_constants(self) -> (Program<Expr>, Vec<Diagnostic>) {
+        let (variables, stmts, spans) = self.into_parts();
+        let mut folder = ConstFolder {
+            types: variables
+                .iter()
+                .map(|var| (var.name().clone(), var.its_type().clone()))
+                .collect(),
+            spans: &spans,
+            at: 0,
+            diagnostics: Vec::new(),
+        };
+        let stmts = folder.block(stmts, &mut Env::new());
+        let diagnostics = folder.diagnostics;
+        (Program::with_spans(variables, stmts, spans), diagnostics)
+    }
+}
+
+struct ConstFolder<'a> {
+    types: HashMap<VarName, Type>,
+    spans: &'a [Span],
+    /// The pre-order index of the statement being folded.
+    at: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl ConstFolder<'_> {
+    fn block(&mut self, stmts: Vec<Stmt<Expr>>, constants: &mut Env) -> Vec<Stmt<Expr>> {
+        stmts.into_iter().map(|s| self.stmt(s, constants)).collect()
+    }
+
+    fn stmt(&mut self, s: Stmt<Expr>, constants: &mut Env) -> Stmt<Expr> {
+        let span = self.spans.get(self.at).copied();
+        self.at += 1;
+        match s {
+            Stmt::Assign(var, e) => {
+                let e = self.expr(e, constants, span);
+                match e.as_value() {
+                    Some(x) if !x.is_var_name() => {
+                        let x = match (self.types.get(&var), x) {
+                            (Some(Type::Float), &Value::Int(x)) => Value::Float(x.into()),
+                            _ => x.clone(),
+                        };
+                        constants.set(var.clone(), x);
+                    }
+                    _ => {
+                        constants.remove(&var);
+                    }
+                }
+                Stmt::Assign(var, e)
+            }
+            Stmt::Print(e) => Stmt::Print(self.expr(e, constants, span)),
+            Stmt::Read(var) => {
+                constants.remove(&var);
+                Stmt::Read(var)
+            }
+            Stmt::If(cond, inner) => {
+                let cond = self.expr(cond, constants, span);
+                let mut after = constants.clone();
+                let inner = self.block(inner, &mut after);
+                retain_common(constants, &after);
+                Stmt::If(cond, inner)
+            }
+            Stmt::IfElse(cond, inner, else_inner) => {
+                let cond = self.expr(cond, constants, span);
+                let mut after = constants.clone();
+                let inner = self.block(inner, &mut after);
+                let else_inner = self.block(else_inner, constants);
+                retain_common(constants, &after);
+                Stmt::IfElse(cond, inner, else_inner)
+            }
+            Stmt::While(cond, inner) => {
+                let mut targets = Targets::default();
+                targets.visit_block(&inner);
+                for var in &targets.0 {
+                    constants.remove(var);
+                }
+                let cond = self.expr(cond, constants, span);
+                let inner = self.block(inner, &mut constants.clone());
+                Stmt::While(cond, inner)
+            }
+        }
+    }
+
+    fn expr(&mut self, e: Expr, constants: &Env, span: Option<Span>) -> Expr {
+        let mut warnings = Vec::new();
+        let e = e.fold_constants(constants, &mut warnings);
+        for warning in warnings {
+            self.diagnostics.push(Diagnostic::new(span, warning.into()))
+        }
+        e
+    }
+}
+
+/// Keeps the constants of `constants` that have the same value in `other`.
+fn retain_common(constants: &mut Env, other: &Env) {
+    let differing: Vec<_> = constants
+        .vars()
+        .filter(|(var, x)| other.get(var) != Some(x))
+        .map(|(var, _)| var.clone())
+        .collect();
+    for var in differing {
+        constants.remove(&var);
+    }
+}
+
+/// Collects the variables a block writes.
+#[derive(Default)]
+struct Targets(HashSet<VarName>);
+
+impl Visitor for Targets {}
+
+impl StmtVisitor for Targets {
+    fn visit_target(&mut self, var: &VarName) {
+        self.0.insert(var.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(source: &str) -> (Vec<String>, Vec<String>) {
+        let program = Program::parse(source).unwrap().parse_exprs().unwrap();
+        let (program, diagnostics) = program.fold_constants();
+        let exprs = program
+            .pre_order()
+            .filter_map(Stmt::expr)
+            .map(ToString::to_string)
+            .collect();
+        let diagnostics = diagnostics.iter().map(ToString::to_string).collect();
+        (exprs, diagnostics)
+    }
+
+    #[test]
+    fn propagates() {
+        let (exprs, diagnostics) = fold("n := 2 * 3\nm := n + 1\nPRINT: m * x");
+        assert_eq!(exprs, vec!["6", "7", "7 * x"]);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn widens_declared_floats() {
+        let (exprs, _) = fold("VARIABLES: x: Float\nx := 1\nPRINT: x / 2");
+        assert_eq!(exprs, vec!["1", "0.5"]);
+    }
+
+    #[test]
+    fn read_forgets() {
+        let (exprs, _) = fold("n := 1\nREAD: n\nPRINT: n + 1");
+        assert_eq!(exprs, vec!["1", "n + 1"]);
+    }
+
+    #[test]
+    fn branches() {
+        let (exprs, _) = fold(
+            r"
+            a := 1
+            b := 1
+            READ: c
+            IF c THEN
+                a := 2
+                b := 1
+            FI
+            PRINT: a + b
+            IF c THEN
+                a := 3
+            ELSE
+                a := 3
+            FI
+            PRINT: a
+            ",
+        );
+        assert_eq!(
+            exprs,
+            vec!["1", "1", "c", "2", "1", "a + 1", "c", "3", "3", "3"]
+        );
+    }
+
+    #[test]
+    fn loops() {
+        let (exprs, _) = fold(
+            r"
+            i := 0
+            n := 10
+            WHILE i < n
+                PRINT: i * n
+                i := i + 1
+            DONE
+            PRINT: i + n
+            ",
+        );
+        assert_eq!(
+            exprs,
+            vec!["0", "10", "i < 10", "i * 10", "i + 1", "i + 10"]
+        );
+    }
+
+    #[test]
+    fn reports_runtime_errors() {
+        let source = "z := 0\nPRINT: 1 DIV z";
+        let (exprs, diagnostics) = fold(source);
+        assert_eq!(exprs, vec!["0", "1 DIV 0"]);
+        assert_eq!(
+            diagnostics,
+            vec!["`1 DIV 0` always fails: division by zero at 7..21"]
+        );
+    }
+}
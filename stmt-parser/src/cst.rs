@@ -0,0 +1,629 @@
+//! A lossless concrete syntax tree of a program.
+//!
+//! Every byte of the source belongs to a token of the tree, whitespace,
+//! comments and newlines included, so printing the tree gives back the source,
+//! and the keywords keep the spelling the user wrote them in. The tree is built
+//! even from broken code: text that fits nowhere ends up in
+//! [`Error`](NodeKind::Error) nodes, and the problems are listed in
+//! [`SyntaxTree::errors`].
+//!
+//! The nodes are untyped, [`crate::ast`] gives a typed view of them. Lowering
+//! that view gives the same [`Program`](crate::Program) as the parser, in which
+//! a parenthesized expression is a [`Parentheses`](expr_parser::UnaryCode::Parentheses)
+//! node, while the tree keeps the parentheses as [`Paren`](NodeKind::Paren) nodes.
+
+use crate::ast::{self, AstNode};
+use crate::Span;
+use derive_more::Display;
+use expr_parser::Expr;
+use lexer::TokenKind;
+use std::fmt;
+use std::sync::Arc;
+
+/// What a [`SyntaxNode`] is.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum NodeKind {
+    /// The whole source, an optional `Variables` node and a `Block`.
+    Program,
+    /// `VARIABLES:` and the declarations.
+    Variables,
+    /// `a, b: Int`
+    Declaration,
+    /// Statements and the newlines between them.
+    Block,
+    Assign,
+    Read,
+    Print,
+    /// `IF`, the condition, `THEN`, a `Block`, an optional `Else` and `END_IF`.
+    If,
+    /// `ELSE` and a `Block`.
+    Else,
+    /// `WHILE`, the condition, a `Block` and `END_WHILE`.
+    While,
+    /// `1`, `2.5`, `'a'`, `"ab"`, `TRUE` or `FALSE`
+    Literal,
+    /// A variable in an expression.
+    Name,
+    /// `(a)`
+    Paren,
+    /// `|a|`
+    Abs,
+    /// A prefix operator and its operand.
+    Unary,
+    /// The left operand, the operator and the right operand.
+    Binary,
+    /// `a[i]`
+    Index,
+    /// `a[i:j]`
+    Slice,
+    /// Tokens that fit nowhere.
+    Error,
+}
+
+impl NodeKind {
+    /// Whether the node is an expression.
+    pub fn is_expr(self) -> bool {
+        use NodeKind::*;
+        matches!(
+            self,
+            Literal | Name | Paren | Abs | Unary | Binary | Index | Slice
+        )
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct GreenNode {
+    kind: NodeKind,
+    len: usize,
+    children: Vec<GreenElement>,
+    /// The errors found while building the node, relative to its start.
+    errors: Vec<SyntaxError>,
+    /// Whether the node or a node under it has errors.
+    has_errors: bool,
+    /// Whether a literal missing its closing quote is in the node, which the
+    /// lexer looked for up to the end of the source.
+    has_open_literal: bool,
+}
+
+impl GreenNode {
+    pub fn new(kind: NodeKind, children: Vec<GreenElement>, errors: Vec<SyntaxError>) -> Self {
+        let has_errors = !errors.is_empty()
+            || children.iter().any(|child| match child {
+                GreenElement::Node(node) => node.has_errors,
+                GreenElement::Token(_) => false,
+            });
+        GreenNode {
+            kind,
+            len: children.iter().map(GreenElement::len).sum(),
+            has_open_literal: children.iter().any(GreenElement::has_open_literal),
+            children,
+            errors,
+            has_errors,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    /// The node with its child at `index` replaced by `node`, moving the
+    /// errors after the child along with the text.
+    pub fn with_child(&self, index: usize, node: GreenNode) -> Self {
+        let start: usize = self.children[..index].iter().map(GreenElement::len).sum();
+        let end = start + self.children[index].len();
+        let delta = node.len as isize - self.children[index].len() as isize;
+        let mut children = self.children.clone();
+        children[index] = GreenElement::Node(Arc::new(node));
+        let errors = self
+            .errors
+            .iter()
+            .map(|e| match e.span.start() >= end {
+                true => e.shifted(delta),
+                false => e.clone(),
+            })
+            .collect();
+        GreenNode::new(self.kind, children, errors)
+    }
+
+    /// Collects the errors of the node starting at `offset` and of the nodes
+    /// under it, with their spans in the source.
+    fn collect_errors(&self, offset: usize, errors: &mut Vec<SyntaxError>) {
+        if !self.has_errors {
+            return;
+        }
+        let mut child_offset = offset;
+        for child in &self.children {
+            if let GreenElement::Node(node) = child {
+                node.collect_errors(child_offset, errors);
+            }
+            child_offset += child.len();
+        }
+        errors.extend(self.errors.iter().map(|e| e.shifted(offset as isize)));
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) struct GreenToken {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+/// A child of a node, the nodes are shared, so trees can reuse subtrees.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(crate) enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(GreenToken),
+}
+
+impl GreenElement {
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn has_open_literal(&self) -> bool {
+        match self {
+            GreenElement::Node(node) => node.has_open_literal,
+            GreenElement::Token(token) => {
+                token.kind == TokenKind::Unknown && token.text.starts_with(['\'', '"'])
+            }
+        }
+    }
+}
+
+/// A problem found while building the tree.
+#[derive(Debug, PartialEq, Eq, Clone, Display)]
+#[display(fmt = "{} at {}", message, span)]
+pub struct SyntaxError {
+    span: Span,
+    message: String,
+}
+
+impl SyntaxError {
+    pub(crate) fn new(span: Span, message: impl Into<String>) -> Self {
+        SyntaxError {
+            span,
+            message: message.into(),
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    pub(crate) fn shifted(&self, by: isize) -> Self {
+        let shift = |offset: usize| (offset as isize + by) as usize;
+        SyntaxError {
+            span: Span::new(shift(self.span.start()), shift(self.span.end())),
+            message: self.message.clone(),
+        }
+    }
+}
+
+/// The concrete syntax tree of a program, see the [module documentation](self).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SyntaxTree {
+    source: String,
+    green: Arc<GreenNode>,
+    errors: Vec<SyntaxError>,
+}
+
+impl SyntaxTree {
+    /// Builds the tree of `source`, which never fails.
+    pub fn parse(source: &str) -> Self {
+        SyntaxTree::new(source.to_string(), crate::cst_parser::parse(source))
+    }
+
+    pub(crate) fn new(source: String, green: GreenNode) -> Self {
+        let mut errors = Vec::new();
+        green.collect_errors(0, &mut errors);
+        errors.sort_by_key(|e| e.span.start());
+        SyntaxTree {
+            source,
+            green: Arc::new(green),
+            errors,
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn root(&self) -> SyntaxNode<'_> {
+        SyntaxNode {
+            green: &self.green,
+            offset: 0,
+        }
+    }
+
+    /// The typed view of the root.
+    pub fn program(&self) -> ast::Program<'_> {
+        ast::Program::cast(self.root()).unwrap()
+    }
+
+    /// The problems of the source, in the order they appear in it.
+    pub fn errors(&self) -> &[SyntaxError] {
+        &self.errors
+    }
+
+    /// Lowers the tree into a program with parsed expressions, failing with
+    /// the first problem of the source.
+    pub fn to_program(&self) -> Result<crate::Program<Expr>, SyntaxError> {
+        match self.errors.first() {
+            Some(error) => Err(error.clone()),
+            None => self.program().lower(),
+        }
+    }
+}
+
+/// Formats the tree as the source it was built from.
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// A node of a [`SyntaxTree`], knowing where it is in the source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SyntaxNode<'a> {
+    green: &'a GreenNode,
+    offset: usize,
+}
+
+/// A token of a [`SyntaxTree`], knowing where it is in the source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SyntaxToken<'a> {
+    green: &'a GreenToken,
+    offset: usize,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyntaxElement<'a> {
+    Node(SyntaxNode<'a>),
+    Token(SyntaxToken<'a>),
+}
+
+impl<'a> SyntaxNode<'a> {
+    pub fn kind(&self) -> NodeKind {
+        self.green.kind
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.len)
+    }
+
+    pub(crate) fn green(&self) -> &'a GreenNode {
+        self.green
+    }
+
+    /// The nodes and tokens directly under the node.
+    pub fn children(&self) -> impl Iterator<Item = SyntaxElement<'a>> {
+        let mut offset = self.offset;
+        self.green.children.iter().map(move |child| {
+            let element = match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode { green, offset }),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken { green, offset }),
+            };
+            offset += child.len();
+            element
+        })
+    }
+
+    pub fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.children().filter_map(SyntaxElement::into_node)
+    }
+
+    /// The tokens directly under the node, trivia included.
+    pub fn child_tokens(&self) -> impl Iterator<Item = SyntaxToken<'a>> {
+        self.children().filter_map(SyntaxElement::into_token)
+    }
+
+    /// The node and every node under it, parents first.
+    pub fn descendants(&self) -> impl Iterator<Item = SyntaxNode<'a>> {
+        self.pre_order().filter_map(SyntaxElement::into_node)
+    }
+
+    /// Every token under the node, in source order.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken<'a>> {
+        self.pre_order().filter_map(SyntaxElement::into_token)
+    }
+
+    fn pre_order(&self) -> PreOrder<'a> {
+        PreOrder {
+            stack: vec![SyntaxElement::Node(*self)],
+        }
+    }
+}
+
+/// Formats the node as its source text.
+impl fmt::Display for SyntaxNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.tokens()
+            .try_for_each(|token| f.write_str(token.text()))
+    }
+}
+
+impl<'a> SyntaxToken<'a> {
+    pub fn kind(&self) -> TokenKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &'a str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+
+    /// Whether the token is whitespace, a comment or a newline.
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self.kind(),
+            TokenKind::Whitespace | TokenKind::Comment | TokenKind::Newline
+        )
+    }
+}
+
+impl<'a> SyntaxElement<'a> {
+    pub fn span(&self) -> Span {
+        match self {
+            SyntaxElement::Node(node) => node.span(),
+            SyntaxElement::Token(token) => token.span(),
+        }
+    }
+
+    pub fn into_node(self) -> Option<SyntaxNode<'a>> {
+        match self {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        }
+    }
+
+    pub fn into_token(self) -> Option<SyntaxToken<'a>> {
+        match self {
+            SyntaxElement::Node(_) => None,
+            SyntaxElement::Token(token) => Some(token),
+        }
+    }
+}
+
+struct PreOrder<'a> {
+    stack: Vec<SyntaxElement<'a>>,
+}
+
+impl<'a> Iterator for PreOrder<'a> {
+    type Item = SyntaxElement<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let element = self.stack.pop()?;
+        if let SyntaxElement::Node(node) = element {
+            let start = self.stack.len();
+            self.stack.extend(node.children());
+            self.stack[start..].reverse();
+        }
+        Some(element)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+
+    const SOURCES: [&str; 6] = [
+        "",
+        "VARIABLES: a, b: Int,\n           s: String // names\nREAD: a\nb := a * 2\n",
+        "\n  VÁLTOZÓK: a: Egész\nBE: a\nHA a > 1 AKKOR\n  KI: (a + 1) * 2\nKÜLÖNBEN\n  KI: -a\nHA_VÉGE\n",
+        "a := 1 // one\nWHILE a < 3\n  IF a = 2\n     AND TRUE THEN\n    PRINT: |a - 5|[1:2]\n  END_IF\n  a := a + 1\nEND_WHILE",
+        "s := \"a\\\"b\nc\" + 'd'\r\nPRINT: s[0] @ \"b\" ^ 2 ^ -x @ s DIV 1 MOD 2.5f\t\n\n",
+        "IF ki THEN END_IF a := NOT x = - - y OR sin 1 / 2",
+    ];
+
+    #[test]
+    fn lossless() {
+        for source in SOURCES
+            .iter()
+            .chain(&["IF a\n  x := \nEND_WHILE #", "VARIABLES: 1"])
+        {
+            let tree = SyntaxTree::parse(source);
+            assert_eq!(tree.to_string(), *source);
+            let tokens: Vec<_> = tree.root().tokens().collect();
+            assert_eq!(tokens.iter().map(|t| t.text()).collect::<String>(), *source);
+            for pair in tokens.windows(2) {
+                assert_eq!(pair[0].span().end(), pair[1].span().start());
+            }
+        }
+    }
+
+    #[test]
+    fn lowering() {
+        for source in &SOURCES {
+            let tree = SyntaxTree::parse(source);
+            assert_eq!(tree.errors(), &[], "{}", source);
+            let program = tree.to_program().unwrap();
+            let parsed = Program::parse(source).unwrap();
+            assert_eq!(program.spans(), parsed.spans(), "{}", source);
+            assert_eq!(program, parsed.parse_exprs().unwrap(), "{}", source);
+        }
+    }
+
+    /// Writes a random block of statements nested at most `depth` deep. A few
+    /// tokens are swapped for random ones, so some programs are invalid.
+    fn random_block(source: &mut String, depth: usize, below: &dyn Fn(usize) -> usize) {
+        const ATOMS: [&str; 7] = ["a", "b", "1", "2.5", "'c'", "TRUE", "(a)"];
+        const OPS: [&str; 10] = ["+", "-", "*", "^", "@", "MOD", "=", "<", "AND", "OR"];
+        const NOISE: [&str; 8] = ["THEN", "ELSE", "END_IF", "(", ")", ":=", "\n", "NOT"];
+        let expr = |source: &mut String| {
+            for i in 0..1 + below(3) {
+                if i > 0 {
+                    source.push_str(OPS[below(OPS.len())]);
+                    source.push(' ');
+                }
+                if below(4) == 0 {
+                    source.push_str("- ");
+                }
+                let atom = match below(20) {
+                    0 => NOISE[below(NOISE.len())],
+                    _ => ATOMS[below(ATOMS.len())],
+                };
+                source.push_str(atom);
+                source.push(' ');
+            }
+        };
+        for _ in 0..below(4) {
+            match below(if depth == 0 { 3 } else { 6 }) {
+                0 => source.push_str("READ: a"),
+                1 => {
+                    source.push_str("PRINT: ");
+                    expr(source);
+                }
+                2 => {
+                    source.push_str(["a := ", "b := "][below(2)]);
+                    expr(source);
+                }
+                3 | 4 => {
+                    source.push_str("IF ");
+                    expr(source);
+                    source.push_str("THEN\n");
+                    random_block(source, depth - 1, below);
+                    if below(2) == 0 {
+                        source.push_str("ELSE\n");
+                        random_block(source, depth - 1, below);
+                    }
+                    source.push_str("END_IF");
+                }
+                _ => {
+                    source.push_str("WHILE ");
+                    expr(source);
+                    source.push('\n');
+                    random_block(source, depth - 1, below);
+                    source.push_str("END_WHILE");
+                }
+            }
+            source.push('\n');
+        }
+    }
+
+    /// The tree is built by its own parser, so it is checked against
+    /// [`Program::parse`] on random programs.
+    #[test]
+    fn agrees_with_the_parser() {
+        let env = expr_parser::Env::with_seed(47);
+        let below = |n: usize| (env.random() * n as f64) as usize;
+        let mut valid = 0;
+        for _ in 0..10_000 {
+            let mut source = String::new();
+            if below(4) == 0 {
+                source.push_str("VARIABLES: a, b: Int\n");
+            }
+            random_block(&mut source, 3, &below);
+            let program = SyntaxTree::parse(&source).to_program().ok();
+            let parsed = Program::parse(&source)
+                .ok()
+                .and_then(|p| p.parse_exprs().ok());
+            if let (Some(program), Some(parsed)) = (&program, &parsed) {
+                assert_eq!(program.spans(), parsed.spans(), "{:?}", source);
+                valid += 1;
+            }
+            assert_eq!(program, parsed, "{:?}", source);
+        }
+        assert!(valid > 2000, "only {} valid programs", valid);
+    }
+
+    #[test]
+    fn nodes_start_and_end_at_tokens() {
+        let tree = SyntaxTree::parse("a := 1 // one\n   PRINT: ( a )  \n");
+        let print = tree
+            .root()
+            .descendants()
+            .find(|n| n.kind() == NodeKind::Print)
+            .unwrap();
+        assert_eq!(print.to_string(), "PRINT: ( a )");
+        let paren = print.child_nodes().next().unwrap();
+        assert_eq!(paren.kind(), NodeKind::Paren);
+        assert_eq!(paren.span(), Span::new(24, 29));
+    }
+
+    #[test]
+    fn errors() {
+        let source = "IF a\n  x := \nEND_WHILE\nPRINT: (1";
+        let tree = SyntaxTree::parse(source);
+        let errors: Vec<_> = tree.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "expected \"THEN\" at 7..8",
+                "expected expression at 12..13",
+                "expected \"END_IF\" at 13..22",
+                "expected statement at 13..22",
+                "expected \")\" at 32..32",
+            ]
+        );
+        assert_eq!(tree.to_program(), Err(tree.errors()[0].clone()));
+        let error = tree
+            .root()
+            .descendants()
+            .find(|n| n.kind() == NodeKind::Error)
+            .unwrap();
+        assert_eq!(error.to_string(), "END_WHILE");
+    }
+
+    #[test]
+    fn too_deep() {
+        let nested = |depth| {
+            let (open, close) = ("IF a THEN\n", "END_IF\n");
+            format!("{}a := 1\n{}", open.repeat(depth), close.repeat(depth))
+        };
+        assert_eq!(SyntaxTree::parse(&nested(crate::MAX_DEPTH)).errors(), &[]);
+        let source = nested(50_000);
+        let tree = SyntaxTree::parse(&source);
+        assert_eq!(tree.to_string(), source);
+        let errors: Vec<_> = tree.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec!["the statements are nested too deeply at 1000..1002"]
+        );
+        let source = format!(
+            "PRINT: {}1{}\nPRINT: 2",
+            "-".repeat(50_000),
+            " + 1".repeat(50)
+        );
+        let tree = SyntaxTree::parse(&source);
+        assert_eq!(tree.to_string(), source);
+        let errors: Vec<_> = tree.errors().iter().map(ToString::to_string).collect();
+        assert_eq!(
+            errors,
+            vec!["the expression is nested too deeply at 106..107"]
+        );
+        let prints = tree
+            .root()
+            .descendants()
+            .filter(|n| n.kind() == NodeKind::Print);
+        assert_eq!(prints.count(), 2);
+    }
+
+    #[test]
+    fn invalid_values() {
+        let tree = SyntaxTree::parse("VARIABLES: a: Foo\nPRINT: 'ab'");
+        assert_eq!(
+            tree.to_program().unwrap_err().to_string(),
+            "expected type at 14..17"
+        );
+        let tree = SyntaxTree::parse("PRINT: 'ab'");
+        assert_eq!(
+            tree.to_program().unwrap_err().to_string(),
+            "a character literal holds exactly one character at 7..11"
+        );
+    }
+}
@@ -0,0 +1,668 @@
+//! Builds the [`SyntaxTree`](crate::SyntaxTree) of a program by recursive
+//! descent, accepting the same programs as [`crate::parser`], and recovering
+//! from errors at the end of the line. The two are compared on random programs
+//! in the tests of [`crate::cst`].
+//!
+//! Trivia is only attached when the next token or node is, so nodes start
+//! and end at significant tokens, and the trivia between them belongs to
+//! their parent.
+
+use crate::cst::{GreenElement, GreenNode, GreenToken, NodeKind, SyntaxError};
+use crate::{Span, MAX_DEPTH};
+use expr_parser::{Associativity, BinaryCode, MAX_DEPTH as MAX_EXPR_DEPTH};
+use lexer::{Keyword, Lexer, Token, TokenKind};
+use std::sync::Arc;
+
+/// Builds the tree of `source`.
+pub(crate) fn parse(source: &str) -> GreenNode {
+    let mut p = Parser::new(source, 0);
+    p.builder.start_node(NodeKind::Program);
+    p.mode = Mode::Declarations;
+    if let (Some(TokenKind::Keyword(Keyword::Variables)), Some(TokenKind::Colon)) =
+        (p.nth_kind(0), p.nth_kind(1))
+    {
+        p.variables();
+    }
+    p.mode = Mode::Line;
+    p.block(true);
+    p.flush_trivia();
+    p.builder.finish_node();
+    p.builder.finish()
+}
+
+/// Builds the node of the statement starting at `start`, nested in `depth`
+/// blocks, looking at the source after it as much as a full parse would.
+/// `None` if no statement starts there.
+pub(crate) fn parse_stmt(source: &str, start: usize, depth: usize) -> Option<GreenNode> {
+    let mut p = Parser::new(source, start);
+    p.depth = depth;
+    let first = p.token(0)?;
+    if p.is_trivia(&first) || !p.starts_stmt() {
+        return None;
+    }
+    p.stmt();
+    Some(p.builder.finish())
+}
+
+/// Collects the children of the nodes under construction.
+struct Builder {
+    parents: Vec<Parent>,
+    children: Vec<GreenElement>,
+    /// The end of the last token.
+    offset: usize,
+}
+
+/// A node under construction.
+struct Parent {
+    kind: NodeKind,
+    /// The index of its first child.
+    first: usize,
+    offset: usize,
+    /// Its errors, relative to `offset`.
+    errors: Vec<SyntaxError>,
+}
+
+/// Where a node may be started after its first children are built.
+#[derive(Clone, Copy)]
+struct Checkpoint {
+    first: usize,
+    offset: usize,
+}
+
+impl Builder {
+    fn new(offset: usize) -> Self {
+        Builder {
+            parents: Vec::new(),
+            children: Vec::new(),
+            offset,
+        }
+    }
+
+    fn start_node(&mut self, kind: NodeKind) {
+        let checkpoint = self.checkpoint();
+        self.start_node_at(checkpoint, kind);
+    }
+
+    fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            first: self.children.len(),
+            offset: self.offset,
+        }
+    }
+
+    fn start_node_at(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        self.parents.push(Parent {
+            kind,
+            first: checkpoint.first,
+            offset: checkpoint.offset,
+            errors: Vec::new(),
+        });
+    }
+
+    /// Wraps the children added since `checkpoint` into a node.
+    fn wrap(&mut self, checkpoint: Checkpoint, kind: NodeKind) {
+        self.start_node_at(checkpoint, kind);
+        self.finish_node();
+    }
+
+    fn finish_node(&mut self) {
+        let parent = self.parents.pop().unwrap();
+        let children = self.children.drain(parent.first..).collect();
+        let node = GreenNode::new(parent.kind, children, parent.errors);
+        self.children.push(GreenElement::Node(Arc::new(node)));
+    }
+
+    fn token(&mut self, token: Token) {
+        self.offset += token.text.len();
+        self.children.push(GreenElement::Token(GreenToken {
+            kind: token.kind,
+            text: token.text.to_string(),
+        }));
+    }
+
+    /// Adds an error to the innermost node under construction.
+    fn error(&mut self, error: SyntaxError) {
+        let parent = self.parents.last_mut().unwrap();
+        parent.errors.push(error.shifted(-(parent.offset as isize)));
+    }
+
+    fn finish(mut self) -> GreenNode {
+        match self.children.pop() {
+            Some(GreenElement::Node(node)) if self.children.is_empty() => {
+                Arc::try_unwrap(node).unwrap_or_else(|node| (*node).clone())
+            }
+            _ => panic!("the tree has a single root"),
+        }
+    }
+}
+
+/// Which tokens are skipped as trivia.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Mode {
+    /// Newlines end statements.
+    Line,
+    /// The condition of an `IF`, which may span lines and ends at `THEN`.
+    Condition,
+    /// The declarations, which may span lines.
+    Declarations,
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    start: usize,
+    lexer: Lexer<'a>,
+    /// The tokens from `start`, as far as the parser looked ahead.
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    mode: Mode,
+    builder: Builder,
+    /// How many blocks the current token is in.
+    depth: usize,
+    /// How many calls of [`Parser::binary`] and [`Parser::unary`] are running.
+    nesting: usize,
+    /// Whether the expression being parsed is nested too deeply, so the errors
+    /// of its unfinished nodes are not reported.
+    too_deep: bool,
+}
+
+impl<'a> Parser<'a> {
+    /// A parser of the source from `start`, which must be the start of a token.
+    fn new(source: &'a str, start: usize) -> Self {
+        Parser {
+            source,
+            start,
+            lexer: lexer::lex(&source[start..]),
+            tokens: Vec::new(),
+            pos: 0,
+            mode: Mode::Line,
+            builder: Builder::new(start),
+            depth: 0,
+            nesting: 0,
+            too_deep: false,
+        }
+    }
+
+    fn token(&mut self, index: usize) -> Option<Token<'a>> {
+        while self.tokens.len() <= index {
+            let mut token = self.lexer.next()?;
+            token.start += self.start;
+            self.tokens.push(token);
+        }
+        Some(self.tokens[index])
+    }
+
+    fn is_trivia(&self, token: &Token) -> bool {
+        token.is_trivia() || (token.kind == TokenKind::Newline && self.mode != Mode::Line)
+    }
+
+    /// The `n`th significant token from the current one.
+    fn nth(&mut self, mut n: usize) -> Option<Token<'a>> {
+        let mut index = self.pos;
+        loop {
+            let token = self.token(index)?;
+            if !self.is_trivia(&token) {
+                if n == 0 {
+                    return Some(token);
+                }
+                n -= 1;
+            }
+            index += 1;
+        }
+    }
+
+    fn nth_kind(&mut self, n: usize) -> Option<TokenKind> {
+        self.nth(n).map(|t| t.kind)
+    }
+
+    fn at(&mut self, kind: TokenKind) -> bool {
+        self.nth_kind(0) == Some(kind)
+    }
+
+    fn at_end_of_line(&mut self) -> bool {
+        matches!(self.nth_kind(0), Some(TokenKind::Newline) | None)
+    }
+
+    fn flush_trivia(&mut self) {
+        while let Some(token) = self.token(self.pos) {
+            if !self.is_trivia(&token) {
+                break;
+            }
+            self.builder.token(token);
+            self.pos += 1;
+        }
+    }
+
+    /// Adds the next significant token, and the trivia before it, to the tree.
+    fn bump(&mut self) {
+        self.flush_trivia();
+        if let Some(token) = self.token(self.pos) {
+            self.builder.token(token);
+            self.pos += 1;
+        }
+    }
+
+    fn start_node(&mut self, kind: NodeKind) {
+        self.flush_trivia();
+        self.builder.start_node(kind);
+    }
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.flush_trivia();
+        self.builder.checkpoint()
+    }
+
+    /// Reports an error at the next token.
+    fn error_at_next(&mut self, message: String) {
+        let span = match self.nth(0) {
+            Some(token) => Span::new(token.start, token.end()),
+            None => Span::new(self.source.len(), self.source.len()),
+        };
+        self.builder.error(SyntaxError::new(span, message));
+    }
+
+    /// Reports that the next token is not what was `expected`.
+    fn error(&mut self, expected: &str) {
+        if !self.too_deep {
+            self.error_at_next(format!("expected {}", expected));
+        }
+    }
+
+    /// Takes the next token if it is of `kind`, reports an error otherwise.
+    fn expect(&mut self, kind: TokenKind, expected: &str) -> bool {
+        if self.at(kind) {
+            self.bump();
+            true
+        } else {
+            self.error(expected);
+            false
+        }
+    }
+
+    /// Puts the rest of the line, up to a token of `until`, into an error node.
+    fn recover(&mut self, until: &[TokenKind]) {
+        let stops =
+            |p: &mut Self| p.at_end_of_line() || p.nth_kind(0).is_some_and(|k| until.contains(&k));
+        if stops(self) {
+            return;
+        }
+        self.start_node(NodeKind::Error);
+        while !stops(self) {
+            self.bump();
+        }
+        self.builder.finish_node();
+    }
+
+    /// Ends a statement that runs to the end of the line.
+    fn end_of_line(&mut self) {
+        if !self.at_end_of_line() {
+            self.error("newline");
+            self.recover(&[]);
+        }
+    }
+
+    fn variables(&mut self) {
+        self.start_node(NodeKind::Variables);
+        self.bump();
+        self.bump();
+        let mut declared = self.declaration();
+        while declared && self.at(TokenKind::Comma) {
+            self.bump();
+            declared = self.declaration();
+        }
+        self.builder.finish_node();
+        if !declared {
+            self.mode = Mode::Line;
+            self.recover(&[]);
+        }
+    }
+
+    /// Comma separated names, `:` and a type.
+    fn declaration(&mut self) -> bool {
+        self.start_node(NodeKind::Declaration);
+        let mut declared = self.declared_name();
+        while declared && self.at(TokenKind::Comma) {
+            self.bump();
+            declared = self.declared_name();
+        }
+        declared = declared && self.expect(TokenKind::Colon, "\":\"") && self.type_name();
+        self.builder.finish_node();
+        declared
+    }
+
+    fn declared_name(&mut self) -> bool {
+        let is_name = match self.nth_kind(0) {
+            Some(TokenKind::Word) => true,
+            Some(TokenKind::Keyword(keyword)) => {
+                keyword != Keyword::Variables
+                    && !keyword.is_type()
+                    && expr_parser::Keyword::of(keyword).is_none()
+            }
+            _ => false,
+        };
+        if is_name {
+            self.bump();
+        } else {
+            self.error("name");
+        }
+        is_name
+    }
+
+    fn type_name(&mut self) -> bool {
+        match self.nth_kind(0) {
+            Some(TokenKind::Keyword(keyword)) if keyword.is_type() => {
+                self.bump();
+                true
+            }
+            _ => {
+                self.error("type");
+                false
+            }
+        }
+    }
+
+    /// Statements and newlines up to the keyword closing the block, or the
+    /// end of the source if the block is `top`.
+    fn block(&mut self, top: bool) {
+        self.start_node(NodeKind::Block);
+        while let Some(token) = self.nth(0) {
+            if token.kind == TokenKind::Newline {
+                self.bump();
+            } else if self.starts_stmt() {
+                self.stmt();
+            } else if !top && is_block_end(token.kind) {
+                break;
+            } else {
+                self.error("statement");
+                self.recover(&[]);
+            }
+        }
+        self.builder.finish_node();
+    }
+
+    fn starts_stmt(&mut self) -> bool {
+        let (first, second) = match self.nth(0) {
+            Some(first) => (first, self.nth_kind(1)),
+            None => return false,
+        };
+        match (first.kind, second) {
+            _ if is_name(first) && second == Some(TokenKind::Assign) => true,
+            (TokenKind::Keyword(Keyword::Read), Some(TokenKind::Colon)) => true,
+            (TokenKind::Keyword(Keyword::Print), Some(TokenKind::Colon)) => true,
+            (TokenKind::Keyword(Keyword::If), _) => true,
+            (TokenKind::Keyword(Keyword::While), _) => true,
+            _ => false,
+        }
+    }
+
+    /// A statement, which [`Parser::starts_stmt`].
+    fn stmt(&mut self) {
+        let first = self.nth(0).unwrap();
+        if self.nth_kind(1) == Some(TokenKind::Assign) && is_name(first) {
+            self.start_node(NodeKind::Assign);
+            self.bump();
+            self.bump();
+            self.expr();
+            self.end_of_line();
+            return self.builder.finish_node();
+        }
+        match first.kind {
+            TokenKind::Keyword(Keyword::Read) => {
+                self.start_node(NodeKind::Read);
+                self.bump();
+                self.bump();
+                match self.nth(0) {
+                    Some(token) if is_name(token) => self.bump(),
+                    _ => self.error("name"),
+                }
+                self.end_of_line();
+            }
+            TokenKind::Keyword(Keyword::Print) => {
+                self.start_node(NodeKind::Print);
+                self.bump();
+                self.bump();
+                self.expr();
+                self.end_of_line();
+            }
+            TokenKind::Keyword(Keyword::If | Keyword::While) if self.depth == MAX_DEPTH => {
+                return self.skip_too_deep();
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                self.start_node(NodeKind::If);
+                self.bump();
+                self.mode = Mode::Condition;
+                self.expr();
+                if !self.at(TokenKind::Keyword(Keyword::Then)) {
+                    self.error("\"THEN\"");
+                    self.mode = Mode::Line;
+                    self.recover(&[TokenKind::Keyword(Keyword::Then)]);
+                }
+                if self.at(TokenKind::Keyword(Keyword::Then)) {
+                    self.bump();
+                }
+                self.mode = Mode::Line;
+                self.inner_block();
+                if self.at(TokenKind::Keyword(Keyword::Else)) {
+                    self.start_node(NodeKind::Else);
+                    self.bump();
+                    self.inner_block();
+                    self.builder.finish_node();
+                }
+                self.expect(TokenKind::Keyword(Keyword::EndIf), "\"END_IF\"");
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                self.start_node(NodeKind::While);
+                self.bump();
+                self.expr();
+                self.end_of_line();
+                self.inner_block();
+                self.expect(TokenKind::Keyword(Keyword::EndWhile), "\"END_WHILE\"");
+            }
+            _ => unreachable!("not the start of a statement"),
+        }
+        self.builder.finish_node();
+    }
+
+    /// A block nested in a statement.
+    fn inner_block(&mut self) {
+        self.depth += 1;
+        self.block(false);
+        self.depth -= 1;
+    }
+
+    /// Puts a statement whose blocks would be nested too deeply into an error
+    /// node, up to the keyword closing it, without parsing the blocks.
+    fn skip_too_deep(&mut self) {
+        self.error_at_next("the statements are nested too deeply".to_string());
+        self.start_node(NodeKind::Error);
+        let mut open = 0;
+        while let Some(token) = self.nth(0) {
+            match token.kind {
+                TokenKind::Keyword(Keyword::If | Keyword::While) => open += 1,
+                TokenKind::Keyword(Keyword::EndIf | Keyword::EndWhile) => open -= 1,
+                _ => {}
+            }
+            self.bump();
+            if open == 0 {
+                break;
+            }
+        }
+        self.builder.finish_node();
+    }
+
+    fn expr(&mut self) {
+        self.binary(0);
+        if self.nesting == 0 {
+            self.too_deep = false;
+        }
+    }
+
+    /// Reports an expression nested deeper than [`expr_parser::MAX_DEPTH`],
+    /// and puts the rest of it into an error node.
+    fn skip_too_deep_expr(&mut self) {
+        if !self.too_deep {
+            self.error_at_next("the expression is nested too deeply".to_string());
+            self.too_deep = true;
+        }
+        self.recover(&[TokenKind::Keyword(Keyword::Then)]);
+    }
+
+    /// Starts a call of [`Parser::binary`] or [`Parser::unary`], if they
+    /// don't nest too deeply.
+    fn enter(&mut self) -> bool {
+        if self.nesting == MAX_EXPR_DEPTH {
+            self.skip_too_deep_expr();
+            return false;
+        }
+        self.nesting += 1;
+        true
+    }
+
+    /// Binary operators of at least `min` precedence, and their operands.
+    fn binary(&mut self, min: u8) {
+        if !self.enter() {
+            return;
+        }
+        let checkpoint = self.checkpoint();
+        self.unary();
+        while let Some(code) = self.nth_kind(0).and_then(binary_code) {
+            if code.precedence() < min {
+                break;
+            }
+            self.bump();
+            self.binary(match code.associativity() {
+                Associativity::Left => code.precedence() + 1,
+                Associativity::Right => code.precedence(),
+            });
+            self.builder.wrap(checkpoint, NodeKind::Binary);
+        }
+        self.nesting -= 1;
+    }
+
+    fn unary(&mut self) {
+        if !self.nth_kind(0).is_some_and(is_prefix) {
+            return self.postfix();
+        }
+        if !self.enter() {
+            return;
+        }
+        self.start_node(NodeKind::Unary);
+        self.bump();
+        self.unary();
+        self.builder.finish_node();
+        self.nesting -= 1;
+    }
+
+    /// An atom followed by any number of indexes and slices.
+    fn postfix(&mut self) {
+        let checkpoint = self.checkpoint();
+        if !self.atom() {
+            return;
+        }
+        while self.at(TokenKind::LBracket) {
+            self.bump();
+            self.expr();
+            let kind = if self.at(TokenKind::Colon) {
+                self.bump();
+                self.expr();
+                NodeKind::Slice
+            } else {
+                NodeKind::Index
+            };
+            self.expect(TokenKind::RBracket, "\"]\"");
+            self.builder.wrap(checkpoint, kind);
+        }
+    }
+
+    /// A value, a name or a wrapped expression, reporting an error if the next
+    /// token starts none.
+    fn atom(&mut self) -> bool {
+        let token = match self.nth(0) {
+            Some(token) => token,
+            None => {
+                self.error("expression");
+                return false;
+            }
+        };
+        let (kind, close) = match token.kind {
+            TokenKind::LParen => (NodeKind::Paren, Some((TokenKind::RParen, "\")\""))),
+            TokenKind::Bar => (NodeKind::Abs, Some((TokenKind::Bar, "\"|\""))),
+            TokenKind::Int | TokenKind::Float | TokenKind::Char | TokenKind::String => {
+                (NodeKind::Literal, None)
+            }
+            TokenKind::Keyword(Keyword::True) | TokenKind::Keyword(Keyword::False) => {
+                (NodeKind::Literal, None)
+            }
+            TokenKind::Keyword(Keyword::Then) if self.mode == Mode::Condition => {
+                self.error("expression");
+                return false;
+            }
+            _ if is_name(token) => (NodeKind::Name, None),
+            _ => {
+                self.error("expression");
+                return false;
+            }
+        };
+        self.start_node(kind);
+        self.bump();
+        if let Some((close, expected)) = close {
+            self.expr();
+            self.expect(close, expected);
+        }
+        self.builder.finish_node();
+        true
+    }
+}
+
+/// Whether the token is a variable name: a word, or a keyword that
+/// expressions don't reserve.
+fn is_name(token: Token) -> bool {
+    match token.kind {
+        TokenKind::Word => true,
+        TokenKind::Keyword(keyword) => expr_parser::Keyword::of(keyword).is_none(),
+        _ => false,
+    }
+}
+
+fn is_block_end(kind: TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Keyword(Keyword::Else)
+            | TokenKind::Keyword(Keyword::EndIf)
+            | TokenKind::Keyword(Keyword::EndWhile)
+    )
+}
+
+fn is_prefix(kind: TokenKind) -> bool {
+    match kind {
+        TokenKind::Plus | TokenKind::Minus => true,
+        TokenKind::Keyword(keyword) => matches!(
+            expr_parser::Keyword::of(keyword),
+            Some(expr_parser::Keyword::Unary(_))
+        ),
+        _ => false,
+    }
+}
+
+/// The binary operator a token is spelled with.
+pub(crate) fn binary_code(kind: TokenKind) -> Option<BinaryCode> {
+    use TokenKind::*;
+    Some(match kind {
+        Caret => BinaryCode::Pow,
+        At => BinaryCode::Search,
+        Star => BinaryCode::Mul,
+        Slash => BinaryCode::Div,
+        Plus => BinaryCode::Add,
+        Minus => BinaryCode::Sub,
+        Eq => BinaryCode::Eq,
+        Ne => BinaryCode::Ne,
+        Lt => BinaryCode::Lt,
+        Le => BinaryCode::Le,
+        Gt => BinaryCode::Gt,
+        Ge => BinaryCode::Ge,
+        Keyword(keyword) => match expr_parser::Keyword::of(keyword)? {
+            expr_parser::Keyword::Binary(code) => code,
+            _ => return None,
+        },
+        _ => return None,
+    })
+}
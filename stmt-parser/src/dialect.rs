@@ -0,0 +1,252 @@
+use crate::ast::{self, AstNode};
+use crate::cst::{NodeKind, SyntaxElement, SyntaxNode, SyntaxTree};
+use crate::{stmt::fmt_block, Dialect, ParseError, Program, Stmt};
+use lexer::{Keyword, TokenKind};
+use std::fmt;
+
+/// The statement level keywords of a dialect.
+pub(crate) struct Keywords {
+    pub variables: &'static str,
+    pub print: &'static str,
+    pub read: &'static str,
+    pub if_head: &'static str,
+    pub if_then: &'static str,
+    pub else_head: &'static str,
+    pub if_end: &'static str,
+    pub while_head: &'static str,
+    pub while_end: &'static str,
+}
+
+const ENGLISH: Keywords = Keywords {
+    variables: "VARIABLES",
+    print: "PRINT",
+    read: "READ",
+    if_head: "IF",
+    if_then: "THEN",
+    else_head: "ELSE",
+    if_end: "END_IF",
+    while_head: "WHILE",
+    while_end: "END_WHILE",
+};
+
+const HUNGARIAN: Keywords = Keywords {
+    variables: "VÁLTOZÓK",
+    print: "KI",
+    read: "BE",
+    if_head: "HA",
+    if_then: "AKKOR",
+    else_head: "KÜLÖNBEN",
+    if_end: "HA_VÉGE",
+    while_head: "CIKLUS AMÍG",
+    while_end: "CIKLUS_VÉGE",
+};
+
+impl Keywords {
+    pub fn of(dialect: Dialect) -> &'static Keywords {
+        match dialect {
+            Dialect::English => &ENGLISH,
+            Dialect::Hungarian => &HUNGARIAN,
+        }
+    }
+}
+
+/// Formats a node with every keyword, type name and word operator
+/// written in the given dialect.
+pub struct Translated<'a, T> {
+    node: &'a T,
+    dialect: Dialect,
+}
+
+impl<E> Stmt<E> {
+    pub fn translated(&self, dialect: Dialect) -> Translated<'_, Self> {
+        Translated {
+            node: self,
+            dialect,
+        }
+    }
+}
+
+impl<E> Program<E> {
+    pub fn translated(&self, dialect: Dialect) -> Translated<'_, Self> {
+        Translated {
+            node: self,
+            dialect,
+        }
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Translated<'_, Stmt<E>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt_indented(f, 0, Some(self.dialect))
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Translated<'_, Program<E>> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt_variables(f, self.dialect)?;
+        fmt_block(f, self.node.stmts(), 0, Some(self.dialect))
+    }
+}
+
+/// Rewrites every keyword, type name and word operator of a program into `to`.
+///
+/// The rest of the source, including identifiers, literals, comments and
+/// whitespace, is copied as is, like [`expr_parser::translate`] does for
+/// expressions. Programs that don't parse are rejected.
+pub fn translate(source: &str, to: Dialect) -> Result<String, ParseError> {
+    Program::parse(source)?;
+    let tree = SyntaxTree::parse(source);
+    let mut out = String::with_capacity(source.len());
+    write_translated(tree.root(), to, &mut out);
+    Ok(out)
+}
+
+/// Writes the source of `node`, with the keywords of its statements and
+/// declarations and of the expressions in it translated.
+fn write_translated(node: SyntaxNode, to: Dialect, out: &mut String) {
+    let mut first = true;
+    let mut after_colon = false;
+    for child in node.children() {
+        let token = match child {
+            SyntaxElement::Node(child) => {
+                write_translated(child, to, out);
+                first = false;
+                continue;
+            }
+            SyntaxElement::Token(token) if token.is_trivia() => {
+                out.push_str(token.text());
+                continue;
+            }
+            SyntaxElement::Token(token) => token,
+        };
+        let spelling = match (node.kind(), token.kind()) {
+            (NodeKind::Name, _) => None,
+            (kind, TokenKind::Keyword(_)) if kind.is_expr() => {
+                Some(expr_parser::translate(token.text(), to))
+            }
+            (NodeKind::Declaration, TokenKind::Keyword(_)) if after_colon => {
+                ast::Declaration::cast(node)
+                    .and_then(|declaration| declaration.its_type())
+                    .map(|t| cased(token.text(), &t.name(to)))
+            }
+            (kind, TokenKind::Keyword(keyword)) => {
+                stmt_keyword(kind, keyword, first, Keywords::of(to))
+                    .map(|spelling| cased(token.text(), spelling))
+            }
+            _ => None,
+        };
+        out.push_str(spelling.as_deref().unwrap_or(token.text()));
+        after_colon |= token.kind() == TokenKind::Colon;
+        first = false;
+    }
+}
+
+/// The spelling of a keyword of a statement node, `None` for the names that
+/// are spelled like keywords.
+fn stmt_keyword(
+    kind: NodeKind,
+    keyword: Keyword,
+    first: bool,
+    keywords: &Keywords,
+) -> Option<&'static str> {
+    Some(match (kind, keyword) {
+        (NodeKind::Variables, Keyword::Variables) if first => keywords.variables,
+        (NodeKind::Read, Keyword::Read) if first => keywords.read,
+        (NodeKind::Print, Keyword::Print) if first => keywords.print,
+        (NodeKind::If, Keyword::If) if first => keywords.if_head,
+        (NodeKind::If, Keyword::Then) => keywords.if_then,
+        (NodeKind::If, Keyword::EndIf) => keywords.if_end,
+        (NodeKind::Else, Keyword::Else) if first => keywords.else_head,
+        (NodeKind::While, Keyword::While) if first => keywords.while_head,
+        (NodeKind::While, Keyword::EndWhile) => keywords.while_end,
+        _ => return None,
+    })
+}
+
+/// Keeps a keyword written in lower case in lower case, if it has a lower
+/// case spelling.
+fn cased(original: &str, spelling: &str) -> String {
+    let lower = spelling.to_lowercase();
+    if original.chars().any(char::is_uppercase) || Keyword::parse(&lower).is_none() {
+        spelling.to_string()
+    } else {
+        lower
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENGLISH_SOURCE: &str = r#"VARIABLES: i, n: Int, s: String
+READ: n
+i := 0
+WHILE i < n AND NOT s @ "IF"
+    IF i MOD 2 = 0 THEN
+        PRINT: UPPER s
+    ELSE
+        PRINT: i
+    END_IF
+    i := i + 1
+END_WHILE
+"#;
+
+    const HUNGARIAN_SOURCE: &str = r#"VÁLTOZÓK: i, n: Egész, s: Szöveg
+BE: n
+i := 0
+CIKLUS AMÍG i < n ÉS NEM s @ "IF"
+    HA i MOD 2 = 0 AKKOR
+        KI: NAGY s
+    KÜLÖNBEN
+        KI: i
+    HA_VÉGE
+    i := i + 1
+CIKLUS_VÉGE
+"#;
+
+    #[test]
+    fn to_hungarian() {
+        assert_eq!(
+            translate(ENGLISH_SOURCE, Dialect::Hungarian),
+            Ok(HUNGARIAN_SOURCE.to_string())
+        );
+    }
+
+    #[test]
+    fn to_english() {
+        assert_eq!(
+            translate(HUNGARIAN_SOURCE, Dialect::English),
+            Ok(ENGLISH_SOURCE.to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_comments_and_spacing() {
+        let source = "// számol\nváltozók:  n: egész // a határ\n\nBE:n\n\
+                      ciklus amíg n>0 // amíg van\n\tki: n  MOD 2   // bit\n\
+                      \tn := n DIV 2\nciklus_vége\nKI: \"ÉS // nem\"\n";
+        assert_eq!(
+            translate(source, Dialect::English),
+            Ok("// számol\nvariables:  n: int // a határ\n\nREAD:n\n\
+                while n>0 // amíg van\n\tprint: n  MOD 2   // bit\n\
+                \tn := n DIV 2\nend_while\nPRINT: \"ÉS // nem\"\n"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_names_spelled_like_keywords() {
+        assert_eq!(
+            translate("BE: ki\nHA ki THEN\n  fi := be\nFI\n", Dialect::English),
+            Ok("READ: ki\nIF ki THEN\n  fi := be\nEND_IF\n".to_string())
+        );
+    }
+
+    #[test]
+    fn keeps_identifiers() {
+        assert_eq!(
+            translate("ki := nem igaz\nKI: ki\n", Dialect::English),
+            Ok("ki := not true\nPRINT: ki\n".to_string())
+        );
+    }
+}
@@ -0,0 +1,12 @@
+use derive_more::Display;
+
+pub type StmtsError = lalrpop_util::ParseError<usize, String, &'static str>;
+pub type VarsError = lalrpop_util::ParseError<usize, String, &'static str>;
+pub type ExprError = lalrpop_util::ParseError<usize, String, &'static str>;
+
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum ParseError {
+    Stmts(StmtsError),
+    Variables(VarsError),
+    Expr(ExprError),
+}
@@ -0,0 +1,594 @@
+use crate::{Dialect, ParseError, Program, RawExpr, Span, Stmt, VarName};
+use derive_more::{Display, From};
+use expr_parser::{BinaryCode, Expr, Type, UnaryCode, Value};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use vars_parser::Variable;
+
+/// What the uses of a variable tell about its type.
+#[derive(Debug, PartialEq, Clone, Display)]
+pub enum Constraint {
+    #[display(fmt = "{}", _0)]
+    Exactly(Type),
+    #[display(fmt = "a number")]
+    Number,
+    #[display(fmt = "text")]
+    Text,
+}
+
+impl Constraint {
+    /// The constraint satisfying both, `None` if they contradict each other.
+    ///
+    /// An `Int` and a `Float` give a `Float`, because `Int`s widen into `Float` variables.
+    /// Where only an `Int` will do, the inferrer refuses that widening itself.
+    fn join(&self, other: &Constraint) -> Option<Constraint> {
+        use Constraint::*;
+        match (self, other) {
+            _ if self == other => Some(self.clone()),
+            (Number, Exactly(t)) | (Exactly(t), Number) if t.is_number() => {
+                Some(Exactly(t.clone()))
+            }
+            (Text, Exactly(t)) | (Exactly(t), Text) if t.is_text() => Some(Exactly(t.clone())),
+            (Exactly(a), Exactly(b)) if a.is_number() && b.is_number() => {
+                Some(Exactly(Type::Float))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_number(&self) -> bool {
+        match self {
+            Constraint::Exactly(t) => t.is_number(),
+            c => *c == Constraint::Number,
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        match self {
+            Constraint::Exactly(t) => t.is_text(),
+            c => *c == Constraint::Text,
+        }
+    }
+
+    /// The type a variable gets if nothing narrows the constraint any further.
+    fn resolve(&self) -> Type {
+        match self {
+            Constraint::Exactly(t) => t.clone(),
+            Constraint::Number => Type::Int,
+            Constraint::Text => Type::String,
+        }
+    }
+}
+
+/// A variable that is used in two incompatible ways.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Conflict {
+    var: VarName,
+    first: Constraint,
+    second: Constraint,
+    span: Option<Span>,
+}
+
+impl Conflict {
+    pub fn var(&self) -> &VarName {
+        &self.var
+    }
+
+    /// What the variable was known to be before the conflicting use.
+    pub fn first(&self) -> &Constraint {
+        &self.first
+    }
+
+    pub fn second(&self) -> &Constraint {
+        &self.second
+    }
+
+    /// The span of the statement with the conflicting use.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is used both as {} and as {}",
+            self.var, self.first, self.second
+        )?;
+        match self.span {
+            Some(span) => write!(f, " at {}", span),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The outcome of [`Program::infer_variables`].
+#[derive(Debug, PartialEq, Clone)]
+pub struct Inference {
+    variables: Vec<Variable>,
+    conflicts: Vec<Conflict>,
+}
+
+impl Inference {
+    /// Every variable of the program: the declared ones first, then the rest
+    /// in the order they first appear in.
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Display, From)]
+pub enum InferError {
+    Parse(ParseError),
+    #[display(fmt = "{}", "display_conflicts(_0)")]
+    Conflicts(Vec<Conflict>),
+}
+
+fn display_conflicts(conflicts: &[Conflict]) -> String {
+    let conflicts: Vec<_> = conflicts.iter().map(ToString::to_string).collect();
+    conflicts.join("\n")
+}
+
+impl Program<Expr> {
+    /// Infers the type of every variable from the values assigned to it and
+    /// the operators it is used with.
+    ///
+    /// Declared variables keep their type, uses that contradict it are conflicts.
+    /// Variables that are only read, without any hint about their type, are `String`s.
+    pub fn infer_variables(&self) -> Inference {
+        let mut inferrer = Inferrer::default();
+        for var in self.variables() {
+            let t = Constraint::Exactly(var.its_type().clone());
+            inferrer.seen(var.name());
+            inferrer.declared.insert(var.name().clone(), t.clone());
+            inferrer.known.insert(var.name().clone(), t);
+        }
+        loop {
+            inferrer.changed = false;
+            for (at, s) in self.pre_order().enumerate() {
+                inferrer.span = self.spans().get(at).copied();
+                inferrer.stmt(s);
+            }
+            if !inferrer.changed {
+                break;
+            }
+        }
+        Inference {
+            variables: inferrer
+                .order
+                .iter()
+                .map(|name| {
+                    let t = inferrer
+                        .known
+                        .get(name)
+                        .map_or(Type::String, Constraint::resolve);
+                    Variable::new(name.clone(), t)
+                })
+                .collect(),
+            conflicts: inferrer.conflicts,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Inferrer {
+    known: HashMap<VarName, Constraint>,
+    declared: HashMap<VarName, Constraint>,
+    order: Vec<VarName>,
+    conflicted: HashSet<VarName>,
+    /// The variables used where only an `Int` will do, which must not widen into `Float`s.
+    integral: HashSet<VarName>,
+    conflicts: Vec<Conflict>,
+    /// The span of the statement being inspected.
+    span: Option<Span>,
+    changed: bool,
+}
+
+impl Inferrer {
+    fn seen(&mut self, name: &VarName) {
+        if !self.order.contains(name) {
+            self.order.push(name.clone())
+        }
+    }
+
+    /// Inspects a statement, not counting the statements nested in it.
+    fn stmt(&mut self, s: &Stmt<Expr>) {
+        match s {
+            Stmt::Assign(var, e) => {
+                self.walk(e);
+                self.seen(var);
+                if let Some(c) = self.synth(e) {
+                    self.constrain(var, c)
+                }
+                if let Some(c) = self.known.get(var).cloned() {
+                    // Any number can be assigned to a `Float`, it says nothing about `e`.
+                    let c = if c == Constraint::Exactly(Type::Float) {
+                        Constraint::Number
+                    } else {
+                        c
+                    };
+                    self.check(e, c)
+                }
+            }
+            Stmt::Print(e) => self.walk(e),
+            Stmt::Read(var) => self.seen(var),
+            Stmt::If(cond, _) | Stmt::IfElse(cond, _, _) | Stmt::While(cond, _) => {
+                self.walk(cond);
+                self.check(cond, Constraint::Exactly(Type::Bool));
+            }
+        }
+    }
+
+    fn constrain(&mut self, var: &VarName, c: Constraint) {
+        if self.conflicted.contains(var) {
+            return;
+        }
+        let known = match self.known.get(var) {
+            Some(known) => known,
+            None => {
+                self.known.insert(var.clone(), c);
+                self.changed = true;
+                return;
+            }
+        };
+        match known.join(&c) {
+            Some(joined)
+                if self.declared.get(var).is_none_or(|d| *d == joined)
+                    && !(self.integral.contains(var)
+                        && joined == Constraint::Exactly(Type::Float)) =>
+            {
+                if joined != *known {
+                    self.known.insert(var.clone(), joined);
+                    self.changed = true;
+                }
+            }
+            _ => {
+                self.conflicts.push(Conflict {
+                    var: var.clone(),
+                    first: known.clone(),
+                    second: c,
+                    span: self.span,
+                });
+                self.conflicted.insert(var.clone());
+            }
+        }
+    }
+
+    /// The type of `e`, as far as it is known.
+    fn synth(&self, e: &Expr) -> Option<Constraint> {
+        use Constraint::*;
+        use Type::*;
+        Some(match e {
+            Expr::Value(Value::VarName(name)) => self.known.get(name)?.clone(),
+            Expr::Value(x) => Exactly(x.type_of()?),
+            Expr::Unary(x) => match x.op_code() {
+                UnaryCode::Parentheses => self.synth(x.rhs())?,
+                UnaryCode::Plus | UnaryCode::Neg | UnaryCode::Abs | UnaryCode::Random => self
+                    .synth(x.rhs())
+                    .filter(Constraint::is_number)
+                    .unwrap_or(Number),
+                UnaryCode::Round | UnaryCode::ToInt => Exactly(Int),
+                UnaryCode::Not | UnaryCode::IsDigit | UnaryCode::IsWordChar => Exactly(Bool),
+                UnaryCode::ToUpper | UnaryCode::ToLower => self
+                    .synth(x.rhs())
+                    .filter(Constraint::is_text)
+                    .unwrap_or(Text),
+                _ => Exactly(Float),
+            },
+            Expr::Binary(x) => {
+                let (l, r) = (self.synth(x.lhs()), self.synth(x.rhs()));
+                let either = |f: fn(&Constraint) -> bool| {
+                    l.as_ref().is_some_and(f) || r.as_ref().is_some_and(f)
+                };
+                match x.op_code() {
+                    BinaryCode::Add if either(Constraint::is_text) => Exactly(String),
+                    BinaryCode::Add | BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Pow => {
+                        match (l, r) {
+                            (Some(l), Some(r)) => l.join(&r).unwrap_or(Number),
+                            (Some(c), None) | (None, Some(c)) if c.is_number() => c,
+                            _ => Number,
+                        }
+                    }
+                    BinaryCode::Div => Exactly(Float),
+                    BinaryCode::IntDiv | BinaryCode::Mod => Exactly(Int),
+                    _ => Exactly(Bool),
+                }
+            }
+            Expr::Index(..) => Exactly(Char),
+            Expr::Slice(..) => Exactly(String),
+        })
+    }
+
+    /// Expects `e` to satisfy `c`, constraining it if it is a variable.
+    fn check(&mut self, e: &Expr, c: Constraint) {
+        match e {
+            Expr::Value(Value::VarName(name)) => self.constrain(name, c),
+            Expr::Unary(x) if *x.op_code() == UnaryCode::Parentheses => self.check(x.rhs(), c),
+            _ => {}
+        }
+    }
+
+    /// Expects `e` to be an `Int`, so that none of its variables may widen into a `Float`.
+    fn require_int(&mut self, e: &Expr) {
+        match e {
+            Expr::Value(Value::VarName(name)) => {
+                self.integral.insert(name.clone());
+                self.constrain(name, Constraint::Exactly(Type::Int))
+            }
+            Expr::Unary(x) => match x.op_code() {
+                UnaryCode::Parentheses | UnaryCode::Plus | UnaryCode::Neg | UnaryCode::Abs => {
+                    self.require_int(x.rhs())
+                }
+                _ => {}
+            },
+            Expr::Binary(x) => match x.op_code() {
+                BinaryCode::Add | BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Pow => {
+                    self.require_int(x.lhs());
+                    self.require_int(x.rhs());
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Constrains the operands of every operator in `e`.
+    fn walk(&mut self, e: &Expr) {
+        use Constraint::*;
+        use Type::*;
+        if let Expr::Value(Value::VarName(name)) = e {
+            self.seen(name);
+        }
+        match e {
+            Expr::Value(_) => {}
+            Expr::Unary(x) => match x.op_code() {
+                UnaryCode::Parentheses => {}
+                UnaryCode::Not => self.check(x.rhs(), Exactly(Bool)),
+                UnaryCode::ToUpper | UnaryCode::ToLower => self.check(x.rhs(), Text),
+                UnaryCode::IsDigit | UnaryCode::IsWordChar => self.check(x.rhs(), Exactly(Char)),
+                _ => self.check(x.rhs(), Number),
+            },
+            Expr::Binary(x) => {
+                let (l, r) = (x.lhs(), x.rhs());
+                let both = |this: &mut Self, c: Constraint| {
+                    this.check(l, c.clone());
+                    this.check(r, c);
+                };
+                match x.op_code() {
+                    BinaryCode::Add => {
+                        let hint = self.synth(l).or_else(|| self.synth(r));
+                        match hint {
+                            Some(c) if c.is_text() => both(self, Text),
+                            Some(c) if c.is_number() => both(self, Number),
+                            _ => {}
+                        }
+                    }
+                    BinaryCode::Sub | BinaryCode::Mul | BinaryCode::Div | BinaryCode::Pow => {
+                        both(self, Number)
+                    }
+                    BinaryCode::IntDiv | BinaryCode::Mod => {
+                        self.require_int(l);
+                        self.require_int(r);
+                    }
+                    BinaryCode::And | BinaryCode::Or => both(self, Exactly(Bool)),
+                    BinaryCode::Search => {
+                        self.check(l, Exactly(String));
+                        self.check(r, Text);
+                    }
+                    _ => {
+                        let comparable = |c: Constraint| if c.is_number() { Number } else { c };
+                        if let Some(c) = self.synth(r) {
+                            self.check(l, comparable(c))
+                        }
+                        if let Some(c) = self.synth(l) {
+                            self.check(r, comparable(c))
+                        }
+                    }
+                }
+            }
+            Expr::Index(x, at) => {
+                self.check(x, Exactly(String));
+                self.require_int(at);
+            }
+            Expr::Slice(x, start, end) => {
+                self.check(x, Exactly(String));
+                self.require_int(start);
+                self.require_int(end);
+            }
+        }
+        for child in e.children() {
+            self.walk(child)
+        }
+    }
+}
+
+/// Infers the variables of `source` and writes them into its `VARIABLES:` line,
+/// which is added if the source has none. The rest of the source is kept as is.
+pub fn declare_variables(source: &str, dialect: Dialect) -> Result<String, InferError> {
+    let inference = Program::parse(source)?.parse_exprs()?.infer_variables();
+    if !inference.conflicts.is_empty() {
+        return Err(inference.conflicts.into());
+    }
+    if inference.variables.is_empty() {
+        return Ok(source.to_string());
+    }
+    let declarations = Program::<RawExpr>::new(inference.variables, Vec::new())
+        .translated(dialect)
+        .to_string();
+    let declarations = declarations.trim_end();
+    let existing = crate::parser::parse_program(source)
+        .map_err(ParseError::Stmts)?
+        .variables;
+    Ok(match existing {
+        Some(span) => format!(
+            "{}{}{}",
+            &source[..span.start()],
+            declarations,
+            &source[span.end()..]
+        ),
+        None => {
+            let start = source.len() - source.trim_start().len();
+            let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+            format!(
+                "{}{}\n{}{}",
+                &source[..start],
+                declarations,
+                &source[line_start..start],
+                &source[start..]
+            )
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn infer(source: &str) -> Inference {
+        Program::parse(source)
+            .unwrap()
+            .parse_exprs()
+            .unwrap()
+            .infer_variables()
+    }
+
+    fn types(source: &str) -> Vec<String> {
+        let inference = infer(source);
+        assert_eq!(inference.conflicts(), &[]);
+        inference
+            .variables()
+            .iter()
+            .map(|var| format!("{}: {}", var.name(), var.its_type()))
+            .collect()
+    }
+
+    #[test]
+    fn from_assignments() {
+        assert_eq!(
+            types("i := 0\ns := \"a\"\nc := s[i]\nb := i < 3"),
+            vec!["i: Int", "s: String", "c: Char", "b: Bool"]
+        );
+    }
+
+    #[test]
+    fn from_operators() {
+        assert_eq!(
+            types("READ: n\nREAD: s\nREAD: x\nPRINT: n MOD 2\nPRINT: s @ 'a'\nPRINT: SIN x"),
+            vec!["n: Int", "s: String", "x: Int"]
+        );
+    }
+
+    #[test]
+    fn widens_to_float() {
+        assert_eq!(types("x := 0\nx := x + 0.5"), vec!["x: Float"]);
+    }
+
+    #[test]
+    fn through_other_variables() {
+        assert_eq!(
+            types("READ: a\nb := a\nIF b THEN\nFI"),
+            vec!["a: Bool", "b: Bool"]
+        );
+    }
+
+    #[test]
+    fn read_only() {
+        assert_eq!(types("READ: a\nPRINT: a"), vec!["a: String"]);
+    }
+
+    #[test]
+    fn keeps_declarations() {
+        assert_eq!(
+            types("VARIABLES: x: Float\ny := x * 2\nx := 1"),
+            vec!["x: Float", "y: Float"]
+        );
+    }
+
+    #[test]
+    fn conflict() {
+        let source = "a := 1\nPRINT: a\na := \"one\"";
+        let conflicts = infer(source).conflicts;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts[0].to_string(),
+            "`a` is used both as Int and as String at 16..26"
+        );
+    }
+
+    #[test]
+    fn conflict_with_declaration() {
+        let conflicts = infer("VARIABLES: a: Int\na := 0.5").conflicts;
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].second(), &Constraint::Exactly(Type::Float));
+    }
+
+    #[test]
+    fn int_operands_do_not_widen() {
+        for source in [
+            "i := 0.5\nPRINT: i MOD 2",
+            "s := \"abc\"\ni := 0.5\nPRINT: s[i]",
+            "s := \"abc\"\ni := 1\nPRINT: s[i - 1:2]\ni := i / 2",
+            "VARIABLES: x: Float\nPRINT: 7 DIV x",
+        ] {
+            let conflicts = infer(source).conflicts;
+            assert_eq!(conflicts.len(), 1, "{}", source);
+        }
+        assert_eq!(
+            types("i := 0\nx := 0.5\nx := i\nPRINT: i MOD 2"),
+            vec!["i: Int", "x: Float"]
+        );
+    }
+
+    #[test]
+    fn declarations_type_check() {
+        for source in [
+            "READ: n\ni := 0\nWHILE i < n\n    i := i + 1\nDONE",
+            "i := 0\nx := 0.5\nx := i\nPRINT: i MOD 2",
+            "x := 0\nx := x + 0.5\nPRINT: -x",
+            "READ: s\ni := 1\nPRINT: s[i] + s[0:i]\nc := s[i]",
+            "READ: a\nb := a\nIF b THEN\n    PRINT: 1\nELSE\n    PRINT: 2\nFI",
+            "n := 10\nm := n DIV 3 + n MOD 3\ny := n / 4\nPRINT: m * y",
+        ] {
+            let declared = declare_variables(source, Dialect::English).unwrap();
+            let (_, diagnostics) = Program::parse(&declared)
+                .unwrap()
+                .parse_exprs()
+                .unwrap()
+                .type_check();
+            assert_eq!(diagnostics, vec![], "{}", declared);
+        }
+    }
+
+    #[test]
+    fn inserts_declarations() {
+        let source = "\n    READ: n\n    i := 0\n    WHILE i < n\n        i := i + 1\n    DONE\n";
+        let declared = declare_variables(source, Dialect::English).unwrap();
+        assert_eq!(
+            declared,
+            "\n    VARIABLES: n, i: Int\n    READ: n\n    i := 0\n    WHILE i < n\n        i := i + 1\n    DONE\n"
+        );
+        assert_eq!(declare_variables(&declared, Dialect::English), Ok(declared));
+    }
+
+    #[test]
+    fn replaces_declarations() {
+        let source = "VÁLTOZÓK: a: Egész\nb := a / 2\nKI: b\n";
+        assert_eq!(
+            declare_variables(source, Dialect::Hungarian),
+            Ok("VÁLTOZÓK: a: Egész, b: Valós\nb := a / 2\nKI: b\n".to_string())
+        );
+    }
+
+    #[test]
+    fn refuses_conflicts() {
+        assert!(matches!(
+            declare_variables("a := TRUE\na := 1", Dialect::English),
+            Err(InferError::Conflicts(_))
+        ));
+    }
+}
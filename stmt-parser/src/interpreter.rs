@@ -0,0 +1,446 @@
+use crate::program::Extent;
+use crate::{Program, Span, Stmt, VarName};
+use derive_more::{Display, From};
+use expr_parser::{Env, EvalError, Expr, Keyword, TFloat, TInt, Type, Value};
+use std::collections::HashMap;
+use std::{fmt, io};
+
+/// Where `READ` takes its values from.
+pub trait Input {
+    /// The next line of the input without the line break, `None` at the end of the input.
+    fn next_line(&mut self) -> io::Result<Option<String>>;
+}
+
+impl<R: io::BufRead> Input for R {
+    fn next_line(&mut self) -> io::Result<Option<String>> {
+        let mut line = String::new();
+        if self.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let len = line.trim_end_matches(&['\n', '\r'][..]).len();
+        line.truncate(len);
+        Ok(Some(line))
+    }
+}
+
+/// Where `PRINT` writes its values to.
+pub trait Output {
+    fn print(&mut self, value: &Value) -> io::Result<()>;
+}
+
+/// Every value is written on its own line, strings and characters without quotes.
+impl<W: io::Write> Output for W {
+    fn print(&mut self, value: &Value) -> io::Result<()> {
+        match value {
+            Value::String(s) => writeln!(self, "{}", s),
+            Value::Char(c) => writeln!(self, "{}", c),
+            x => writeln!(self, "{}", x),
+        }
+    }
+}
+
+/// An error that stopped the execution of a program.
+///
+/// The span points to the statement that failed, it is missing if the
+/// program was not parsed from source code.
+#[derive(Debug)]
+pub struct RuntimeError {
+    span: Option<Span>,
+    kind: RuntimeErrorKind,
+}
+
+impl RuntimeError {
+    pub(crate) fn new(span: Option<Span>, kind: RuntimeErrorKind) -> Self {
+        RuntimeError { span, kind }
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn kind(&self) -> &RuntimeErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}", self.kind, span),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+#[derive(Debug, Display, From)]
+pub enum RuntimeErrorKind {
+    Eval(EvalError),
+    #[display(fmt = "the condition is {} instead of a Bool", _0)]
+    #[from(ignore)]
+    NotABool(Value),
+    #[display(fmt = "{} of type {} can't hold {}", var, expected, found)]
+    TypeMismatch {
+        var: VarName,
+        expected: Type,
+        found: Value,
+    },
+    #[display(fmt = "{:?} is not a valid {} for {}", input, expected, var)]
+    InvalidInput {
+        var: VarName,
+        expected: Type,
+        input: String,
+    },
+    #[display(fmt = "the input ended before {} could be read", _0)]
+    #[from(ignore)]
+    EndOfInput(VarName),
+    Io(io::Error),
+}
+
+impl Program<Expr> {
+    /// Executes the program, reading from `input` and printing to `output`.
+    ///
+    /// Declared variables start with the default value of their type and only
+    /// accept values of that type, except that `Int`s are widened into `Float`
+    /// variables. Undeclared variables take any value. Returns the final values
+    /// of the variables.
+    pub fn run(
+        &self,
+        input: &mut impl Input,
+        output: &mut impl Output,
+    ) -> Result<Env, RuntimeError> {
+        self.run_with(Env::new(), input, output)
+    }
+
+    /// Executes the program like [`Program::run`], starting from `env`.
+    ///
+    /// Variables already bound in `env` keep their values.
+    pub fn run_with(
+        &self,
+        mut env: Env,
+        input: &mut impl Input,
+        output: &mut impl Output,
+    ) -> Result<Env, RuntimeError> {
+        for var in self.variables() {
+            if let (None, Some(value)) = (env.get(var.name()), var.its_type().default_value()) {
+                env.set(var.name().clone(), value);
+            }
+        }
+        let mut interpreter = Interpreter {
+            types: self
+                .variables()
+                .iter()
+                .map(|var| (var.name(), var.its_type()))
+                .collect(),
+            spans: self.spans(),
+            extents: self.extents(),
+            env,
+            input,
+            output,
+        };
+        interpreter.block(self.stmts(), 0)?;
+        Ok(interpreter.env)
+    }
+}
+
+struct Interpreter<'a, I, O> {
+    types: HashMap<&'a VarName, &'a Type>,
+    spans: &'a [Span],
+    extents: &'a [Extent],
+    env: Env,
+    input: &'a mut I,
+    output: &'a mut O,
+}
+
+impl<'a, I: Input, O: Output> Interpreter<'a, I, O> {
+    /// Executes `stmts`, the first of which is the `at`th statement in pre-order.
+    fn block(&mut self, stmts: &[Stmt<Expr>], mut at: usize) -> Result<(), RuntimeError> {
+        for s in stmts {
+            self.stmt(s, at)?;
+            at = self.extents[at].end;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, s: &Stmt<Expr>, at: usize) -> Result<(), RuntimeError> {
+        match s {
+            Stmt::Assign(var, e) => {
+                let value = self.eval(e, at)?;
+                self.assign(var, value).map_err(|k| self.error(at, k))
+            }
+            Stmt::Print(e) => {
+                let value = self.eval(e, at)?;
+                self.output
+                    .print(&value)
+                    .map_err(|e| self.error(at, e.into()))
+            }
+            Stmt::Read(var) => self.read(var).map_err(|k| self.error(at, k)),
+            Stmt::If(cond, inner) => {
+                if self.cond(cond, at)? {
+                    self.block(inner, at + 1)?;
+                }
+                Ok(())
+            }
+            Stmt::IfElse(cond, inner, else_inner) => {
+                if self.cond(cond, at)? {
+                    self.block(inner, at + 1)
+                } else {
+                    self.block(else_inner, self.extents[at].second)
+                }
+            }
+            Stmt::While(cond, inner) => {
+                while self.cond(cond, at)? {
+                    self.block(inner, at + 1)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn error(&self, at: usize, kind: RuntimeErrorKind) -> RuntimeError {
+        RuntimeError {
+            span: self.spans.get(at).copied(),
+            kind,
+        }
+    }
+
+    fn eval(&self, e: &Expr, at: usize) -> Result<Value, RuntimeError> {
+        e.eval(&self.env).map_err(|e| self.error(at, e.into()))
+    }
+
+    fn cond(&self, e: &Expr, at: usize) -> Result<bool, RuntimeError> {
+        match self.eval(e, at)? {
+            Value::Boolean(x) => Ok(x),
+            x => Err(self.error(at, RuntimeErrorKind::NotABool(x))),
+        }
+    }
+
+    fn assign(&mut self, var: &VarName, value: Value) -> Result<(), RuntimeErrorKind> {
+        let value = check_assign(var, self.types.get(var).copied(), value)?;
+        self.env.set(var.clone(), value);
+        Ok(())
+    }
+
+    fn read(&mut self, var: &VarName) -> Result<(), RuntimeErrorKind> {
+        let value = read_value(self.input, var, self.types.get(var).copied())?;
+        self.env.set(var.clone(), value);
+        Ok(())
+    }
+}
+
+/// The value a variable of type `t` holds after `value` is assigned to it.
+///
+/// `Int`s widen into `Float`s, undeclared variables, of no type, take any value.
+pub(crate) fn check_assign(
+    var: &VarName,
+    t: Option<&Type>,
+    value: Value,
+) -> Result<Value, RuntimeErrorKind> {
+    match (t, value) {
+        (Some(Type::Float), Value::Int(x)) => Ok(Value::Float(x.into())),
+        (Some(expected), found) if found.type_of().as_ref() != Some(expected) => {
+            Err(RuntimeErrorKind::TypeMismatch {
+                var: var.clone(),
+                expected: expected.clone(),
+                found,
+            })
+        }
+        (_, value) => Ok(value),
+    }
+}
+
+/// Reads the next line of `input` as the value of a variable of type `t`.
+///
+/// Input for undeclared variables is an `Int` or a `Float` if it parses as one,
+/// a `String` otherwise.
+pub(crate) fn read_value(
+    input: &mut impl Input,
+    var: &VarName,
+    t: Option<&Type>,
+) -> Result<Value, RuntimeErrorKind> {
+    let line = input
+        .next_line()?
+        .ok_or_else(|| RuntimeErrorKind::EndOfInput(var.clone()))?;
+    match t {
+        Some(expected) => parse_input(&line, expected).ok_or(RuntimeErrorKind::InvalidInput {
+            var: var.clone(),
+            expected: expected.clone(),
+            input: line,
+        }),
+        None => Ok([Type::Int, Type::Float]
+            .iter()
+            .find_map(|t| parse_input(&line, t))
+            .unwrap_or(Value::String(line))),
+    }
+}
+
+/// Reads a line of input as a value of type `t`.
+///
+/// Surrounding whitespace is ignored, except in strings and characters.
+fn parse_input(line: &str, t: &Type) -> Option<Value> {
+    match t {
+        Type::Bool => match Keyword::parse(line.trim()) {
+            Some(Keyword::Bool(x)) => Some(Value::Boolean(x)),
+            _ => None,
+        },
+        Type::Int => line.trim().parse::<TInt>().ok().map(Value::Int),
+        Type::Float => line.trim().parse::<TFloat>().ok().map(Value::Float),
+        Type::Char => {
+            let mut chars = line.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Some(Value::Char(c)),
+                _ => None,
+            }
+        }
+        Type::String => Some(Value::String(line.to_string())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str, input: &str) -> Result<String, RuntimeError> {
+        let program = Program::parse(source).unwrap().parse_exprs().unwrap();
+        let mut output = Vec::new();
+        program.run(&mut input.as_bytes(), &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    fn run_err<'a>(source: &'a str, input: &str) -> (RuntimeErrorKind, &'a str) {
+        let err = run(source, input).unwrap_err();
+        let span = err.span().unwrap();
+        (err.kind, &source[span.start()..span.end()])
+    }
+
+    #[test]
+    fn sum() {
+        let source = r"
+        VARIABLES: n, i, sum: Int
+        READ: n
+        WHILE i < n
+            i := i + 1
+            sum := sum + i
+        DONE
+        PRINT: sum
+        ";
+        assert_eq!(run(source, "4\n").unwrap(), "10\n");
+    }
+
+    #[test]
+    fn hungarian() {
+        let source = r#"
+        VÁLTOZÓK: nev: Szöveg, kor: Egész
+        BE: nev
+        BE: kor
+        HA kor >= 18 AKKOR
+            KI: nev + " felnőtt"
+        KÜLÖNBEN
+            KI: nev + " gyerek"
+        HA_VÉGE
+        "#;
+        assert_eq!(run(source, "Anna\n12\n").unwrap(), "Anna gyerek\n");
+        assert_eq!(run(source, "Béla\r\n30\r\n").unwrap(), "Béla felnőtt\n");
+    }
+
+    #[test]
+    fn else_after_nested_block() {
+        let source = r"
+        IF FALSE THEN
+            IF TRUE THEN
+                PRINT: 1
+            FI
+        ELSE
+            PRINT: 2 DIV 0
+        FI
+        ";
+        assert_eq!(
+            run_err(source, "").1,
+            "PRINT: 2 DIV 0",
+            "the span of the else branch skips the nested statements"
+        );
+    }
+
+    #[test]
+    fn prints() {
+        let source = "PRINT: 'c'\nPRINT: \"s\"\nPRINT: 1.0\nPRINT: 1 = 1";
+        assert_eq!(run(source, "").unwrap(), "c\ns\n1.0\nTRUE\n");
+    }
+
+    #[test]
+    fn declared_defaults() {
+        let source = "VARIABLES: b: Bool, f: Float, s: String\nPRINT: b\nPRINT: f\nPRINT: s";
+        assert_eq!(run(source, "").unwrap(), "FALSE\n0.0\n\n");
+    }
+
+    #[test]
+    fn widens_int() {
+        let source = "VARIABLES: f: Float\nf := 2\nPRINT: f";
+        assert_eq!(run(source, "").unwrap(), "2.0\n");
+    }
+
+    #[test]
+    fn undeclared_input() {
+        let source = "READ: a\nREAD: b\nREAD: c\nPRINT: a + b\nPRINT: c";
+        assert_eq!(run(source, "1\n0.5\nabc\n").unwrap(), "1.5\nabc\n");
+    }
+
+    #[test]
+    fn final_env() {
+        let program = Program::parse("VARIABLES: a: Int\nREAD: a\nb := a * 2")
+            .unwrap()
+            .parse_exprs()
+            .unwrap();
+        let env = program.run(&mut "21".as_bytes(), &mut io::sink()).unwrap();
+        assert_eq!(env.get(&"b".into()), Some(&Value::Int(42)));
+    }
+
+    #[test]
+    fn eval_error() {
+        let source = "a := 1\nWHILE a < 3\n    PRINT: 1 DIV (a - 2)\n    a := a + 1\nDONE";
+        let (kind, stmt) = run_err(source, "");
+        assert!(matches!(
+            kind,
+            RuntimeErrorKind::Eval(EvalError::DivisionByZero)
+        ));
+        assert_eq!(stmt, "PRINT: 1 DIV (a - 2)");
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let source = "VARIABLES: a: Int\na := \"x\"";
+        let (kind, stmt) = run_err(source, "");
+        assert!(matches!(kind, RuntimeErrorKind::TypeMismatch { .. }));
+        assert_eq!(stmt, "a := \"x\"");
+    }
+
+    #[test]
+    fn not_a_bool() {
+        let (kind, stmt) = run_err("IF 1 THEN\nFI", "");
+        assert!(matches!(kind, RuntimeErrorKind::NotABool(Value::Int(1))));
+        assert_eq!(stmt, "IF 1 THEN\nFI");
+    }
+
+    #[test]
+    fn invalid_input() {
+        let (kind, _) = run_err("VARIABLES: c: Char\nREAD: c", "ab");
+        assert!(matches!(kind, RuntimeErrorKind::InvalidInput { .. }));
+    }
+
+    #[test]
+    fn end_of_input() {
+        let err = run("READ: a\nREAD: b", "1\n").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the input ended before b could be read at 8..15"
+        );
+    }
+
+    #[test]
+    fn without_spans() {
+        let program = Program::new(vec![], vec![Stmt::Print(Expr::from(VarName::from("a")))]);
+        let err = program.run(&mut io::empty(), &mut io::sink()).unwrap_err();
+        assert_eq!(err.span(), None);
+        assert_eq!(err.to_string(), "variable `a` is not bound");
+    }
+}
@@ -1,18 +1,45 @@
-#[macro_use]
-extern crate pest_derive;
-
+pub mod ast;
+mod bytecode;
+mod bytecode_file;
+mod const_fold;
+mod cst;
+mod cst_parser;
+mod dialect;
+mod error;
+mod infer;
+mod interpreter;
+mod parser;
+mod program;
 mod raw_expr;
+mod reparse;
+#[cfg(feature = "serde")]
+mod serialize;
+mod span;
 mod stmt;
+mod typecheck;
+mod visit;
+mod vm;
 
+pub use bytecode::{Bytecode, Instr, Slot};
+pub use bytecode_file::{LoadError, BYTECODE_VERSION};
+pub use cst::{NodeKind, SyntaxElement, SyntaxError, SyntaxNode, SyntaxToken, SyntaxTree};
+pub use dialect::{translate, Translated};
+pub use error::{ExprError, ParseError, StmtsError, VarsError};
+pub use expr_parser::{Dialect, Type};
+pub use infer::{declare_variables, Conflict, Constraint, InferError, Inference};
+pub use interpreter::{Input, Output, RuntimeError, RuntimeErrorKind};
+pub use parser::MAX_DEPTH;
+pub use program::Program;
 pub use raw_expr::RawExpr;
-pub use stmt::Stmt;
+pub use reparse::TextEdit;
+pub use span::Span;
+pub use stmt::{PreOrder, Stmt};
+pub use typecheck::{Diagnostic, DiagnosticKind};
 pub use vars_parser::VarName;
-
-#[allow(unused_imports)]
-use pest::Parser;
-#[derive(Parser)]
-#[grammar = "stmts.pest"]
-struct StmtsParser;
+pub use visit::{
+    fold_stmt_children, walk_block, walk_block_mut, walk_stmt, walk_stmt_mut, StmtFold,
+    StmtVisitor, StmtVisitorMut,
+};
 
 #[cfg(test)]
 mod tests {
@@ -36,6 +63,45 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_then_in_condition() {
+        assert_eq!(
+            Stmt::parse("IF athena = \"then\" AND thenx THEN\nFI"),
+            Ok(vec![Stmt::If("athena = \"then\" AND thenx".into(), vec![])])
+        );
+    }
+
+    #[test]
+    fn test_comments() {
+        assert_eq!(
+            Stmt::parse("a := 1 // one\n// a whole line\nPRINT: \"a\\\"b // c\" // d"),
+            Ok(vec![
+                Stmt::Assign("a".into(), "1".into()),
+                Stmt::Print("\"a\\\"b // c\"".into())
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "IF a THEN\nPRINT: a";
+        assert_eq!(
+            Stmt::parse(source),
+            Err(lalrpop_util::ParseError::UnrecognizedEOF {
+                location: source.len(),
+                expected: vec!["statement".into(), "\"ELSE\"".into(), "\"END_IF\"".into()]
+            })
+        );
+        assert_eq!(
+            Stmt::parse("a := 1\nREAD: a b"),
+            Err(lalrpop_util::ParseError::UnrecognizedToken {
+                token: (15, "b".into(), 16),
+                expected: vec!["newline".into()]
+            })
+        );
+        assert!(Stmt::parse("").is_err());
+    }
+
     #[test]
     fn test_while() {
         assert_eq!(
@@ -62,4 +128,62 @@ mod tests {
             ])
         );
     }
+
+    #[test]
+    fn test_if_else() {
+        assert_eq!(
+            Stmt::parse(
+                r"
+            HA a > 1 AKKOR
+                KI: a
+            KÜLÖNBEN
+            HA_VÉGE
+            "
+            ),
+            Ok(vec![Stmt::IfElse(
+                "a > 1".into(),
+                vec![Stmt::Print("a".into())],
+                vec![]
+            )])
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let stmts = vec![
+            Stmt::Read("a".into()),
+            Stmt::While(
+                "a < 10".into(),
+                vec![
+                    Stmt::IfElse(
+                        "a MOD 2 = 0".into(),
+                        vec![Stmt::Print("\"even\"".into())],
+                        vec![Stmt::Print("a".into())],
+                    ),
+                    Stmt::Assign("a".into(), "a + 1".into()),
+                ],
+            ),
+            Stmt::If("a = 10".into(), vec![]),
+        ];
+        let source = stmts
+            .iter()
+            .map(Stmt::to_string)
+            .collect::<Vec<_>>()
+            .join("\n");
+        assert_eq!(
+            source,
+            r#"READ: a
+WHILE a < 10
+    IF a MOD 2 = 0 THEN
+        PRINT: "even"
+    ELSE
+        PRINT: a
+    END_IF
+    a := a + 1
+END_WHILE
+IF a = 10 THEN
+END_IF"#
+        );
+        assert_eq!(Stmt::parse(&source), Ok(stmts));
+    }
 }
@@ -0,0 +1,309 @@
+//! The statement grammar, parsed by recursive descent over the tokens of
+//! the shared lexer. The expressions are kept as source text, and the
+//! declarations are left to vars-parser.
+
+use crate::{RawExpr, Span, Stmt, StmtsError};
+use lalrpop_util::ParseError;
+use lexer::{Keyword, Token, TokenKind};
+
+/// How deep blocks may nest. Deeper programs are parse errors, so the
+/// recursive passes over the statements can't run out of stack.
+pub const MAX_DEPTH: usize = 100;
+
+const TOO_DEEP: &str = "the statements are nested too deeply";
+
+/// The parts of a program, before its declarations are parsed.
+pub(crate) struct Parsed {
+    /// The `VARIABLES` section, from its header to its last type name.
+    pub variables: Option<Span>,
+    pub stmts: Vec<Stmt>,
+    /// The spans of the statements in pre-order.
+    pub spans: Vec<Span>,
+}
+
+/// Parses an optional `VARIABLES` section followed by any number of statements.
+pub(crate) fn parse_program(source: &str) -> Result<Parsed, StmtsError> {
+    let mut parser = Parser::new(source);
+    parser.skip_newlines();
+    let variables = match (parser.peek_kind(0), parser.peek_kind(1)) {
+        (Some(TokenKind::Keyword(Keyword::Variables)), Some(TokenKind::Colon)) => {
+            Some(parser.variables()?)
+        }
+        _ => None,
+    };
+    let stmts = parser.block()?;
+    parser.end()?;
+    Ok(Parsed {
+        variables,
+        stmts,
+        spans: parser.spans,
+    })
+}
+
+/// Parses one or more statements.
+pub(crate) fn parse_stmts(source: &str) -> Result<Vec<Stmt>, StmtsError> {
+    let mut parser = Parser::new(source);
+    let stmts = parser.block()?;
+    if stmts.is_empty() {
+        return Err(parser.unexpected(&["statement"]));
+    }
+    parser.end()?;
+    Ok(stmts)
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    /// The tokens without whitespace and comments, newlines are kept.
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    spans: Vec<Span>,
+    /// How many blocks the current token is in.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Parser {
+            source,
+            tokens: lexer::lex(source).filter(|t| !t.is_trivia()).collect(),
+            pos: 0,
+            spans: Vec::new(),
+            depth: 0,
+        }
+    }
+
+    fn peek(&self, ahead: usize) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos + ahead)
+    }
+
+    fn peek_kind(&self, ahead: usize) -> Option<TokenKind> {
+        self.peek(ahead).map(|t| t.kind)
+    }
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.tokens.get(self.pos).copied();
+        self.pos += token.is_some() as usize;
+        token
+    }
+
+    fn skip_newlines(&mut self) {
+        while self.peek_kind(0) == Some(TokenKind::Newline) {
+            self.pos += 1;
+        }
+    }
+
+    /// Takes the next token, which must be of `kind`.
+    fn expect(&mut self, kind: TokenKind, name: &'static str) -> Result<Token<'a>, StmtsError> {
+        match self.peek_kind(0) {
+            Some(x) if x == kind => Ok(self.next().unwrap()),
+            _ => Err(self.unexpected(&[name])),
+        }
+    }
+
+    /// Takes the next token, which must be a variable name.
+    fn name(&mut self) -> Result<Token<'a>, StmtsError> {
+        match self.peek(0) {
+            Some(&t) if is_name(t) => Ok(self.next().unwrap()),
+            _ => Err(self.unexpected(&["name"])),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), StmtsError> {
+        self.skip_newlines();
+        match self.peek(0) {
+            Some(_) => Err(self.unexpected(&["statement"])),
+            None => Ok(()),
+        }
+    }
+
+    fn unexpected(&self, expected: &[&str]) -> StmtsError {
+        let expected = expected.iter().map(|x| x.to_string()).collect();
+        match self.peek(0) {
+            Some(t) => ParseError::UnrecognizedToken {
+                token: (t.start, t.text.to_string(), t.end()),
+                expected,
+            },
+            None => ParseError::UnrecognizedEOF {
+                location: self.source.len(),
+                expected,
+            },
+        }
+    }
+
+    /// The extent of a `VARIABLES` section: comma separated declarations of
+    /// comma separated names, each followed by `:` and a type name.
+    fn variables(&mut self) -> Result<Span, StmtsError> {
+        let start = self.next().unwrap().start;
+        self.next();
+        loop {
+            self.skip_newlines();
+            self.name()?;
+            self.skip_newlines();
+            while self.peek_kind(0) == Some(TokenKind::Comma) {
+                self.next();
+                self.skip_newlines();
+                self.name()?;
+                self.skip_newlines();
+            }
+            self.expect(TokenKind::Colon, "\":\"")?;
+            self.skip_newlines();
+            let end = match self.peek_kind(0) {
+                Some(TokenKind::Word) | Some(TokenKind::Keyword(_)) => self.next().unwrap().end(),
+                _ => return Err(self.unexpected(&["type"])),
+            };
+            let before = self.pos;
+            self.skip_newlines();
+            if self.peek_kind(0) == Some(TokenKind::Comma) {
+                self.next();
+            } else {
+                self.pos = before;
+                return Ok(Span::new(start, end));
+            }
+        }
+    }
+
+    /// Statements up to the first token that starts none.
+    fn block(&mut self) -> Result<Vec<Stmt>, StmtsError> {
+        let mut stmts = Vec::new();
+        loop {
+            self.skip_newlines();
+            let (first, second) = match (self.peek(0), self.peek(1)) {
+                (Some(&first), second) => (first, second.map(|t| t.kind)),
+                (None, _) => return Ok(stmts),
+            };
+            let starts_stmt = match (first.kind, second) {
+                _ if is_name(first) && second == Some(TokenKind::Assign) => true,
+                (TokenKind::Keyword(Keyword::Read), Some(TokenKind::Colon)) => true,
+                (TokenKind::Keyword(Keyword::Print), Some(TokenKind::Colon)) => true,
+                (TokenKind::Keyword(Keyword::If), _) => true,
+                (TokenKind::Keyword(Keyword::While), _) => true,
+                _ => false,
+            };
+            if !starts_stmt {
+                return Ok(stmts);
+            }
+            let index = self.spans.len();
+            self.spans.push(Span::default());
+            let (stmt, end) = self.stmt()?;
+            self.spans[index] = Span::new(first.start, end);
+            stmts.push(stmt);
+        }
+    }
+
+    /// A block nested in a statement.
+    fn inner_block(&mut self) -> Result<Vec<Stmt>, StmtsError> {
+        if self.depth == MAX_DEPTH {
+            return Err(ParseError::User { error: TOO_DEEP });
+        }
+        self.depth += 1;
+        let stmts = self.block()?;
+        self.depth -= 1;
+        Ok(stmts)
+    }
+
+    /// A statement, which [`Parser::block`] checked the start of, and its end.
+    fn stmt(&mut self) -> Result<(Stmt, usize), StmtsError> {
+        let first = self.next().unwrap();
+        if self.peek_kind(0) == Some(TokenKind::Assign) {
+            let assign = self.next().unwrap();
+            let (e, end) = self.rest_of_line(assign.end());
+            return Ok((Stmt::Assign(first.text.into(), e), end));
+        }
+        Ok(match first.kind {
+            TokenKind::Keyword(Keyword::Read) => {
+                self.next();
+                let name = self.name()?;
+                match self.peek_kind(0) {
+                    Some(TokenKind::Newline) | None => {}
+                    _ => return Err(self.unexpected(&["newline"])),
+                }
+                (Stmt::Read(name.text.into()), name.end())
+            }
+            TokenKind::Keyword(Keyword::Print) => {
+                let colon = self.next().unwrap();
+                let (e, end) = self.rest_of_line(colon.end());
+                (Stmt::Print(e), end)
+            }
+            TokenKind::Keyword(Keyword::If) => {
+                let cond = self.until_then(first.end())?;
+                let inner = self.inner_block()?;
+                let else_inner = match self.peek_kind(0) {
+                    Some(TokenKind::Keyword(Keyword::Else)) => {
+                        self.next();
+                        Some(self.inner_block()?)
+                    }
+                    Some(TokenKind::Keyword(Keyword::EndIf)) => None,
+                    _ => return Err(self.unexpected(&["statement", "\"ELSE\"", "\"END_IF\""])),
+                };
+                let end = self.end_of_block(Keyword::EndIf, "\"END_IF\"")?;
+                let stmt = match else_inner {
+                    Some(else_inner) => Stmt::IfElse(cond, inner, else_inner),
+                    None => Stmt::If(cond, inner),
+                };
+                (stmt, end)
+            }
+            TokenKind::Keyword(Keyword::While) => {
+                let (cond, _) = self.rest_of_line(first.end());
+                let inner = self.inner_block()?;
+                let end = self.end_of_block(Keyword::EndWhile, "\"END_WHILE\"")?;
+                (Stmt::While(cond, inner), end)
+            }
+            _ => unreachable!("not the start of a statement"),
+        })
+    }
+
+    /// Takes the keyword closing a block, and gives its end.
+    fn end_of_block(&mut self, keyword: Keyword, name: &str) -> Result<usize, StmtsError> {
+        match self.peek_kind(0) {
+            Some(TokenKind::Keyword(x)) if x == keyword => Ok(self.next().unwrap().end()),
+            _ => Err(self.unexpected(&["statement", name])),
+        }
+    }
+
+    /// The expression up to the end of the line, empty if the line ends at
+    /// `start`, and the end of the expression.
+    fn rest_of_line(&mut self, start: usize) -> (RawExpr, usize) {
+        let from = self.pos;
+        while !matches!(self.peek_kind(0), Some(TokenKind::Newline) | None) {
+            self.pos += 1;
+        }
+        self.expr(from, start)
+    }
+
+    /// The condition of an `IF`, which may span lines, up to a `THEN`.
+    fn until_then(&mut self, start: usize) -> Result<RawExpr, StmtsError> {
+        let from = self.pos;
+        loop {
+            match self.peek_kind(0) {
+                Some(TokenKind::Keyword(Keyword::Then)) => break,
+                Some(_) => self.pos += 1,
+                None => return Err(self.unexpected(&["\"THEN\""])),
+            }
+        }
+        let (e, _) = self.expr(from, start);
+        self.next();
+        Ok(e)
+    }
+
+    /// The source of the tokens from `from` up to the current one.
+    fn expr(&self, from: usize, start: usize) -> (RawExpr, usize) {
+        let tokens = &self.tokens[from..self.pos];
+        match (tokens.first(), tokens.last()) {
+            (Some(first), Some(last)) => {
+                let text = self.source[first.start..last.end()].to_string();
+                (RawExpr::at(text, first.start), last.end())
+            }
+            _ => (RawExpr::at(String::new(), start), start),
+        }
+    }
+}
+
+/// Whether the token is a variable name: a word, or a keyword that
+/// expressions don't reserve.
+fn is_name(token: Token) -> bool {
+    match token.kind {
+        TokenKind::Word => true,
+        TokenKind::Keyword(keyword) => expr_parser::Keyword::of(keyword).is_none(),
+        _ => false,
+    }
+}
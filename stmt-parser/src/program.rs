@@ -0,0 +1,334 @@
+use crate::{stmt::fmt_block, Dialect, ParseError, PreOrder, RawExpr, Span, Stmt};
+use expr_parser::Expr;
+use expr_parser::Type;
+use std::fmt;
+use std::sync::OnceLock;
+use vars_parser::{Variable, VariablesParser};
+
+/// A whole source file: the declared variables followed by the statements.
+///
+/// The spans of the statements are kept in pre-order, so the first span
+/// belongs to the first statement, the second one to its first nested
+/// statement if it has any, and so on. Spans don't take part in comparisons.
+#[derive(Debug, Clone)]
+pub struct Program<E = RawExpr> {
+    variables: Vec<Variable>,
+    stmts: Vec<Stmt<E>>,
+    spans: Vec<Span>,
+    /// Built on first use by [`Program::extents`].
+    extents: OnceLock<Vec<Extent>>,
+}
+
+/// Where the statements nested in a statement are, as pre-order indexes.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Extent {
+    /// The first statement of the second block, the `ELSE` branch.
+    pub(crate) second: usize,
+    /// The statement after the last nested one.
+    pub(crate) end: usize,
+}
+
+impl Program {
+    pub fn parse(s: &str) -> Result<Self, ParseError> {
+        let parsed = crate::parser::parse_program(s).map_err(ParseError::Stmts)?;
+        let variables = match parsed.variables {
+            Some(span) => VariablesParser::new()
+                .parse(vars_parser::tokens(&s[span.start()..span.end()]))
+                .map_err(|e| {
+                    let e = e.map_token(|t| t.to_string());
+                    ParseError::Variables(e.map_location(|l| l + span.start()))
+                })?,
+            None => Vec::new(),
+        };
+        Ok(Program::with_spans(variables, parsed.stmts, parsed.spans))
+    }
+
+    /// Parses the expressions of every statement.
+    pub fn parse_exprs(self) -> Result<Program<Expr>, ParseError> {
+        let stmts = self
+            .stmts
+            .into_iter()
+            .map(Stmt::parse_exprs)
+            .collect::<Result<_, _>>()
+            .map_err(ParseError::Expr)?;
+        Ok(Program::with_spans(self.variables, stmts, self.spans))
+    }
+}
+
+impl<E> Program<E> {
+    /// Creates a program that is not backed by source code, so it has no spans.
+    pub fn new(variables: Vec<Variable>, stmts: Vec<Stmt<E>>) -> Self {
+        Program::with_spans(variables, stmts, Vec::new())
+    }
+
+    pub(crate) fn with_spans(
+        variables: Vec<Variable>,
+        stmts: Vec<Stmt<E>>,
+        spans: Vec<Span>,
+    ) -> Self {
+        Program {
+            variables,
+            stmts,
+            spans,
+            extents: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn into_parts(self) -> (Vec<Variable>, Vec<Stmt<E>>, Vec<Span>) {
+        (self.variables, self.stmts, self.spans)
+    }
+
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    pub fn stmts(&self) -> &[Stmt<E>] {
+        &self.stmts
+    }
+
+    /// Gives the statements to edit, dropping the spans, which may not match
+    /// the statements afterwards.
+    pub fn stmts_mut(&mut self) -> &mut Vec<Stmt<E>> {
+        self.spans.clear();
+        self.extents = OnceLock::new();
+        &mut self.stmts
+    }
+
+    /// The spans of every statement, in pre-order.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The extent of every statement, in pre-order.
+    pub(crate) fn extents(&self) -> &[Extent] {
+        fn extents<E>(stmts: &[Stmt<E>], out: &mut Vec<Extent>) {
+            for s in stmts {
+                let at = out.len();
+                out.push(Extent { second: 0, end: 0 });
+                let mut blocks = s.blocks();
+                if let Some(block) = blocks.next() {
+                    extents(block, out);
+                }
+                let second = out.len();
+                if let Some(block) = blocks.next() {
+                    extents(block, out);
+                }
+                out[at] = Extent {
+                    second,
+                    end: out.len(),
+                };
+            }
+        }
+        self.extents.get_or_init(|| {
+            let mut out = Vec::new();
+            extents(&self.stmts, &mut out);
+            out
+        })
+    }
+
+    /// Iterates over every statement, parents first, see [`Program::spans`].
+    pub fn pre_order(&self) -> PreOrder<'_, E> {
+        Stmt::pre_order(&self.stmts)
+    }
+
+    /// Writes the `VARIABLES:` line, grouping neighbouring names of the same type.
+    pub(crate) fn fmt_variables(&self, f: &mut fmt::Formatter, dialect: Dialect) -> fmt::Result {
+        let mut groups: Vec<(Vec<String>, Type)> = Vec::new();
+        for var in &self.variables {
+            match groups.last_mut() {
+                Some((names, t)) if t == var.its_type() => names.push(var.name().to_string()),
+                _ => groups.push((vec![var.name().to_string()], var.its_type().clone())),
+            }
+        }
+        if groups.is_empty() {
+            return Ok(());
+        }
+        let groups: Vec<_> = groups
+            .into_iter()
+            .map(|(names, t)| format!("{}: {}", names.join(", "), t.name(dialect)))
+            .collect();
+        writeln!(
+            f,
+            "{}: {}",
+            crate::dialect::Keywords::of(dialect).variables,
+            groups.join(", ")
+        )
+    }
+}
+
+impl Program<Expr> {
+    /// Iterates over every expression and subexpression of the program,
+    /// statement by statement, parents first.
+    pub fn exprs_pre_order(&self) -> impl Iterator<Item = &Expr> {
+        self.pre_order()
+            .filter_map(Stmt::expr)
+            .flat_map(Expr::pre_order)
+    }
+
+    /// Iterates over every expression and subexpression of the program,
+    /// statement by statement, children first.
+    pub fn exprs_post_order(&self) -> impl Iterator<Item = &Expr> {
+        self.pre_order()
+            .filter_map(Stmt::expr)
+            .flat_map(Expr::post_order)
+    }
+}
+
+impl<E: PartialEq> PartialEq for Program<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.variables == other.variables && self.stmts == other.stmts
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for Program<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_variables(f, Dialect::English)?;
+        fmt_block(f, &self.stmts, 0, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn with_variables() {
+        assert_eq!(
+            Program::parse(
+                r"
+            VARIABLES: a, b: Int,
+                       s: String
+            READ: a
+            b := a * 2
+            "
+            ),
+            Ok(Program::new(
+                vec![
+                    Variable::new("a".into(), Type::Int),
+                    Variable::new("b".into(), Type::Int),
+                    Variable::new("s".into(), Type::String),
+                ],
+                vec![
+                    Stmt::Read("a".into()),
+                    Stmt::Assign("b".into(), "a * 2".into())
+                ]
+            ))
+        );
+    }
+
+    #[test]
+    fn spans() {
+        let source =
+            "a := 1\nWHILE a < 3\n  IF a = 2 THEN\n    PRINT: a\n  FI\n  a := a + 1\nDONE\n";
+        let program = Program::parse(source).unwrap();
+        let spans: Vec<_> = program
+            .spans()
+            .iter()
+            .map(|s| &source[s.start()..s.end()])
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                "a := 1",
+                "WHILE a < 3\n  IF a = 2 THEN\n    PRINT: a\n  FI\n  a := a + 1\nDONE",
+                "IF a = 2 THEN\n    PRINT: a\n  FI",
+                "PRINT: a",
+                "a := a + 1",
+            ]
+        );
+    }
+
+    #[test]
+    fn without_variables() {
+        assert_eq!(
+            Program::parse("PRINT: 1"),
+            Ok(Program::new(vec![], vec![Stmt::Print("1".into())]))
+        );
+    }
+
+    #[test]
+    fn unknown_type() {
+        assert!(matches!(
+            Program::parse("VARIABLES: a: Foo\nPRINT: a"),
+            Err(ParseError::Variables(_))
+        ));
+    }
+
+    #[test]
+    fn expr_error_locations() {
+        let source = "a := 1\nb := 2\nPRINT: a +* b\nIF THEN\nFI";
+        let program = Program::parse(source).unwrap();
+        match program.clone().parse_exprs() {
+            Err(ParseError::Expr(lalrpop_util::ParseError::UnrecognizedToken {
+                token: (24, t, 25),
+                ..
+            })) => assert_eq!(t, "*"),
+            x => panic!("{:?}", x),
+        }
+        match Program::parse("IF THEN\nFI").unwrap().parse_exprs() {
+            Err(ParseError::Expr(lalrpop_util::ParseError::UnrecognizedEOF {
+                location: 2,
+                ..
+            })) => {}
+            x => panic!("{:?}", x),
+        }
+    }
+
+    #[test]
+    fn too_deep() {
+        let nested = |depth| {
+            format!(
+                "{}a := 1\n{}",
+                "IF a THEN\n".repeat(depth),
+                "FI\n".repeat(depth)
+            )
+        };
+        assert!(Program::parse(&nested(crate::MAX_DEPTH)).is_ok());
+        for depth in [crate::MAX_DEPTH + 1, 50_000] {
+            assert_eq!(
+                Program::parse(&nested(depth)),
+                Err(ParseError::Stmts(lalrpop_util::ParseError::User {
+                    error: "the statements are nested too deeply"
+                }))
+            );
+        }
+        let source = format!("PRINT: {}1{}", "(".repeat(50_000), ")".repeat(50_000));
+        assert!(matches!(
+            Program::parse(&source).unwrap().parse_exprs(),
+            Err(ParseError::Expr(lalrpop_util::ParseError::User { .. }))
+        ));
+    }
+
+    #[test]
+    fn extents() {
+        let source = "IF a THEN\n  b := 1\nELSE\n  WHILE c\n    d := 2\n  DONE\nFI\ne := 3";
+        let mut program = Program::parse(source).unwrap();
+        let extents: Vec<_> = program
+            .extents()
+            .iter()
+            .map(|x| (x.second, x.end))
+            .collect();
+        assert_eq!(extents, vec![(2, 4), (2, 2), (4, 4), (4, 4), (5, 5)]);
+        program.stmts_mut().remove(0);
+        let extents: Vec<_> = program
+            .extents()
+            .iter()
+            .map(|x| (x.second, x.end))
+            .collect();
+        assert_eq!(extents, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn editing_drops_spans() {
+        let mut program = Program::parse("a := 1\nPRINT: a").unwrap();
+        program.stmts_mut().remove(0);
+        assert_eq!(program.spans(), &[]);
+    }
+
+    #[test]
+    fn display() {
+        let source = "VARIABLES: a, b: Int, s: String\nREAD: a\nPRINT: a + b\n";
+        let program = Program::parse(source).unwrap();
+        assert_eq!(program.to_string(), source);
+    }
+}
@@ -1,7 +1,56 @@
-use derive_more::{Constructor, Display, From};
+use crate::ExprError;
+use derive_more::Display;
+use expr_parser::Expr;
 
-#[derive(Debug, PartialEq, Clone, From, Constructor, Display)]
-pub struct RawExpr(String);
+/// The source of an expression, to be parsed with [`RawExpr::parse`].
+///
+/// Expressions of a parsed program remember where they start in its source,
+/// so their errors point into the program. The start doesn't take part in
+/// comparisons.
+#[derive(Debug, Clone, Display)]
+#[display(fmt = "{}", text)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct RawExpr {
+    text: String,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    start: usize,
+}
+
+impl RawExpr {
+    pub fn new(text: String) -> Self {
+        RawExpr::at(text, 0)
+    }
+
+    /// An expression starting at the byte offset `start` of the source.
+    pub(crate) fn at(text: String, start: usize) -> Self {
+        RawExpr { text, start }
+    }
+
+    /// Parses the expression, with the locations of errors in the source it
+    /// was taken from.
+    pub fn parse(&self) -> Result<Expr, ExprError> {
+        Expr::parse(&self.text).map_err(|e| {
+            e.map_token(|t| t.to_string())
+                .map_location(|l| l + self.start)
+        })
+    }
+}
+
+impl PartialEq for RawExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.text == other.text
+    }
+}
+
+impl From<String> for RawExpr {
+    fn from(text: String) -> Self {
+        RawExpr::new(text)
+    }
+}
 
 impl From<&str> for RawExpr {
     fn from(x: &str) -> Self {
@@ -0,0 +1,279 @@
+//! Updating a [`SyntaxTree`] after an edit of its source, reparsing only the
+//! statement around the edit.
+
+use crate::cst::{GreenElement, GreenNode, NodeKind, SyntaxNode, SyntaxTree};
+use crate::Span;
+
+/// A change of the source: the text in `span` is replaced.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TextEdit {
+    span: Span,
+    replacement: String,
+}
+
+impl TextEdit {
+    pub fn new(span: Span, replacement: impl Into<String>) -> Self {
+        TextEdit {
+            span,
+            replacement: replacement.into(),
+        }
+    }
+
+    pub fn insert(offset: usize, text: impl Into<String>) -> Self {
+        TextEdit::new(Span::new(offset, offset), text)
+    }
+
+    pub fn delete(span: Span) -> Self {
+        TextEdit::new(span, "")
+    }
+
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+
+    /// The source with the edit applied.
+    ///
+    /// Panics if the span is out of `source` or splits a character, like
+    /// [`String::replace_range`].
+    pub fn apply(&self, source: &str) -> String {
+        let mut s = source.to_string();
+        s.replace_range(self.span.start()..self.span.end(), &self.replacement);
+        s
+    }
+
+    /// How much longer the source gets.
+    fn delta(&self) -> isize {
+        self.replacement.len() as isize - (self.span.end() - self.span.start()) as isize
+    }
+}
+
+impl SyntaxTree {
+    /// The tree of the source with `edit` applied.
+    ///
+    /// The innermost statement around the edit is parsed again, and if it
+    /// still ends where it did, the rest of the tree is reused. Otherwise the
+    /// enclosing statements are tried, up to parsing the whole source. Either
+    /// way, the result is the tree [`SyntaxTree::parse`] builds from the new
+    /// source.
+    pub fn edit(&self, edit: &TextEdit) -> SyntaxTree {
+        let source = edit.apply(self.source());
+        match reparse(self.root(), edit, &source) {
+            Some(green) => SyntaxTree::new(source, green),
+            None => SyntaxTree::parse(&source),
+        }
+    }
+}
+
+/// The new root, if a statement around the edit could be reparsed alone.
+fn reparse(root: SyntaxNode, edit: &TextEdit, source: &str) -> Option<GreenNode> {
+    // The nodes containing the edit, outermost first, with the index of the
+    // next one among the children.
+    let mut path = vec![(root, 0)];
+    while let Some((index, child)) = path.last().and_then(|&(node, _)| {
+        node.children()
+            .enumerate()
+            .filter_map(|(i, child)| Some((i, child.into_node()?)))
+            .find(|(_, child)| contains(child.span(), edit.span()))
+    }) {
+        path.last_mut().unwrap().1 = index;
+        path.push((child, 0));
+    }
+    for depth in (0..path.len()).rev() {
+        let node = path[depth].0;
+        if !is_stmt(node.kind()) || has_open_literal_before(&path[..depth]) {
+            continue;
+        }
+        let blocks = path[..depth]
+            .iter()
+            .filter(|(node, _)| matches!(node.kind(), NodeKind::If | NodeKind::While))
+            .count();
+        if let Some(mut green) = reparse_stmt(node, edit, source, blocks) {
+            for &(ancestor, index) in path[..depth].iter().rev() {
+                green = ancestor.green().with_child(index, green);
+            }
+            return Some(green);
+        }
+    }
+    None
+}
+
+/// The new node of the statement nested in `blocks` blocks, if it can be
+/// reparsed alone: the edit leaves its first token alone, which the parser
+/// looked at to find the statement, and it still ends where the edit moved its end.
+fn reparse_stmt(
+    node: SyntaxNode,
+    edit: &TextEdit,
+    source: &str,
+    blocks: usize,
+) -> Option<GreenNode> {
+    let first = node.children().next()?.into_token()?;
+    if edit.span().start() <= first.span().end() {
+        return None;
+    }
+    let green = crate::cst_parser::parse_stmt(source, node.span().start(), blocks)?;
+    let len = node.span().end() - node.span().start();
+    match green.len() as isize == len as isize + edit.delta() {
+        true => Some(green),
+        false => None,
+    }
+}
+
+/// Whether a literal missing its closing quote is before the child of the
+/// last node of `path`, whose length depends on the text after it.
+fn has_open_literal_before(path: &[(SyntaxNode, usize)]) -> bool {
+    path.iter().any(|&(node, index)| {
+        node.green().children()[..index]
+            .iter()
+            .any(GreenElement::has_open_literal)
+    })
+}
+
+fn contains(span: Span, edit: Span) -> bool {
+    span.start() <= edit.start() && edit.end() <= span.end()
+}
+
+fn is_stmt(kind: NodeKind) -> bool {
+    use NodeKind::*;
+    matches!(kind, Assign | Read | Print | If | While)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expr_parser::Env;
+    use std::sync::Arc;
+
+    const SOURCES: [&str; 4] = [
+        "VARIABLES: a, b: Int,\n  s: String\nREAD: a\nb := a * 2 // twice\nPRINT: s[1:b]\n",
+        "a := 1\nWHILE a < 3\n  IF a = 2\n     AND TRUE THEN\n    PRINT: |a - 5|\n  ELSE\n    a := (a + 1) * 2\n  END_IF\nEND_WHILE\n",
+        "HA x > 1 AKKOR\n  KI: \"x\\\"y\"\nHA_VÉGE a := 'b'\r\nCIKLUS AMÍG NEM x\n  BE: x\nCIKLUS_VÉGE",
+        "IF a\n  x := \nEND_WHILE\nPRINT: (1 +\nb := 2 2\nREAD: 1",
+    ];
+
+    const FRAGMENTS: [&str; 24] = [
+        "",
+        " ",
+        "\n",
+        "1",
+        "x",
+        "+",
+        "- 2",
+        "*",
+        "(",
+        ")",
+        "[",
+        ":",
+        "]",
+        "|",
+        "\"",
+        "'",
+        ":=",
+        "// c",
+        "IF b THEN\n",
+        "END_IF",
+        "ELSE\n",
+        "WHILE x\n",
+        "END_WHILE\n",
+        "PRINT: ",
+    ];
+
+    fn below(env: &Env, n: usize) -> usize {
+        (env.random() * n as f64) as usize
+    }
+
+    fn floor_char_boundary(s: &str, mut offset: usize) -> usize {
+        while !s.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    /// An edit of `source`, drawn from the seeded random numbers of `env`.
+    fn random_edit(env: &Env, source: &str) -> TextEdit {
+        let start = floor_char_boundary(source, below(env, source.len() + 1));
+        let end = floor_char_boundary(source, (start + below(env, 6)).min(source.len()));
+        let replacement = match below(env, 3) {
+            0 => {
+                let from = floor_char_boundary(source, below(env, source.len() + 1));
+                let to = floor_char_boundary(source, (from + below(env, 12)).min(source.len()));
+                &source[from..to]
+            }
+            _ => FRAGMENTS[below(env, FRAGMENTS.len())],
+        };
+        TextEdit::new(Span::new(start, end), replacement)
+    }
+
+    #[test]
+    fn matches_full_parse() {
+        let env = Env::with_seed(47);
+        for source in &SOURCES {
+            for _ in 0..200 {
+                let mut tree = SyntaxTree::parse(source);
+                for _ in 0..5 {
+                    let edit = random_edit(&env, tree.source());
+                    let edited = tree.edit(&edit);
+                    let parsed = SyntaxTree::parse(&edit.apply(tree.source()));
+                    assert_eq!(edited, parsed, "{:?} on {:?}", edit, tree.source());
+                    tree = edited;
+                }
+            }
+        }
+    }
+
+    fn stmt_nodes(tree: &SyntaxTree) -> Vec<Arc<GreenNode>> {
+        let block = tree.root().child_nodes().last().unwrap();
+        block
+            .green()
+            .children()
+            .iter()
+            .filter_map(|child| match child {
+                GreenElement::Node(node) => Some(node.clone()),
+                GreenElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reuses_untouched_statements() {
+        let tree = SyntaxTree::parse("a := 1\nIF a THEN\n  PRINT: a\nEND_IF\nb := 2\n");
+        let edited = tree.edit(&TextEdit::insert(27, " + 1"));
+        assert_eq!(
+            edited.source(),
+            "a := 1\nIF a THEN\n  PRINT: a + 1\nEND_IF\nb := 2\n"
+        );
+        let (old, new) = (stmt_nodes(&tree), stmt_nodes(&edited));
+        assert!(Arc::ptr_eq(&old[0], &new[0]));
+        assert!(!Arc::ptr_eq(&old[1], &new[1]));
+        assert!(Arc::ptr_eq(&old[2], &new[2]));
+        assert_eq!(edited, SyntaxTree::parse(edited.source()));
+    }
+
+    #[test]
+    fn keeps_the_depth_of_the_statement() {
+        let (open, close) = ("IF a THEN\n", "END_IF\n");
+        let depth = crate::MAX_DEPTH;
+        let source = format!("{}a := 1\n{}", open.repeat(depth), close.repeat(depth));
+        let tree = SyntaxTree::parse(&source);
+        // The new `WHILE` would be one block too deep.
+        let at = source.find(" 1").unwrap() + 2;
+        let edited = tree.edit(&TextEdit::insert(at, "\nWHILE a\nEND_WHILE"));
+        assert_eq!(edited, SyntaxTree::parse(edited.source()));
+        assert_eq!(edited.errors().len(), 1);
+    }
+
+    #[test]
+    fn falls_back_to_the_enclosing_statement() {
+        let tree = SyntaxTree::parse("a := 1\nIF a THEN\n  PRINT: a\nEND_IF\nb := 2\n");
+        // A new statement after `PRINT: a`, which the IF is parsed again for.
+        let edited = tree.edit(&TextEdit::insert(27, "\n  READ: c"));
+        assert_eq!(edited, SyntaxTree::parse(edited.source()));
+        let (old, new) = (stmt_nodes(&tree), stmt_nodes(&edited));
+        assert!(Arc::ptr_eq(&old[0], &new[0]));
+        assert!(!Arc::ptr_eq(&old[1], &new[1]));
+        assert!(Arc::ptr_eq(&old[2], &new[2]));
+    }
+}
@@ -0,0 +1,195 @@
+//! The JSON representation of statements and programs.
+//!
+//! Every statement is an object tagged with its `kind`:
+//!
+//! ```json
+//! {"kind": "Assign", "var": "a", "expr": "a + 1"}
+//! {"kind": "Print", "expr": "a"}
+//! {"kind": "Read", "var": "a"}
+//! {"kind": "If", "cond": "a > 1", "body": [<stmt>, ...]}
+//! {"kind": "IfElse", "cond": "a > 1", "body": [<stmt>, ...], "else_body": [<stmt>, ...]}
+//! {"kind": "While", "cond": "a > 1", "body": [<stmt>, ...]}
+//! ```
+//!
+//! The expressions are either their source text, as above, or [`Expr`](expr_parser::Expr)
+//! trees once they are parsed with [`Program::parse_exprs`].
+//!
+//! A program is `{"variables": [{"name": "a", "type": "Int"}, ...], "stmts": [...],
+//! "spans": [{"start": 0, "end": 6}, ...]}` where the spans follow the statements in pre-order.
+
+use crate::{Program, Span, Stmt, VarName};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use vars_parser::Variable;
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind")]
+enum StmtRepr<N, E, B> {
+    Assign { var: N, expr: E },
+    Print { expr: E },
+    Read { var: N },
+    If { cond: E, body: B },
+    IfElse { cond: E, body: B, else_body: B },
+    While { cond: E, body: B },
+}
+
+impl<E: Serialize> Serialize for Stmt<E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr: StmtRepr<&VarName, &E, &[Stmt<E>]> = match self {
+            Stmt::Assign(var, expr) => StmtRepr::Assign { var, expr },
+            Stmt::Print(expr) => StmtRepr::Print { expr },
+            Stmt::Read(var) => StmtRepr::Read { var },
+            Stmt::If(cond, body) => StmtRepr::If { cond, body },
+            Stmt::IfElse(cond, body, else_body) => StmtRepr::IfElse {
+                cond,
+                body,
+                else_body,
+            },
+            Stmt::While(cond, body) => StmtRepr::While { cond, body },
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de, E: Deserialize<'de>> Deserialize<'de> for Stmt<E> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(
+            match StmtRepr::<VarName, E, Vec<Stmt<E>>>::deserialize(deserializer)? {
+                StmtRepr::Assign { var, expr } => Stmt::Assign(var, expr),
+                StmtRepr::Print { expr } => Stmt::Print(expr),
+                StmtRepr::Read { var } => Stmt::Read(var),
+                StmtRepr::If { cond, body } => Stmt::If(cond, body),
+                StmtRepr::IfElse {
+                    cond,
+                    body,
+                    else_body,
+                } => Stmt::IfElse(cond, body, else_body),
+                StmtRepr::While { cond, body } => Stmt::While(cond, body),
+            },
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProgramRepr<V, S, P> {
+    variables: V,
+    stmts: S,
+    spans: P,
+}
+
+impl<E: Serialize> Serialize for Program<E> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ProgramRepr {
+            variables: self.variables(),
+            stmts: self.stmts(),
+            spans: self.spans(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, E: Deserialize<'de>> Deserialize<'de> for Program<E> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let x = ProgramRepr::<Vec<Variable>, Vec<Stmt<E>>, Vec<Span>>::deserialize(deserializer)?;
+        Ok(Program::with_spans(x.variables, x.stmts, x.spans))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn validator() -> jsonschema::Validator {
+        let schema = serde_json::from_str(include_str!("../../schema/program.schema.json"));
+        jsonschema::validator_for(&schema.unwrap()).unwrap()
+    }
+
+    /// Checks the JSON against `schema/program.schema.json`.
+    fn assert_valid(json: &serde_json::Value) {
+        let validator = validator();
+        let errors: Vec<_> = validator.iter_errors(json).map(|e| e.to_string()).collect();
+        assert!(errors.is_empty(), "{:?} in {}", errors, json);
+    }
+
+    #[test]
+    fn program() {
+        let source = "VARIABLES: a: Int\nREAD: a\nIF a > 1 THEN\n  PRINT: a\nELSE\nEND_IF";
+        let program = Program::parse(source).unwrap();
+        let json = json!({
+            "variables": [{"name": "a", "type": "Int"}],
+            "stmts": [
+                {"kind": "Read", "var": "a"},
+                {
+                    "kind": "IfElse",
+                    "cond": "a > 1",
+                    "body": [{"kind": "Print", "expr": "a"}],
+                    "else_body": [],
+                },
+            ],
+            "spans": [
+                {"start": 18, "end": 25},
+                {"start": 26, "end": 62},
+                {"start": 42, "end": 50},
+            ],
+        });
+        assert_eq!(serde_json::to_value(&program).unwrap(), json);
+        assert_valid(&json);
+        let back: Program = serde_json::from_value(json).unwrap();
+        assert_eq!(back, program);
+        assert_eq!(back.spans(), program.spans());
+    }
+
+    #[test]
+    fn parsed_exprs() {
+        let program = Program::parse("PRINT: -a").unwrap().parse_exprs().unwrap();
+        let json = json!({
+            "variables": [],
+            "stmts": [{
+                "kind": "Print",
+                "expr": {
+                    "kind": "Unary",
+                    "op": "Neg",
+                    "operand": {"kind": "Value", "type": "VarName", "value": "a"},
+                },
+            }],
+            "spans": [{"start": 0, "end": 9}],
+        });
+        assert_eq!(serde_json::to_value(&program).unwrap(), json);
+        assert_valid(&json);
+        let back: Program<expr_parser::Expr> = serde_json::from_value(json).unwrap();
+        assert_eq!(back, program);
+    }
+
+    #[test]
+    fn schema() {
+        let source = r#"
+        VARIABLES: a: Int, b: Bool, c: Char, s: String, x: Float
+        READ: a
+        x := |a - 1.5| ^ 2
+        WHILE NOT b AND s @ "q" OR c = 'c'
+            s := s[0:a] + s[1]
+        END_WHILE
+        IF a > 1 THEN
+            PRINT: (SIN x)
+        ELSE
+            IF TRUE THEN
+            END_IF
+        END_IF
+        "#;
+        let program = Program::parse(source).unwrap();
+        assert_valid(&serde_json::to_value(&program).unwrap());
+        let program = program.parse_exprs().unwrap();
+        let json = serde_json::to_value(&program).unwrap();
+        assert_valid(&json);
+        assert_eq!(
+            serde_json::from_value::<Program<_>>(json).ok(),
+            Some(program)
+        );
+        let validator = validator();
+        let print = |expr| json!({"variables": [], "stmts": [{"kind": "Print", "expr": expr}], "spans": []});
+        assert!(!validator.is_valid(&print(
+            json!({"kind": "Value", "type": "Float", "value": null})
+        )));
+        assert!(!validator.is_valid(&print(json!({"kind": "Loop"}))));
+    }
+}
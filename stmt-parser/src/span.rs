@@ -0,0 +1,20 @@
+use derive_more::{Constructor, Display};
+
+/// A byte range of the source code.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default, Constructor, Display)]
+#[display(fmt = "{}..{}", start, end)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    start: usize,
+    end: usize,
+}
+
+impl Span {
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
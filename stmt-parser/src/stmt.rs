@@ -1,62 +1,158 @@
-use crate::{Parser, RawExpr, VarName};
+use crate::{dialect::Keywords, Dialect, ExprError, RawExpr, StmtsError, VarName};
+use expr_parser::Expr;
+use std::fmt;
 
+/// A statement, generic over the representation of its expressions.
+///
+/// The parser produces statements holding the source text of the expressions,
+/// use [`Stmt::parse_exprs`] to parse them into [`Expr`] trees.
 #[derive(Debug, PartialEq, Clone)]
-pub enum Stmt {
-    Assign(VarName, RawExpr),
-    Print(RawExpr),
+pub enum Stmt<E = RawExpr> {
+    Assign(VarName, E),
+    Print(E),
     Read(VarName),
-    If(RawExpr, Vec<Stmt>),
-    IfElse(RawExpr, Vec<Stmt>, Vec<Stmt>),
-    While(RawExpr, Vec<Stmt>),
+    If(E, Vec<Stmt<E>>),
+    IfElse(E, Vec<Stmt<E>>, Vec<Stmt<E>>),
+    While(E, Vec<Stmt<E>>),
 }
 
-use pest::iterators::Pair as PairTmp;
-type Pair<'a> = PairTmp<'a, crate::Rule>;
-type PError = pest::error::Error<crate::Rule>;
-
 impl Stmt {
-    pub fn parse(s: &str) -> Result<Vec<Self>, PError> {
-        let pair = crate::StmtsParser::parse(crate::Rule::input, s)?
-            .next()
-            .unwrap();
-        try_into_stmts(pair)
-    }
-}
-
-fn try_into_stmt(pair: Pair) -> Result<Stmt, PError> {
-    use crate::Rule as R;
-    let rule = pair.as_rule();
-    let mut pairs = pair.into_inner();
-    Ok(match rule {
-        R::stmt => try_into_stmt(pairs.next().unwrap())?,
-        R::read => Stmt::Read(pairs.as_str().trim().into()),
-        R::print => Stmt::Print(pairs.as_str().trim().into()),
-        R::assign => {
-            let var_name = pairs.next().unwrap().as_str().trim();
-            let expr = pairs.next().unwrap().as_str().trim();
-            Stmt::Assign(var_name.into(), expr.into())
+    pub fn parse(s: &str) -> Result<Vec<Self>, StmtsError> {
+        crate::parser::parse_stmts(s)
+    }
+
+    pub fn parse_exprs(self) -> Result<Stmt<Expr>, ExprError> {
+        self.try_map(&mut |e| e.parse())
+    }
+}
+
+impl<E> Stmt<E> {
+    /// The expression the statement evaluates itself, not counting nested statements.
+    pub fn expr(&self) -> Option<&E> {
+        match self {
+            Stmt::Assign(_, e) | Stmt::Print(e) => Some(e),
+            Stmt::If(e, _) | Stmt::IfElse(e, _, _) | Stmt::While(e, _) => Some(e),
+            Stmt::Read(_) => None,
+        }
+    }
+
+    /// The directly nested blocks of the statement.
+    pub fn blocks(&self) -> impl DoubleEndedIterator<Item = &[Stmt<E>]> {
+        let blocks = match self {
+            Stmt::If(_, inner) | Stmt::While(_, inner) => [Some(&inner[..]), None],
+            Stmt::IfElse(_, inner, else_inner) => [Some(&inner[..]), Some(&else_inner[..])],
+            Stmt::Assign(..) | Stmt::Print(_) | Stmt::Read(_) => [None, None],
+        };
+        IntoIterator::into_iter(blocks).flatten()
+    }
+
+    /// Iterates over `stmts` and every statement nested in them, parents first.
+    ///
+    /// This is the order [`Program::spans`](crate::Program::spans) follows.
+    pub fn pre_order(stmts: &[Stmt<E>]) -> PreOrder<'_, E> {
+        PreOrder {
+            stack: stmts.iter().rev().collect(),
+        }
+    }
+
+    /// Converts every expression with `f`, keeping the structure of the statement.
+    pub fn try_map<T, Err>(self, f: &mut impl FnMut(E) -> Result<T, Err>) -> Result<Stmt<T>, Err> {
+        fn block<E, T, Err>(
+            stmts: Vec<Stmt<E>>,
+            f: &mut impl FnMut(E) -> Result<T, Err>,
+        ) -> Result<Vec<Stmt<T>>, Err> {
+            stmts.into_iter().map(|s| s.try_map(f)).collect()
         }
-        R::if_stmt => {
-            let expr = pairs.next().unwrap().as_str().trim();
-            let inner = try_into_stmts(pairs.next().unwrap()).unwrap();
-            Stmt::If(expr.into(), inner)
+        Ok(match self {
+            Stmt::Assign(var_name, e) => Stmt::Assign(var_name, f(e)?),
+            Stmt::Print(e) => Stmt::Print(f(e)?),
+            Stmt::Read(var_name) => Stmt::Read(var_name),
+            Stmt::If(cond, inner) => Stmt::If(f(cond)?, block(inner, f)?),
+            Stmt::IfElse(cond, inner, else_inner) => {
+                let cond = f(cond)?;
+                Stmt::IfElse(cond, block(inner, f)?, block(else_inner, f)?)
+            }
+            Stmt::While(cond, inner) => Stmt::While(f(cond)?, block(inner, f)?),
+        })
+    }
+}
+
+pub struct PreOrder<'a, E> {
+    stack: Vec<&'a Stmt<E>>,
+}
+
+impl<'a, E> Iterator for PreOrder<'a, E> {
+    type Item = &'a Stmt<E>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let stmt = self.stack.pop()?;
+        for block in stmt.blocks().rev() {
+            self.stack.extend(block.iter().rev());
         }
-        R::while_stmt => {
-            let expr = pairs.next().unwrap().as_str().trim();
-            let inner = try_into_stmts(pairs.next().unwrap());
-            Stmt::While(expr.into(), inner.unwrap())
+        Some(stmt)
+    }
+}
+
+const INDENT: usize = 4;
+
+impl<E: fmt::Display> Stmt<E> {
+    /// Formats the statement with the keywords of `dialect`. Unless `dialect`
+    /// is given, the expressions are copied verbatim next to English keywords.
+    pub(crate) fn fmt_indented(
+        &self,
+        f: &mut fmt::Formatter,
+        depth: usize,
+        dialect: Option<Dialect>,
+    ) -> fmt::Result {
+        let kw = Keywords::of(dialect.unwrap_or(Dialect::English));
+        let expr = |e: &E| match dialect {
+            Some(dialect) => expr_parser::translate(&e.to_string(), dialect),
+            None => e.to_string(),
+        };
+        let pad = depth * INDENT;
+        write!(f, "{:pad$}", "", pad = pad)?;
+        match self {
+            Stmt::Assign(var_name, e) => write!(f, "{} := {}", var_name, expr(e)),
+            Stmt::Print(e) => write!(f, "{}: {}", kw.print, expr(e)),
+            Stmt::Read(var_name) => write!(f, "{}: {}", kw.read, var_name),
+            Stmt::If(cond, inner) => {
+                writeln!(f, "{} {} {}", kw.if_head, expr(cond), kw.if_then)?;
+                fmt_block(f, inner, depth + 1, dialect)?;
+                write!(f, "{:pad$}{}", "", kw.if_end, pad = pad)
+            }
+            Stmt::IfElse(cond, inner, else_inner) => {
+                writeln!(f, "{} {} {}", kw.if_head, expr(cond), kw.if_then)?;
+                fmt_block(f, inner, depth + 1, dialect)?;
+                writeln!(f, "{:pad$}{}", "", kw.else_head, pad = pad)?;
+                fmt_block(f, else_inner, depth + 1, dialect)?;
+                write!(f, "{:pad$}{}", "", kw.if_end, pad = pad)
+            }
+            Stmt::While(cond, inner) => {
+                writeln!(f, "{} {}", kw.while_head, expr(cond))?;
+                fmt_block(f, inner, depth + 1, dialect)?;
+                write!(f, "{:pad$}{}", "", kw.while_end, pad = pad)
+            }
         }
-        _ => unimplemented!(),
-    })
+    }
+}
+
+pub(crate) fn fmt_block<E: fmt::Display>(
+    f: &mut fmt::Formatter,
+    stmts: &[Stmt<E>],
+    depth: usize,
+    dialect: Option<Dialect>,
+) -> fmt::Result {
+    for stmt in stmts {
+        stmt.fmt_indented(f, depth, dialect)?;
+        writeln!(f)?;
+    }
+    Ok(())
 }
 
-fn try_into_stmts(pair: Pair) -> Result<Vec<Stmt>, PError> {
-    assert_eq!(pair.as_rule(), crate::Rule::stmts);
-    let mut v = Vec::new();
-    for pair in pair.into_inner() {
-        dbg!(pair.as_rule());
-        let stmt = try_into_stmt(pair)?;
-        v.push(stmt);
+/// Formats the statement as planggy source, nested blocks are indented
+/// by four spaces. The output parses back to the same statement.
+impl<E: fmt::Display> fmt::Display for Stmt<E> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0, None)
     }
-    Ok(v)
 }
@@ -0,0 +1,275 @@
+use crate::{Program, Span, Stmt, VarName};
+use derive_more::{Display, From};
+use expr_parser::{Expr, FoldWarning, Type, TypeEnv, TypeError, TypedExpr};
+use std::fmt;
+
+/// A problem a static pass, like the type checker, found in a statement.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    span: Option<Span>,
+    kind: DiagnosticKind,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(span: Option<Span>, kind: DiagnosticKind) -> Self {
+        Diagnostic { span, kind }
+    }
+
+    /// The span of the statement, missing if the program was not parsed from source code.
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    pub fn kind(&self) -> &DiagnosticKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span {
+            Some(span) => write!(f, "{} at {}", self.kind, span),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Display, From)]
+pub enum DiagnosticKind {
+    Expr(TypeError),
+    #[display(
+        fmt = "{} of type {} can't hold a value of type {}",
+        var,
+        expected,
+        found
+    )]
+    Assign {
+        var: VarName,
+        expected: Type,
+        found: Type,
+    },
+    #[display(fmt = "the condition is of type {} instead of Bool", _0)]
+    NotABool(Type),
+    #[display(fmt = "variable `{}` is not declared", _0)]
+    #[from(ignore)]
+    Undeclared(VarName),
+    NotFolded(FoldWarning),
+}
+
+impl Program<Expr> {
+    /// Infers the type of every expression against the declared variables.
+    ///
+    /// Checks that assignments match the declared type of their target, where
+    /// `Int`s widen into `Float`s, that conditions are `Bool`s, and that every
+    /// variable written by `:=` or `READ` is declared.
+    pub fn type_check(self) -> (Program<TypedExpr>, Vec<Diagnostic>) {
+        let (variables, stmts, spans) = self.into_parts();
+        let mut checker = Checker {
+            env: variables
+                .iter()
+                .map(|var| (var.name().clone(), var.its_type().clone()))
+                .collect(),
+            spans: &spans,
+            at: 0,
+            diagnostics: Vec::new(),
+        };
+        let stmts = checker.block(stmts);
+        let diagnostics = checker.diagnostics;
+        (Program::with_spans(variables, stmts, spans), diagnostics)
+    }
+}
+
+struct Checker<'a> {
+    env: TypeEnv,
+    spans: &'a [Span],
+    /// The pre-order index of the statement being checked.
+    at: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker<'_> {
+    fn block(&mut self, stmts: Vec<Stmt<Expr>>) -> Vec<Stmt<TypedExpr>> {
+        stmts.into_iter().map(|s| self.stmt(s)).collect()
+    }
+
+    fn stmt(&mut self, s: Stmt<Expr>) -> Stmt<TypedExpr> {
+        let span = self.spans.get(self.at).copied();
+        self.at += 1;
+        match s {
+            Stmt::Assign(var, e) => {
+                let e = self.expr(e, span);
+                match (self.env.get(&var), e.ty()) {
+                    (None, _) => self.report(span, DiagnosticKind::Undeclared(var.clone())),
+                    (Some(Type::Float), Some(Type::Int)) => {}
+                    (Some(expected), Some(found)) if expected != found => {
+                        let kind = DiagnosticKind::Assign {
+                            var: var.clone(),
+                            expected: expected.clone(),
+                            found: found.clone(),
+                        };
+                        self.report(span, kind)
+                    }
+                    _ => {}
+                }
+                Stmt::Assign(var, e)
+            }
+            Stmt::Print(e) => Stmt::Print(self.expr(e, span)),
+            Stmt::Read(var) => {
+                if !self.env.contains_key(&var) {
+                    self.report(span, DiagnosticKind::Undeclared(var.clone()))
+                }
+                Stmt::Read(var)
+            }
+            Stmt::If(cond, inner) => {
+                let cond = self.cond(cond, span);
+                Stmt::If(cond, self.block(inner))
+            }
+            Stmt::IfElse(cond, inner, else_inner) => {
+                let cond = self.cond(cond, span);
+                let inner = self.block(inner);
+                Stmt::IfElse(cond, inner, self.block(else_inner))
+            }
+            Stmt::While(cond, inner) => {
+                let cond = self.cond(cond, span);
+                Stmt::While(cond, self.block(inner))
+            }
+        }
+    }
+
+    fn report(&mut self, span: Option<Span>, kind: DiagnosticKind) {
+        self.diagnostics.push(Diagnostic::new(span, kind))
+    }
+
+    fn expr(&mut self, e: Expr, span: Option<Span>) -> TypedExpr {
+        let mut errors = Vec::new();
+        let typed = TypedExpr::check(e, &self.env, &mut errors);
+        for error in errors {
+            self.report(span, error.into())
+        }
+        typed
+    }
+
+    fn cond(&mut self, e: Expr, span: Option<Span>) -> TypedExpr {
+        let cond = self.expr(e, span);
+        match cond.ty() {
+            Some(Type::Bool) | None => {}
+            Some(t) => {
+                let kind = DiagnosticKind::NotABool(t.clone());
+                self.report(span, kind)
+            }
+        }
+        cond
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> (Program<TypedExpr>, Vec<(String, &str)>) {
+        let program = Program::parse(source).unwrap().parse_exprs().unwrap();
+        let (program, diagnostics) = program.type_check();
+        let diagnostics = diagnostics
+            .into_iter()
+            .map(|d| {
+                let span = d.span().unwrap();
+                (d.kind().to_string(), &source[span.start()..span.end()])
+            })
+            .collect();
+        (program, diagnostics)
+    }
+
+    #[test]
+    fn well_typed() {
+        let (program, diagnostics) = check(
+            r#"
+            VARIABLES: n, i: Int, x: Float, s: String
+            READ: n
+            WHILE i < n
+                x := x + i / 2
+                i := i + 1
+            DONE
+            IF s @ 'a' THEN
+                PRINT: s[0]
+            ELSE
+                s := s + "a"
+            FI
+            "#,
+        );
+        assert_eq!(diagnostics, vec![]);
+        let types: Vec<_> = program
+            .pre_order()
+            .filter_map(Stmt::expr)
+            .map(|e| e.ty().unwrap().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["Bool", "Float", "Int", "Bool", "Char", "String"]
+        );
+    }
+
+    #[test]
+    fn widens_int() {
+        let (_, diagnostics) = check("VARIABLES: x: Float\nx := 1");
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn power_of_ints_is_an_int() {
+        let source = "VARIABLES: x, y: Int\ny := 0 - 1\nx := 2 ^ y";
+        let (_, diagnostics) = check(source);
+        assert_eq!(diagnostics, vec![]);
+        let program = Program::parse(source).unwrap().parse_exprs().unwrap();
+        let error = program
+            .run(&mut "".as_bytes(), &mut Vec::new())
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "an Int can't be raised to a negative power at 32..42"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let (_, diagnostics) = check(
+            r#"
+            VARIABLES: i: Int, s: String
+            i := s
+            READ: j
+            WHILE i
+                PRINT: SIN s
+            DONE
+            "#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    "i of type Int can't hold a value of type String".to_string(),
+                    "i := s"
+                ),
+                ("variable `j` is not declared".to_string(), "READ: j"),
+                (
+                    "the condition is of type Int instead of Bool".to_string(),
+                    "WHILE i\n                PRINT: SIN s\n            DONE"
+                ),
+                (
+                    "`SIN` is not defined for String".to_string(),
+                    "PRINT: SIN s"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_cascading_errors() {
+        let (_, diagnostics) = check("VARIABLES: b: Bool\nb := y\nIF y THEN\nFI");
+        assert_eq!(
+            diagnostics,
+            vec![
+                ("variable `y` is not declared".to_string(), "b := y"),
+                ("variable `y` is not declared".to_string(), "IF y THEN\nFI"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use crate::{Stmt, VarName};
+use expr_parser::{Expr, Fold, Visitor, VisitorMut};
+
+/// Walks statements and the expressions in them by reference.
+///
+/// The expressions are handed to the [`Visitor`] methods, so one implementation
+/// can look at both levels. Variables written by `:=` and `READ` are reported
+/// as targets, separately from the variable names read by the expressions.
+pub trait StmtVisitor: Visitor {
+    fn visit_block(&mut self, stmts: &[Stmt<Expr>]) {
+        walk_block(self, stmts)
+    }
+
+    fn visit_stmt(&mut self, s: &Stmt<Expr>) {
+        walk_stmt(self, s)
+    }
+
+    fn visit_target(&mut self, _: &VarName) {}
+}
+
+pub fn walk_block<V: StmtVisitor + ?Sized>(v: &mut V, stmts: &[Stmt<Expr>]) {
+    for s in stmts {
+        v.visit_stmt(s)
+    }
+}
+
+pub fn walk_stmt<V: StmtVisitor + ?Sized>(v: &mut V, s: &Stmt<Expr>) {
+    match s {
+        Stmt::Assign(var_name, e) => {
+            v.visit_expr(e);
+            v.visit_target(var_name);
+        }
+        Stmt::Print(e) => v.visit_expr(e),
+        Stmt::Read(var_name) => v.visit_target(var_name),
+        Stmt::If(cond, inner) | Stmt::While(cond, inner) => {
+            v.visit_expr(cond);
+            v.visit_block(inner);
+        }
+        Stmt::IfElse(cond, inner, else_inner) => {
+            v.visit_expr(cond);
+            v.visit_block(inner);
+            v.visit_block(else_inner);
+        }
+    }
+}
+
+/// Walks statements and the expressions in them by mutable reference, see [`StmtVisitor`].
+pub trait StmtVisitorMut: VisitorMut {
+    fn visit_block_mut(&mut self, stmts: &mut Vec<Stmt<Expr>>) {
+        walk_block_mut(self, stmts)
+    }
+
+    fn visit_stmt_mut(&mut self, s: &mut Stmt<Expr>) {
+        walk_stmt_mut(self, s)
+    }
+
+    fn visit_target_mut(&mut self, _: &mut VarName) {}
+}
+
+pub fn walk_block_mut<V: StmtVisitorMut + ?Sized>(v: &mut V, stmts: &mut Vec<Stmt<Expr>>) {
+    for s in stmts {
+        v.visit_stmt_mut(s)
+    }
+}
+
+pub fn walk_stmt_mut<V: StmtVisitorMut + ?Sized>(v: &mut V, s: &mut Stmt<Expr>) {
+    match s {
+        Stmt::Assign(var_name, e) => {
+            v.visit_expr_mut(e);
+            v.visit_target_mut(var_name);
+        }
+        Stmt::Print(e) => v.visit_expr_mut(e),
+        Stmt::Read(var_name) => v.visit_target_mut(var_name),
+        Stmt::If(cond, inner) | Stmt::While(cond, inner) => {
+            v.visit_expr_mut(cond);
+            v.visit_block_mut(inner);
+        }
+        Stmt::IfElse(cond, inner, else_inner) => {
+            v.visit_expr_mut(cond);
+            v.visit_block_mut(inner);
+            v.visit_block_mut(else_inner);
+        }
+    }
+}
+
+/// Rebuilds statements bottom-up, see [`Fold`].
+///
+/// [`StmtFold::fold_block`] may return a different number of statements
+/// than it was given, to remove or to inline statements.
+pub trait StmtFold: Fold {
+    fn fold_block(&mut self, stmts: Vec<Stmt<Expr>>) -> Vec<Stmt<Expr>> {
+        stmts.into_iter().map(|s| self.fold_stmt(s)).collect()
+    }
+
+    fn fold_stmt(&mut self, s: Stmt<Expr>) -> Stmt<Expr> {
+        fold_stmt_children(self, s)
+    }
+}
+
+pub fn fold_stmt_children<F: StmtFold + ?Sized>(f: &mut F, s: Stmt<Expr>) -> Stmt<Expr> {
+    match s {
+        Stmt::Assign(var_name, e) => Stmt::Assign(var_name, f.fold_expr(e)),
+        Stmt::Print(e) => Stmt::Print(f.fold_expr(e)),
+        Stmt::Read(var_name) => Stmt::Read(var_name),
+        Stmt::If(cond, inner) => {
+            let cond = f.fold_expr(cond);
+            Stmt::If(cond, f.fold_block(inner))
+        }
+        Stmt::IfElse(cond, inner, else_inner) => {
+            let cond = f.fold_expr(cond);
+            let inner = f.fold_block(inner);
+            Stmt::IfElse(cond, inner, f.fold_block(else_inner))
+        }
+        Stmt::While(cond, inner) => {
+            let cond = f.fold_expr(cond);
+            Stmt::While(cond, f.fold_block(inner))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Program;
+    use expr_parser::{walk_expr, Value};
+
+    const SOURCE: &str = r"
+    READ: n
+    i := 0
+    WHILE i < n
+        IF i MOD 2 = 0 THEN
+            PRINT: i * 2
+        ELSE
+            j := -i
+        END_IF
+        i := i + 1
+    DONE
+    ";
+
+    fn program() -> Program<Expr> {
+        Program::parse(SOURCE).unwrap().parse_exprs().unwrap()
+    }
+
+    #[test]
+    fn visitor() {
+        #[derive(Default)]
+        struct Names {
+            read: Vec<String>,
+            written: Vec<String>,
+            exprs: usize,
+        }
+        impl Visitor for Names {
+            fn visit_expr(&mut self, e: &Expr) {
+                self.exprs += 1;
+                walk_expr(self, e)
+            }
+
+            fn visit_var_name(&mut self, name: &VarName) {
+                self.read.push(name.to_string())
+            }
+        }
+        impl StmtVisitor for Names {
+            fn visit_target(&mut self, name: &VarName) {
+                self.written.push(name.to_string())
+            }
+        }
+
+        let mut names = Names::default();
+        names.visit_block(program().stmts());
+        assert_eq!(names.read, vec!["i", "n", "i", "i", "i", "i"]);
+        assert_eq!(names.written, vec!["n", "i", "j", "i"]);
+        assert_eq!(names.exprs, program().exprs_pre_order().count());
+    }
+
+    #[test]
+    fn visitor_mut() {
+        struct Double;
+        impl VisitorMut for Double {
+            fn visit_value_mut(&mut self, v: &mut Value) {
+                if let Value::Int(x) = v {
+                    *x *= 2
+                }
+            }
+        }
+        impl StmtVisitorMut for Double {}
+
+        let mut program = program();
+        Double.visit_block_mut(program.stmts_mut());
+        assert_eq!(program.stmts()[1], Stmt::Assign("i".into(), 0.into()));
+        assert_eq!(program.exprs_pre_order().last().unwrap(), &Expr::from(2));
+    }
+
+    #[test]
+    fn fold() {
+        struct DropPrints;
+        impl Fold for DropPrints {}
+        impl StmtFold for DropPrints {
+            fn fold_block(&mut self, stmts: Vec<Stmt<Expr>>) -> Vec<Stmt<Expr>> {
+                stmts
+                    .into_iter()
+                    .filter(|s| !matches!(s, Stmt::Print(_)))
+                    .map(|s| self.fold_stmt(s))
+                    .collect()
+            }
+        }
+
+        let stmts = DropPrints.fold_block(program().stmts().to_vec());
+        assert_eq!(Stmt::pre_order(&stmts).count(), 6);
+        assert!(Stmt::pre_order(&stmts).all(|s| !matches!(s, Stmt::Print(_))));
+    }
+
+    #[test]
+    fn exprs_post_order() {
+        let program = Program::parse("a := -b\nPRINT: a + 1").unwrap();
+        let program = program.parse_exprs().unwrap();
+        let exprs: Vec<_> = program.exprs_post_order().map(Expr::to_string).collect();
+        assert_eq!(exprs, vec!["b", "-b", "a", "1", "a + 1"]);
+    }
+}
@@ -0,0 +1,231 @@
+use crate::bytecode::{Bytecode, Instr};
+use crate::interpreter::{check_assign, read_value};
+use crate::{Input, Output, RuntimeError, RuntimeErrorKind};
+use expr_parser::{Env, EvalError, Value};
+
+impl Bytecode {
+    /// Executes the program, with the same results as [`Program::run`](crate::Program::run).
+    pub fn run(
+        &self,
+        input: &mut impl Input,
+        output: &mut impl Output,
+    ) -> Result<Env, RuntimeError> {
+        self.run_with(Env::new(), input, output)
+    }
+
+    /// Executes the program like [`Program::run_with`](crate::Program::run_with).
+    pub fn run_with(
+        &self,
+        mut env: Env,
+        input: &mut impl Input,
+        output: &mut impl Output,
+    ) -> Result<Env, RuntimeError> {
+        let slots = self
+            .slots
+            .iter()
+            .map(|slot| {
+                env.get(&slot.name)
+                    .cloned()
+                    .or_else(|| slot.ty.as_ref()?.default_value())
+            })
+            .collect();
+        let mut vm = Vm {
+            bytecode: self,
+            slots,
+            stack: Vec::new(),
+            pc: 0,
+        };
+        while vm.pc < self.code.len() {
+            let pc = vm.pc;
+            vm.step(&env, input, output)
+                .map_err(|kind| RuntimeError::new(self.span(pc), kind))?;
+        }
+        for (slot, value) in self.slots.iter().zip(vm.slots) {
+            if let Some(value) = value {
+                env.set(slot.name.clone(), value);
+            }
+        }
+        Ok(env)
+    }
+}
+
+struct Vm<'a> {
+    bytecode: &'a Bytecode,
+    /// The values of the variables, `None` until they are bound.
+    slots: Vec<Option<Value>>,
+    stack: Vec<Value>,
+    pc: usize,
+}
+
+impl Vm<'_> {
+    fn pop(&mut self) -> Value {
+        self.stack
+            .pop()
+            .expect("compiled and loaded bytecode balances the stack")
+    }
+
+    /// Executes the instruction at `pc`, `env` supplies the random numbers.
+    fn step(
+        &mut self,
+        env: &Env,
+        input: &mut impl Input,
+        output: &mut impl Output,
+    ) -> Result<(), RuntimeErrorKind> {
+        let bytecode = self.bytecode;
+        let instr = bytecode.code[self.pc];
+        self.pc += 1;
+        match instr {
+            Instr::Const(i) => self.stack.push(bytecode.constants[i as usize].clone()),
+            Instr::Load(i) => match &self.slots[i as usize] {
+                Some(x) => self.stack.push(x.clone()),
+                None => {
+                    let name = bytecode.slots[i as usize].name.clone();
+                    return Err(EvalError::Unbound(name).into());
+                }
+            },
+            Instr::Store(i) => {
+                let slot = &bytecode.slots[i as usize];
+                let value = check_assign(&slot.name, slot.ty.as_ref(), self.pop())?;
+                self.slots[i as usize] = Some(value);
+            }
+            Instr::Read(i) => {
+                let slot = &bytecode.slots[i as usize];
+                let value = read_value(input, &slot.name, slot.ty.as_ref())?;
+                self.slots[i as usize] = Some(value);
+            }
+            Instr::Print => output.print(&self.pop())?,
+            Instr::Unary(op) => {
+                let x = self.pop().unary(op, env)?;
+                self.stack.push(x);
+            }
+            Instr::Binary(op) => {
+                let rhs = self.pop();
+                let x = self.pop().binary(op, rhs)?;
+                self.stack.push(x);
+            }
+            Instr::Index => {
+                let at = self.pop();
+                let x = self.pop().index(&at)?;
+                self.stack.push(x);
+            }
+            Instr::Slice => {
+                let (end, start) = (self.pop(), self.pop());
+                let x = self.pop().slice(&start, &end)?;
+                self.stack.push(x);
+            }
+            Instr::Jump(to) => self.pc = to as usize,
+            Instr::JumpUnless(to) => match self.pop() {
+                Value::Boolean(true) => {}
+                Value::Boolean(false) => self.pc = to as usize,
+                x => return Err(RuntimeErrorKind::NotABool(x)),
+            },
+            Instr::JumpIfKeep(x, to) => {
+                if self.stack.last() == Some(&Value::Boolean(x)) {
+                    self.pc = to as usize
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Program;
+    use expr_parser::Expr;
+
+    fn program(source: &str) -> Program<Expr> {
+        Program::parse(source).unwrap().parse_exprs().unwrap()
+    }
+
+    /// Runs the program on both the interpreter and the VM, which must agree.
+    fn run(source: &str, input: &str) -> Result<String, String> {
+        let program = program(source);
+        let (mut interpreted, mut executed) = (Vec::new(), Vec::new());
+        let expected = program
+            .run(&mut input.as_bytes(), &mut interpreted)
+            .map_err(|e| e.to_string());
+        let result = program
+            .compile()
+            .run(&mut input.as_bytes(), &mut executed)
+            .map_err(|e| e.to_string());
+        assert_eq!(result.is_ok(), expected.is_ok());
+        assert_eq!(executed, interpreted);
+        match (result, expected) {
+            (Err(e), Err(expected)) => {
+                assert_eq!(e, expected);
+                Err(e)
+            }
+            _ => Ok(String::from_utf8(executed).unwrap()),
+        }
+    }
+
+    #[test]
+    fn sum() {
+        let source = r"
+        VARIABLES: n, i, sum: Int
+        READ: n
+        WHILE i < n
+            i := i + 1
+            sum := sum + i
+        DONE
+        PRINT: sum
+        ";
+        assert_eq!(run(source, "100\n"), Ok("5050\n".to_string()));
+    }
+
+    #[test]
+    fn branches() {
+        let source = r#"
+        VARIABLES: s: String, x: Float
+        READ: s
+        IF s /= "" AND (s @ "a" OR s[0] = 'b') THEN
+            PRINT: UPPER s[0:2]
+        ELSE
+            IF s = "" THEN
+                PRINT: "empty"
+            FI
+        FI
+        x := 1
+        PRINT: x / 4
+        "#;
+        assert_eq!(run(source, "bcd\n"), Ok("BC\n0.25\n".to_string()));
+        assert_eq!(run(source, "\n"), Ok("empty\n0.25\n".to_string()));
+        assert_eq!(run(source, "xyz\n"), Ok("0.25\n".to_string()));
+    }
+
+    #[test]
+    fn short_circuit() {
+        let source = "i := 0\nIF i /= 0 AND 10 DIV i > 1 THEN\nFI\nPRINT: i = 0 OR 1 DIV i = 0";
+        assert_eq!(run(source, ""), Ok("TRUE\n".to_string()));
+    }
+
+    #[test]
+    fn errors() {
+        let err = |source, input| run(source, input).unwrap_err();
+        assert_eq!(err("PRINT: 1 DIV 0", ""), "division by zero at 0..14");
+        assert_eq!(
+            err("VARIABLES: i: Int\nIF i THEN\nFI", ""),
+            "the condition is 0 instead of a Bool at 18..30"
+        );
+        assert_eq!(
+            err("VARIABLES: i: Int\ni := \"a\"", ""),
+            "i of type Int can't hold \"a\" at 18..26"
+        );
+        assert_eq!(err("PRINT: y", ""), "variable `y` is not bound at 0..8");
+        assert_eq!(
+            err("READ: y", ""),
+            "the input ended before y could be read at 0..7"
+        );
+    }
+
+    #[test]
+    fn final_values() {
+        let env = program("VARIABLES: x: Float\nREAD: y\nx := 2")
+            .compile()
+            .run(&mut "abc\n".as_bytes(), &mut Vec::new())
+            .unwrap();
+        assert_eq!(env.get(&"x".into()), Some(&2.0.into()));
+        assert_eq!(env.get(&"y".into()), Some(&"abc".into()));
+    }
+}
@@ -1,14 +1,16 @@
 #[macro_use]
 extern crate lalrpop_util;
 
-mod inner_type;
 mod name;
+mod tokens;
 mod variable;
 
-lalrpop_mod!(pub parsers);
+// The patterns of the tokens are only used in submodules of the generated code.
+lalrpop_mod!(#[allow(clippy::all, unused_imports)] pub parsers);
 
-pub use inner_type::{FromValueError, VarType};
+pub use expr_parser::Type;
 pub use name::VarName;
+pub use tokens::{tokens, Spanned};
 pub use variable::Variable;
 
 pub use parsers::{
@@ -25,7 +27,7 @@ mod tests {
             #[test]
             fn $test_name() {
                 let parser = $parser::new();
-                let result = parser.parse($in).unwrap();
+                let result = parser.parse(tokens($in)).unwrap();
                 assert_eq!(result, $res)
             }
         };
@@ -35,44 +37,70 @@ mod tests {
 
     test!(var_foo123 using VarNameParser: "_foo123" -> VarName::from("_foo123"));
 
-    test!(single_var using VarDeclarationParser: "a: Int" -> vec![Variable::new("a".into(), VarType::Int)]);
+    test!(single_var using VarDeclarationParser: "a: Int" -> vec![Variable::new("a".into(), Type::Int)]);
 
-    test!(same_type using VarDeclarationParser: "a, b: Int" -> vec![Variable::new("a".into(), VarType::Int), Variable::new("b".into(), VarType::Int)]);
+    test!(same_type using VarDeclarationParser: "a, b: Int" -> vec![Variable::new("a".into(), Type::Int), Variable::new("b".into(), Type::Int)]);
 
-    test!(diff_type using VarDeclarationParser: "a: Int, b: Float" -> vec![Variable::new("a".into(), VarType::Int), Variable::new("b".into(), VarType::Float)]);
+    test!(diff_type using VarDeclarationParser: "a: Int, b: Float" -> vec![Variable::new("a".into(), Type::Int), Variable::new("b".into(), Type::Float)]);
 
     test!(all_kind_of using VarDeclarationParser: "a, b: Int, c: Float, d: String, e, f: Bool, g: Char, h: Char" -> vec![
-        Variable::new("a".into(), VarType::Int),
-        Variable::new("b".into(), VarType::Int),
-        Variable::new("c".into(), VarType::Float),
-        Variable::new("d".into(), VarType::String),
-        Variable::new("e".into(), VarType::Bool),
-        Variable::new("f".into(), VarType::Bool),
-        Variable::new("g".into(), VarType::Char),
-        Variable::new("h".into(), VarType::Char),
+        Variable::new("a".into(), Type::Int),
+        Variable::new("b".into(), Type::Int),
+        Variable::new("c".into(), Type::Float),
+        Variable::new("d".into(), Type::String),
+        Variable::new("e".into(), Type::Bool),
+        Variable::new("f".into(), Type::Bool),
+        Variable::new("g".into(), Type::Char),
+        Variable::new("h".into(), Type::Char),
     ]);
 
     test!(full_declaration using VariablesParser: "VARIABLES: a, b: Int, c: Float, d: String, e, f: Bool, g: Char, h: Char" -> vec![
-        Variable::new("a".into(), VarType::Int),
-        Variable::new("b".into(), VarType::Int),
-        Variable::new("c".into(), VarType::Float),
-        Variable::new("d".into(), VarType::String),
-        Variable::new("e".into(), VarType::Bool),
-        Variable::new("f".into(), VarType::Bool),
-        Variable::new("g".into(), VarType::Char),
-        Variable::new("h".into(), VarType::Char),
+        Variable::new("a".into(), Type::Int),
+        Variable::new("b".into(), Type::Int),
+        Variable::new("c".into(), Type::Float),
+        Variable::new("d".into(), Type::String),
+        Variable::new("e".into(), Type::Bool),
+        Variable::new("f".into(), Type::Bool),
+        Variable::new("g".into(), Type::Char),
+        Variable::new("h".into(), Type::Char),
     ]);
 
     test!(full_declaration2 using MaybeVariablesParser: "VARIABLES: a, b: Int, c: Float, d: String, e, f: Bool, g: Char, h: Char" -> vec![
-        Variable::new("a".into(), VarType::Int),
-        Variable::new("b".into(), VarType::Int),
-        Variable::new("c".into(), VarType::Float),
-        Variable::new("d".into(), VarType::String),
-        Variable::new("e".into(), VarType::Bool),
-        Variable::new("f".into(), VarType::Bool),
-        Variable::new("g".into(), VarType::Char),
-        Variable::new("h".into(), VarType::Char),
+        Variable::new("a".into(), Type::Int),
+        Variable::new("b".into(), Type::Int),
+        Variable::new("c".into(), Type::Float),
+        Variable::new("d".into(), Type::String),
+        Variable::new("e".into(), Type::Bool),
+        Variable::new("f".into(), Type::Bool),
+        Variable::new("g".into(), Type::Char),
+        Variable::new("h".into(), Type::Char),
     ]);
 
     test!(empty_declaration using MaybeVariablesParser: "" -> vec![]);
+
+    test!(hungarian_declaration using VariablesParser: "VÁLTOZÓK: a: Egész, b: Szöveg" -> vec![
+        Variable::new("a".into(), Type::Int),
+        Variable::new("b".into(), Type::String),
+    ]);
+
+    test!(multiline_declaration using VariablesParser: "VARIABLES: // two\n  ki, be: Int,\n  s: String" -> vec![
+        Variable::new("ki".into(), Type::Int),
+        Variable::new("be".into(), Type::Int),
+        Variable::new("s".into(), Type::String),
+    ]);
+
+    #[test]
+    fn type_names_parse_back() {
+        use expr_parser::Dialect;
+        for t in &[Type::Bool, Type::Int, Type::Float, Type::Char, Type::String] {
+            for &dialect in &[Dialect::English, Dialect::Hungarian] {
+                let declaration = format!("a: {}", t.name(dialect));
+                let expected = vec![Variable::new("a".into(), t.clone())];
+                assert_eq!(
+                    VarDeclarationParser::new().parse(tokens(&declaration)),
+                    Ok(expected)
+                );
+            }
+        }
+    }
 }
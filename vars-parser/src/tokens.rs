@@ -0,0 +1,23 @@
+use lexer::{Keyword, Token, TokenKind};
+
+/// A token with its byte range, the items the generated parsers consume.
+pub type Spanned<'a> = Result<(usize, Token<'a>, usize), &'static str>;
+
+/// The tokens of a declaration, prepared for the parsers of this crate.
+///
+/// Whitespace, newlines and comments are dropped. The keywords of statements
+/// are variable names, while those of expressions are reserved, as in
+/// [`expr_parser::tokens`].
+pub fn tokens(s: &str) -> impl Iterator<Item = Spanned<'_>> {
+    lexer::lex(s)
+        .filter(|t| !t.is_trivia() && t.kind != TokenKind::Newline)
+        .map(|mut t| {
+            if let TokenKind::Keyword(keyword) = t.kind {
+                let reserved = keyword == Keyword::Variables || keyword.is_type();
+                if !reserved && expr_parser::Keyword::of(keyword).is_none() {
+                    t.kind = TokenKind::Word;
+                }
+            }
+            Ok((t.start, t, t.end()))
+        })
+}
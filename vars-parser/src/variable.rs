@@ -1,10 +1,12 @@
-use crate::{VarName, VarType};
+use crate::{Type, VarName};
 use derive_more::{Constructor, From};
 
 #[derive(Debug, PartialEq, Clone, Constructor, From)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Variable {
     name: VarName,
-    its_type: VarType,
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    its_type: Type,
 }
 
 impl Variable {
@@ -12,7 +14,21 @@ impl Variable {
         &self.name
     }
 
-    pub fn its_type(&self) -> &VarType {
+    pub fn its_type(&self) -> &Type {
         &self.its_type
     }
 }
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn serde() {
+        let var = Variable::new("a".into(), Type::Float);
+        let json = json!({"name": "a", "type": "Float"});
+        assert_eq!(serde_json::to_value(&var).unwrap(), json);
+        assert_eq!(serde_json::from_value::<Variable>(json).unwrap(), var);
+    }
+}